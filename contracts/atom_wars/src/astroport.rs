@@ -0,0 +1,168 @@
+// Minimal copies of the Astroport pair interface, just enough for Atom Wars to talk to pair contracts
+// without pulling in the whole astroport crate.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_binary, Coin, CosmosMsg, Decimal, Deps, StdResult, Uint128, WasmMsg};
+
+#[cw_serde]
+pub enum PairExecuteMsg {
+    ProvideLiquidity {
+        assets: Vec<Asset>,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: Option<String>,
+    },
+    WithdrawLiquidity {
+        assets: Vec<Asset>,
+        min_assets_to_receive: Option<Vec<Asset>>,
+    },
+}
+
+#[cw_serde]
+pub enum PairQueryMsg {
+    Pair {},
+    Pool {},
+    Share { amount: Uint128 },
+}
+
+#[cw_serde]
+pub enum AssetInfo {
+    Token { contract_addr: String },
+    NativeToken { denom: String },
+}
+
+#[cw_serde]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+impl Asset {
+    pub fn native(denom: &str, amount: Uint128) -> Self {
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: denom.to_string(),
+            },
+            amount,
+        }
+    }
+
+    // Returns the amount of the asset if it is the native asset with the given denom
+    pub fn native_amount(&self, denom: &str) -> Option<Uint128> {
+        match &self.info {
+            AssetInfo::NativeToken { denom: asset_denom } if asset_denom == denom => {
+                Some(self.amount)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cw_serde]
+pub struct PairInfo {
+    pub asset_infos: Vec<AssetInfo>,
+    pub contract_addr: String,
+    // Liquidity tokens are native token factory denoms
+    pub liquidity_token: String,
+}
+
+impl PairInfo {
+    // Returns the denoms of the native assets in the pair
+    pub fn native_denoms(&self) -> Vec<String> {
+        self.asset_infos
+            .iter()
+            .filter_map(|asset_info| match asset_info {
+                AssetInfo::NativeToken { denom } => Some(denom.clone()),
+                AssetInfo::Token { .. } => None,
+            })
+            .collect()
+    }
+}
+
+#[cw_serde]
+pub struct PoolResponse {
+    pub assets: Vec<Asset>,
+    pub total_share: Uint128,
+}
+
+// Returns the pair's reserves of the two denoms
+pub fn query_reserves(
+    deps: Deps,
+    pair: &str,
+    denom: &str,
+    counterparty_denom: &str,
+) -> StdResult<(Uint128, Uint128)> {
+    let pool: PoolResponse = deps
+        .querier
+        .query_wasm_smart(pair, &PairQueryMsg::Pool {})?;
+
+    let reserve = |denom: &str| {
+        pool.assets
+            .iter()
+            .find_map(|asset| asset.native_amount(denom))
+            .unwrap_or_else(Uint128::zero)
+    };
+
+    Ok((reserve(denom), reserve(counterparty_denom)))
+}
+
+// Provides both sides of the pair's liquidity. The pair rejects the provision if the price has moved by more than
+// `slippage_tolerance` from the ratio of the assets provided.
+pub fn provide_liquidity_msg(
+    pair: &str,
+    assets: Vec<Coin>,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    // Funds have to be sorted by denom
+    let mut funds = assets.clone();
+    funds.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    Ok(WasmMsg::Execute {
+        contract_addr: pair.to_string(),
+        msg: to_json_binary(&PairExecuteMsg::ProvideLiquidity {
+            assets: assets
+                .iter()
+                .map(|coin| Asset::native(&coin.denom, coin.amount))
+                .collect(),
+            slippage_tolerance: Some(slippage_tolerance),
+            auto_stake: Some(false),
+            receiver: None,
+        })?,
+        funds,
+    }
+    .into())
+}
+
+// Burns `lp_amount` liquidity tokens, requiring at least `1 - slippage_tolerance` of the assets they are currently worth
+pub fn withdraw_liquidity_msg(
+    deps: Deps,
+    pair: &str,
+    lp_token: &str,
+    lp_amount: Uint128,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    let expected_assets: Vec<Asset> = deps
+        .querier
+        .query_wasm_smart(pair, &PairQueryMsg::Share { amount: lp_amount })?;
+
+    let min_assets_to_receive = expected_assets
+        .into_iter()
+        .map(|asset| Asset {
+            info: asset.info,
+            amount: asset.amount * (Decimal::one() - slippage_tolerance),
+        })
+        .collect();
+
+    Ok(WasmMsg::Execute {
+        contract_addr: pair.to_string(),
+        msg: to_json_binary(&PairExecuteMsg::WithdrawLiquidity {
+            assets: vec![],
+            min_assets_to_receive: Some(min_assets_to_receive),
+        })?,
+        funds: vec![Coin {
+            denom: lp_token.to_string(),
+            amount: lp_amount,
+        }],
+    }
+    .into())
+}
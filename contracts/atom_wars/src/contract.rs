@@ -1,40 +1,216 @@
 // MAIN TODOS:
 // - Query methods! We want a very complete set so that it is easy for third party tribute contracts
-// - Tests!
-// - Add real covenant logic
-// - Make it work for separate tranches
-// - Question: How to handle the case where a proposal is executed but the covenant fails?
-// - Covenant Question: How to deal with someone using MEV to skew the pool ratio right before the liquidity is pulled? Streaming the liquidity pull? You'd have to set up a cron job for that.
 // - Covenant Question: Can people sandwich this whole thing - covenant system has price limits - but we should allow people to retry executing the prop during the round
 
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    entry_point, to_json_binary, BankMsg, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response,
-    StdError, StdResult, Uint128,
+    to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env, Event,
+    MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128,
+    WasmMsg,
 };
+use cw_storage_plus::{Bound, Item, Map};
+use osmosis_std::types::cosmos::distribution::v1beta1::MsgFundCommunityPool;
+use osmosis_std::types::osmosis::poolmanager::v1beta1::PoolmanagerQuerier;
 
+use crate::astroport::{self, PairInfo, PairQueryMsg};
+use crate::covenant;
 use crate::error::ContractError;
-use crate::msg::{CountResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::msg::{
+    AllNftInfoResponse, Approval, ApprovalResponse, ApprovalsResponse, ApprovedPoolsResponse,
+    ConsumerChainsResponse, ContractInfo, CurrentRoundResponse, Cw721ReceiveMsg, ExecuteMsg,
+    InfoResponse, InstantiateMsg, LockedTokensResponse, NftInfoResponse, NumTokensResponse,
+    OperatorResponse, OperatorsResponse, OwnerOfResponse, ProposalDepositResponse,
+    ProposalPowerByTierResponse, ProposalResponse, QueryMsg, ReceiverExecuteMsg, RoundResponse,
+    RoundResultResponse, StandingProposalsResponse, SudoMsg, TierPower, TokensResponse,
+    TopNProposalsResponse, TotalLockedResponse, TotalPowerAtHeightResponse,
+    TotalPowerAtRoundResponse, TranchesResponse, UserVoteResponse, VotingPowerAtHeightResponse,
+    VotingPowerAtRoundResponse,
+};
+use crate::osmosis;
 use crate::state::{
-    Constants, LockEntry, Proposal, Round, Vote, CONSTANTS, LOCKS_MAP, LOCK_ID, PROPOSAL_MAP,
-    PROPS_BY_SCORE, PROP_ID, ROUND_ID, ROUND_MAP, TOTAL_POWER_VOTING, VOTE_MAP,
+    Constants, ConsumerChain, CounterpartyDeposit, CovenantParams, DeploymentMethod, DepositStatus,
+    Dex, ExecutionStatus, Expiration, FailedAllocationPolicy, ForfeitedDepositDestination,
+    FundedProposal, LiquidityPosition, LockEntry, LockPower, Proposal, ProposalCreationMode,
+    ProposalDeposit, ProposalEligibility, QuorumFailurePolicy, RegistryEntry, Round, RoundResult,
+    StandingProposal, Tranche, Vote, ACTIVE_POSITIONS, APPROVED_POOLS, CONSTANTS, CONSUMER_CHAINS,
+    DROPPED_POWER, DUE_WITHDRAWALS, EXPIRED_LOCKED, EXTRA_POOL, LOCKED_TOKENS, LOCKS_MAP,
+    LOCK_ENDS, LOCK_EXPIRIES, LOCK_ID, LOCK_OWNERS, NUM_TOKENS, OPERATORS, PENDING_EXECUTION,
+    PENDING_WITHDRAWAL, POWER_DROPS, PROPOSAL_COUNT, PROPOSAL_DEPOSITS, PROPOSAL_MAP,
+    PROPS_BY_SCORE, PROP_ID, PROP_POWER_BY_TIER, ROUNDS_BY_HEIGHT, ROUND_HEIGHTS, ROUND_ID,
+    ROUND_MAP, ROUND_POWER, ROUND_RESULTS, STANDING_PROPOSALS, STICKY_VOTERS, STICKY_VOTES_CURSOR,
+    STREAMED_WITHDRAWALS, SUCCESSORS, TICK_CURSOR, TOKEN_APPROVALS, TOTAL_LOCKED,
+    TOTAL_POWER_VOTING, TOTAL_TOKENS_VOTING, TRANCHE_MAP, VOTE_MAP,
 };
 
-#[entry_point]
+pub const INSTANTIATE_COVENANT_REPLY_ID: u64 = 1;
+pub const OSMOSIS_JOIN_POOL_REPLY_ID: u64 = 2;
+pub const OSMOSIS_CREATE_POSITION_REPLY_ID: u64 = 3;
+pub const ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID: u64 = 4;
+pub const ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID: u64 = 5;
+pub const OSMOSIS_ADD_TO_POSITION_REPLY_ID: u64 = 6;
+pub const WITHDRAW_TRANCHE_REPLY_ID: u64 = 7;
+pub const EXECUTE_PROPOSAL_REPLY_ID: u64 = 8;
+pub const OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID: u64 = 9;
+
+// Maximum number of rounds of a tranche ended by a single call. Tranches that are further behind are caught up by
+// several EndRound (or Tick) calls, so that none of them runs out of gas.
+pub const MAX_ROUNDS_ENDED_PER_CALL: usize = 10;
+
+// Maximum number of tranches withdrawn by a single ProcessWithdrawals
+pub const DEFAULT_PROCESS_WITHDRAWALS_LIMIT: u32 = 10;
+
+// Maximum number of proposals executed in each tranche by a single Tick
+pub const DEFAULT_TICK_EXECUTION_LIMIT: u32 = 5;
+
+// Maximum number of sticky votes carried over by a single ApplyStickyVotes
+pub const DEFAULT_APPLY_STICKY_VOTES_LIMIT: u32 = 30;
+
+// Maximum lengths of the proposal metadata, in bytes
+pub const MAX_TITLE_LENGTH: usize = 100;
+pub const MAX_DESCRIPTION_LENGTH: usize = 5000;
+pub const MAX_URL_LENGTH: usize = 500;
+pub const DEFAULT_TOKENS_QUERY_LIMIT: u32 = 30;
+pub const MAX_TOKENS_QUERY_LIMIT: u32 = 100;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    // Check that the covenant template is a JSON object, since proposals' params get merged into it
+    covenant::parse_template(&msg.covenant_instantiate_template)?;
+    covenant::withdraw_msg(&msg.covenant_withdraw_msg)?;
+
+    if msg.tranches.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "At least one tranche must be configured",
+        )));
+    }
+
+    if msg.consumer_chain_multiplier < Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Consumer chain multiplier must be at least 1",
+        )));
+    }
+
+    if msg.min_vote_share > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Minimum vote share must be between 0 and 1",
+        )));
+    }
+
+    if msg.max_allocation_share.is_zero() || msg.max_allocation_share > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Maximum allocation share must be greater than 0 and at most 1",
+        )));
+    }
+
+    if msg.quorum > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Quorum must be between 0 and 1",
+        )));
+    }
+
+    if msg.deposit_refund_vote_share > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Deposit refund vote share must be between 0 and 1",
+        )));
+    }
+
+    if matches!(&msg.proposal_deposit, Some(deposit) if deposit.amount.is_zero()) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal deposit must be greater than zero",
+        )));
+    }
+
+    if msg.max_standing_rounds == 0 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Maximum number of standing rounds must be at least one",
+        )));
+    }
+
+    if msg.max_proposals_per_address == Some(0) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Maximum number of proposals per address must be at least one",
+        )));
+    }
+
     let state = Constants {
         denom: msg.denom.clone(),
-        round_length: msg.round_length,
-        total_pool: msg.total_pool,
+        admin: msg
+            .admin
+            .map(|admin| deps.api.addr_validate(&admin))
+            .transpose()?
+            .unwrap_or_else(|| info.sender.clone()),
+        governance: msg
+            .governance
+            .map(|governance| deps.api.addr_validate(&governance))
+            .transpose()?,
+        proposal_creation_mode: msg.proposal_creation_mode,
+        pool_denom: msg.pool_denom,
+        top_n_props_count: msg.top_n_props_count,
+        covenant_code_id: msg.covenant_code_id,
+        covenant_instantiate_template: msg.covenant_instantiate_template,
+        covenant_withdraw_msg: msg.covenant_withdraw_msg,
+        failed_allocation_policy: msg.failed_allocation_policy,
+        consumer_chain_multiplier: msg.consumer_chain_multiplier,
+        min_vote_share: msg.min_vote_share,
+        max_allocation_share: msg.max_allocation_share,
+        quorum: msg.quorum,
+        quorum_failure_policy: msg.quorum_failure_policy,
+        proposal_deposit: msg.proposal_deposit,
+        deposit_refund_vote_share: msg.deposit_refund_vote_share,
+        forfeited_deposit_destination: msg.forfeited_deposit_destination,
+        max_proposals_per_address: msg.max_proposals_per_address,
+        max_standing_rounds: msg.max_standing_rounds,
+        dao: msg
+            .dao
+            .map(|dao| deps.api.addr_validate(&dao))
+            .transpose()?
+            .unwrap_or_else(|| info.sender.clone()),
+        time_weighted_voting_power: msg.time_weighted_voting_power,
     };
     CONSTANTS.save(deps.storage, &state)?;
+    LOCK_ID.save(deps.storage, &0)?;
+    NUM_TOKENS.save(deps.storage, &0)?;
+    TOTAL_LOCKED.save(deps.storage, &Uint128::zero(), env.block.height)?;
+    EXPIRED_LOCKED.save(deps.storage, &(env.block.time, Uint128::zero()))?;
+    DROPPED_POWER.save(deps.storage, &(env.block.time, Uint128::zero()))?;
+    PROP_ID.save(deps.storage, &0)?;
+
+    // Each tranche has its own pool and round schedule, and starts its first round at its genesis time
+    for (tranche_id, tranche) in msg.tranches.into_iter().enumerate() {
+        let tranche_id = tranche_id as u64;
+        if tranche.round_length == 0 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Round length must be greater than zero",
+            )));
+        }
+
+        let tranche = Tranche {
+            tranche_id,
+            name: tranche.name,
+            total_pool: tranche.total_pool,
+            round_length: tranche.round_length,
+            genesis_time: tranche.genesis_time.unwrap_or(env.block.time),
+            eligibility: tranche.eligibility,
+        };
+        TRANCHE_MAP.save(deps.storage, tranche_id, &tranche)?;
+        start_round(
+            deps.storage,
+            env.block.height,
+            tranche_id,
+            0,
+            round_end(&tranche, 0),
+        )?;
+    }
+
     Ok(Response::new()
         .add_attribute("action", "initialisation")
-        .add_attribute("sender", _info.sender.clone())
+        .add_attribute("sender", info.sender)
         .add_attribute("denom", msg.denom))
 }
 
@@ -45,21 +221,172 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // Rounds are derived from the block time: end the rounds of the tranche that are over before doing anything
+    // else, so that nothing can happen in a round after its end time
+    if let Some(tranche_id) = msg_tranche_id(&msg) {
+        sync_rounds(deps.storage, &env, tranche_id)?;
+    }
+
     match msg {
         ExecuteMsg::LockTokens { lock_duration } => lock_tokens(deps, env, info, lock_duration),
         ExecuteMsg::UnlockTokens {} => unlock_tokens(deps, env, info),
-        ExecuteMsg::CreateProposal { covenant_params } => create_proposal(deps, covenant_params),
-        ExecuteMsg::Vote { proposal_id } => vote(deps, info, proposal_id),
-        ExecuteMsg::EndRound {} => end_round(deps, env, info),
-        // ExecuteMsg::ExecuteProposal { proposal_id } => {
-        //     execute_proposal(deps, env, info, proposal_id)
-        // }
+        ExecuteMsg::CreateProposal {
+            tranche_id,
+            title,
+            description,
+            url,
+            covenant_params,
+            standing_rounds,
+        } => create_proposal(
+            deps,
+            env,
+            info,
+            tranche_id,
+            title,
+            description,
+            url,
+            *covenant_params,
+            standing_rounds,
+        ),
+        ExecuteMsg::EditProposal {
+            tranche_id,
+            prop_id,
+            title,
+            description,
+            url,
+        } => edit_proposal(deps, info, tranche_id, prop_id, title, description, url),
+        ExecuteMsg::WithdrawProposal {
+            tranche_id,
+            prop_id,
+        } => withdraw_proposal(deps, info, tranche_id, prop_id),
+        ExecuteMsg::RetireProposal {
+            tranche_id,
+            prop_id,
+        } => retire_proposal(deps, info, tranche_id, prop_id),
+        ExecuteMsg::Vote {
+            tranche_id,
+            proposal_id,
+        } => vote(deps, info, tranche_id, proposal_id),
+        ExecuteMsg::SetStickyVote { tranche_id, sticky } => {
+            set_sticky_vote(deps, info, tranche_id, sticky)
+        }
+        ExecuteMsg::DesignateSuccessor {
+            tranche_id,
+            round_id,
+            prop_id,
+            successor_prop_id,
+        } => designate_successor(deps, info, tranche_id, round_id, prop_id, successor_prop_id),
+        ExecuteMsg::ApplyStickyVotes { tranche_id, limit } => {
+            apply_sticky_votes(deps, env, info, tranche_id, limit)
+        }
+        ExecuteMsg::EndRound { tranche_id } => end_round(deps, env, info, tranche_id),
+        ExecuteMsg::ExecuteProposal {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => execute_proposal(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::ExpireProposal {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => expire_proposal(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::DepositCounterparty {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => deposit_counterparty(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::RefundCounterparty {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => refund_counterparty(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::WithdrawLiquidity {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => withdraw_liquidity(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::WithdrawTranche {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => withdraw_next_tranche(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::ClaimDeposit {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => claim_deposit(deps, env, info, tranche_id, round_id, prop_id),
+        ExecuteMsg::ProcessWithdrawals { limit } => process_withdrawals(deps, env, info, limit),
+        ExecuteMsg::AddConsumerChain { denom, chain_id } => {
+            add_consumer_chain(deps, info, denom, chain_id)
+        }
+        ExecuteMsg::RemoveConsumerChain { denom } => remove_consumer_chain(deps, info, denom),
+        ExecuteMsg::ApprovePool { entry } => approve_pool(deps, info, entry),
+        ExecuteMsg::RevokePool { entry } => revoke_pool(deps, info, entry),
+        ExecuteMsg::SetProposalCreationMode { mode } => {
+            set_proposal_creation_mode(deps, info, mode)
+        }
+        ExecuteMsg::TransferNft {
+            recipient,
+            token_id,
+        } => transfer_nft(deps, env, info, recipient, token_id),
+        ExecuteMsg::SendNft {
+            contract,
+            token_id,
+            msg,
+        } => send_nft(deps, env, info, contract, token_id, msg),
+        ExecuteMsg::Approve {
+            spender,
+            token_id,
+            expires,
+        } => approve(deps, env, info, spender, token_id, expires),
+        ExecuteMsg::Revoke { spender, token_id } => revoke(deps, env, info, spender, token_id),
+        ExecuteMsg::ApproveAll { operator, expires } => {
+            approve_all(deps, env, info, operator, expires)
+        }
+        ExecuteMsg::RevokeAll { operator } => revoke_all(deps, info, operator),
+    }
+}
+
+// Returns the tranche that the message acts on, if any. EndRound is left out, since it ends the rounds itself.
+fn msg_tranche_id(msg: &ExecuteMsg) -> Option<u64> {
+    match msg {
+        ExecuteMsg::CreateProposal { tranche_id, .. }
+        | ExecuteMsg::EditProposal { tranche_id, .. }
+        | ExecuteMsg::WithdrawProposal { tranche_id, .. }
+        | ExecuteMsg::RetireProposal { tranche_id, .. }
+        | ExecuteMsg::Vote { tranche_id, .. }
+        | ExecuteMsg::SetStickyVote { tranche_id, .. }
+        | ExecuteMsg::DesignateSuccessor { tranche_id, .. }
+        | ExecuteMsg::ApplyStickyVotes { tranche_id, .. }
+        | ExecuteMsg::ExecuteProposal { tranche_id, .. }
+        | ExecuteMsg::ExpireProposal { tranche_id, .. }
+        | ExecuteMsg::DepositCounterparty { tranche_id, .. }
+        | ExecuteMsg::RefundCounterparty { tranche_id, .. }
+        | ExecuteMsg::WithdrawLiquidity { tranche_id, .. }
+        | ExecuteMsg::WithdrawTranche { tranche_id, .. }
+        | ExecuteMsg::ClaimDeposit { tranche_id, .. } => Some(*tranche_id),
+        ExecuteMsg::LockTokens { .. }
+        | ExecuteMsg::UnlockTokens {}
+        | ExecuteMsg::EndRound { .. }
+        | ExecuteMsg::ProcessWithdrawals { .. }
+        | ExecuteMsg::AddConsumerChain { .. }
+        | ExecuteMsg::RemoveConsumerChain { .. }
+        | ExecuteMsg::ApprovePool { .. }
+        | ExecuteMsg::RevokePool { .. }
+        | ExecuteMsg::SetProposalCreationMode { .. }
+        | ExecuteMsg::TransferNft { .. }
+        | ExecuteMsg::SendNft { .. }
+        | ExecuteMsg::Approve { .. }
+        | ExecuteMsg::Revoke { .. }
+        | ExecuteMsg::ApproveAll { .. }
+        | ExecuteMsg::RevokeAll { .. } => None,
     }
 }
 
 // LockTokens(lock_duration):
 //     Receive tokens
 //     Validate against denom whitelist
+//     End the rounds that are over, so that the new lock doesn't count towards their quorum
 //     Create entry in LocksMap
 fn lock_tokens(
     deps: DepsMut,
@@ -89,7 +416,7 @@ fn lock_tokens(
 
     let sent_funds = info
         .funds
-        .get(0)
+        .first()
         .ok_or_else(|| ContractError::Std(StdError::generic_err("Must send exactly one coin")))?;
 
     if sent_funds.denom != CONSTANTS.load(deps.storage)?.denom {
@@ -98,6 +425,8 @@ fn lock_tokens(
         )));
     }
 
+    sync_all_rounds(deps.storage, &env)?;
+
     // Create entry in LocksMap
     let lock_entry = LockEntry {
         funds: sent_funds.clone(),
@@ -106,17 +435,33 @@ fn lock_tokens(
     };
     let lock_id = LOCK_ID.load(deps.storage)?;
     LOCK_ID.save(deps.storage, &(lock_id + 1))?;
-    LOCKS_MAP.save(deps.storage, (info.sender, lock_id), &lock_entry)?;
+    LOCKS_MAP.save(deps.storage, (info.sender.clone(), lock_id), &lock_entry)?;
+    LOCK_OWNERS.save(deps.storage, lock_id, &info.sender)?;
+    NUM_TOKENS.update(deps.storage, |num_tokens| -> StdResult<u64> {
+        Ok(num_tokens + 1)
+    })?;
+
+    // Keep track of the amounts locked, for the quorum and for the voting power queries
+    checkpoint_lock(
+        deps.storage,
+        env.block.height,
+        &info.sender,
+        &lock_entry,
+        true,
+    )?;
 
     Ok(Response::new().add_attribute("action", "lock_tokens"))
 }
 
 // UnlockTokens():
 //     Validate caller
+//     End the rounds that are over, so that the unlocked tokens still count towards their quorum
 //     Validate `lock_end` < now
 //     Send `amount` tokens back to caller
 //     Delete entry from LocksMap
 fn unlock_tokens(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    sync_all_rounds(deps.storage, &env)?;
+
     // Iterate all locks for the caller and unlock them if lock_end < now
     let locks =
         LOCKS_MAP
@@ -125,6 +470,7 @@ fn unlock_tokens(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response,
 
     let mut sends = vec![];
     let mut to_delete = vec![];
+    let mut unlocked = vec![];
 
     for lock in locks {
         let (lock_id, lock_entry) = lock?;
@@ -134,12 +480,28 @@ fn unlock_tokens(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response,
             // Delete entry from LocksMap
 
             to_delete.push((info.sender.clone(), lock_id));
+            unlocked.push(lock_entry);
         }
     }
 
-    // Delete unlocked locks
-    for (addr, lock_id) in to_delete {
-        LOCKS_MAP.remove(deps.storage, (addr, lock_id));
+    // Delete unlocked locks, along with their CW721 approvals
+    for (addr, lock_id) in to_delete.iter() {
+        LOCKS_MAP.remove(deps.storage, (addr.clone(), *lock_id));
+        LOCK_OWNERS.remove(deps.storage, *lock_id);
+        clear_token_approvals(deps.storage, *lock_id)?;
+    }
+    NUM_TOKENS.update(deps.storage, |num_tokens| -> StdResult<u64> {
+        Ok(num_tokens - to_delete.len() as u64)
+    })?;
+
+    for lock_entry in unlocked.iter() {
+        checkpoint_lock(
+            deps.storage,
+            env.block.height,
+            &info.sender,
+            lock_entry,
+            false,
+        )?;
     }
 
     Ok(Response::new()
@@ -150,319 +512,4058 @@ fn unlock_tokens(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response,
         }))
 }
 
-fn validate_covenant_params(_covenant_params: String) -> Result<(), ContractError> {
-    // Validate covenant_params
-    Ok(())
-}
+// Adds (or subtracts, if `add` is false) a lock to the checkpointed locked tokens and lock ends of its owner, to the
+// total, to the tokens expiring and the power dropping over time, and to the power of the current round of each
+// tranche
+fn checkpoint_lock(
+    storage: &mut dyn Storage,
+    height: u64,
+    owner: &Addr,
+    lock_entry: &LockEntry,
+    add: bool,
+) -> StdResult<()> {
+    let tokens = lock_entry.funds.amount;
+    let lock_end = lock_entry.lock_end;
 
-// CreateProposal(covenant_params, tribute):
-//     Validate covenant_params
-//     Hold tribute in contract's account
-//     Create in PropMap
-fn create_proposal(deps: DepsMut, covenant_params: String) -> Result<Response, ContractError> {
-    validate_covenant_params(covenant_params.clone())?;
+    let apply = |value: Option<Uint128>, delta: Uint128| -> StdResult<Uint128> {
+        let value = value.unwrap_or_else(Uint128::zero);
+        if add {
+            Ok(value + delta)
+        } else {
+            Ok(value - delta)
+        }
+    };
 
-    let round_id = ROUND_ID.load(deps.storage)?;
+    LOCKED_TOKENS.update(storage, owner, height, |locked| apply(locked, tokens))?;
+    TOTAL_LOCKED.update(storage, height, |total| apply(total, tokens))?;
+    LOCK_ENDS.update(storage, owner, height, |lock_ends| -> StdResult<_> {
+        let mut lock_ends = lock_ends.unwrap_or_default();
+        if add {
+            lock_ends.push((lock_end.nanos(), tokens));
+        } else if let Some(index) = lock_ends
+            .iter()
+            .position(|lock| *lock == (lock_end.nanos(), tokens))
+        {
+            lock_ends.remove(index);
+        }
+        Ok(lock_ends)
+    })?;
 
-    // Create proposal in PropMap
-    let proposal = Proposal {
-        covenant_params,
-        round_id,
-        executed: false,
-        power: Uint128::zero(),
-        percentage: Uint128::zero(),
-        amount: Uint128::zero(),
-    };
+    update_schedule(
+        storage,
+        &LOCK_EXPIRIES,
+        &EXPIRED_LOCKED,
+        lock_end,
+        tokens,
+        add,
+    )?;
+    for (time, drop) in power_drops(lock_entry) {
+        update_schedule(storage, &POWER_DROPS, &DROPPED_POWER, time, drop, add)?;
+    }
 
-    let prop_id = PROP_ID.load(deps.storage)?;
-    PROP_ID.save(deps.storage, &(prop_id + 1))?;
-    PROPOSAL_MAP.save(deps.storage, (round_id, prop_id), &proposal)?;
+    // Locks only count in the rounds that end before they do, and with the time left after the round's end
+    let tranche_ids = TRANCHE_MAP
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u64>>>()?;
+    for tranche_id in tranche_ids {
+        let round_id = ROUND_ID.load(storage, tranche_id)?;
+        let round_end = ROUND_MAP.load(storage, (tranche_id, round_id))?.round_end;
+        if lock_end <= round_end {
+            continue;
+        }
+
+        let power = scale_lockup_power(lock_end.nanos() - round_end.nanos(), tokens);
+        ROUND_POWER.update(
+            storage,
+            (tranche_id, round_id),
+            height,
+            |round_power| -> StdResult<_> {
+                let (locked, power_before) = round_power.unwrap_or_default();
+                Ok((
+                    apply(Some(locked), tokens)?,
+                    apply(Some(power_before), power)?,
+                ))
+            },
+        )?;
+    }
 
-    Ok(Response::new().add_attribute("action", "create_proposal"))
+    Ok(())
 }
 
-fn scale_lockup_power(lockup_time: u64, raw_power: Uint128) -> Uint128 {
+// Returns the times at which a lock loses voting power, and how much it loses, as the time left until it ends drops
+// below each lockup tier (see scale_lockup_power()). Adds up to 4 times its amount.
+fn power_drops(lock_entry: &LockEntry) -> Vec<(Timestamp, Uint128)> {
     let one_month_in_nanos: u64 = 2629746000000000;
-
+    let amount = lock_entry.funds.amount;
+    let lock_end = lock_entry.lock_end.nanos();
     let two: Uint128 = 2u16.into();
 
-    // Scale lockup power
-    // 1x if lockup is between 0 and 1 months
-    // 1.5x if lockup is between 1 and 3 months
-    // 2x if lockup is between 3 and 6 months
-    // 4x if lockup is between 6 and 12 months
-    // TODO: is there a less funky way to do Uint128 math???
-    let scaled_power = match lockup_time {
-        // 4x if lockup is over 6 months
-        lockup_time if lockup_time > one_month_in_nanos * 6 => raw_power * two * two,
-        // 2x if lockup is between 3 and 6 months
-        lockup_time if lockup_time > one_month_in_nanos * 3 => raw_power * two,
-        // 1.5x if lockup is between 1 and 3 months
-        lockup_time if lockup_time > one_month_in_nanos => raw_power + (raw_power / two),
-        // Covers 0 and 1 month which have no scaling
-        _ => raw_power,
-    };
-
-    scaled_power
+    vec![
+        // 4x to 2x
+        (
+            lock_end.saturating_sub(one_month_in_nanos * 6),
+            amount * two,
+        ),
+        // 2x to 1.5x
+        (
+            lock_end.saturating_sub(one_month_in_nanos * 3),
+            amount - amount / two,
+        ),
+        // 1.5x to 1x
+        (lock_end.saturating_sub(one_month_in_nanos), amount / two),
+        // 1x to nothing
+        (lock_end, amount),
+    ]
+    .into_iter()
+    .map(|(time, drop)| (Timestamp::from_nanos(time), drop))
+    .collect()
 }
 
-fn vote(deps: DepsMut, info: MessageInfo, proposal_id: u64) -> Result<Response, ContractError> {
-    // This voting system is designed to allow for an unlimited number of proposals and an unlimited number of votes
-    // to be created, without being vulnerable to DOS. A naive implementation, where all votes or all proposals were iterated
-    // at the end of the round could be DOSed by creating a large number of votes or proposals. This is not a problem
-    // for this implementation, but this leads to some subtlety in the implementation.
-    // I will explain the overall principle here:
-    // - The information on which proposal is winning is updated each time someone votes, instead of being calculated at the end of the round.
-    // - This information is stored in a map called PROPS_BY_SCORE, which maps the score of a proposal to the proposal id.
-    // - At the end of the round, a single access to PROPS_BY_SCORE is made to get the winning proposal.
-    // - To enable switching votes (and for other stuff too), we store the vote in VOTE_MAP.
-    // - When a user votes the second time in a round, the information about their previous vote from VOTE_MAP is used to reverse the effect of their previous vote.
-    // - This leads to slightly higher gas costs for each vote, in exchange for a much lower gas cost at the end of the round.
-
-    // Load the round_id
-    let round_id = ROUND_ID.load(deps.storage)?;
-
-    // Load the round
-    let round = ROUND_MAP.load(deps.storage, round_id)?;
-
-    // Get any existing vote for this sender and reverse it- this may be a vote for a different proposal (if they are switching their vote),
-    // or it may be a vote for the same proposal (if they have increased their power by locking more and want to update their vote).
-    // TODO: this could be made more gas-efficient by using a separate path with fewer writes if the vote is for the same proposal
-    let vote = VOTE_MAP.load(deps.storage, (round_id, info.sender.clone()));
-    if let Ok(vote) = vote {
-        // Load the proposal in the vote
-        let mut proposal = PROPOSAL_MAP.load(deps.storage, (round_id, vote.prop_id))?;
-
-        // Remove proposal's old power in PROPS_BY_SCORE
-        PROPS_BY_SCORE.remove(
-            deps.storage,
-            (round_id, proposal.power.into(), vote.prop_id),
-        );
-
-        // Decrement proposal's power
-        proposal.power -= vote.power;
+// Returns the tokens and voting power that the lock ends of an address have in a round ending at `round_end`,
+// counted the same way as in cast_vote()
+fn lock_ends_power(lock_ends: &[(u64, Uint128)], round_end: Timestamp) -> (Uint128, Uint128) {
+    lock_ends
+        .iter()
+        .filter(|(lock_end, _)| *lock_end > round_end.nanos())
+        .fold(
+            (Uint128::zero(), Uint128::zero()),
+            |(locked, power), (lock_end, amount)| {
+                (
+                    locked + amount,
+                    power + scale_lockup_power(lock_end - round_end.nanos(), *amount),
+                )
+            },
+        )
+}
 
-        // Save the proposal
-        PROPOSAL_MAP.save(deps.storage, (round_id, vote.prop_id), &proposal)?;
+// Returns the amount of tokens held in locks that end after `time`, i.e. the tokens that can vote in a round
+// that ends at `time` (see cast_vote())
+fn active_locked(storage: &dyn Storage, time: Timestamp) -> StdResult<Uint128> {
+    Ok(TOTAL_LOCKED.load(storage)? - schedule_sum(storage, &LOCK_EXPIRIES, &EXPIRED_LOCKED, time)?)
+}
 
-        // Add proposal's new power in PROPS_BY_SCORE
-        PROPS_BY_SCORE.save(
-            deps.storage,
-            (round_id, proposal.power.into(), vote.prop_id),
-            &vote.prop_id,
-        )?;
+// Returns the voting power of the tokens held in locks in a round that ends at `time`, i.e. the power all of them
+// would add to the round if they voted in it (see cast_vote())
+fn active_power(storage: &dyn Storage, time: Timestamp) -> StdResult<Uint128> {
+    let four: Uint128 = 4u16.into();
+    Ok(TOTAL_LOCKED.load(storage)? * four
+        - schedule_sum(storage, &POWER_DROPS, &DROPPED_POWER, time)?)
+}
 
-        // Decrement total power voting
-        let total_power_voting = TOTAL_POWER_VOTING.load(deps.storage, round_id)?;
-        TOTAL_POWER_VOTING.save(deps.storage, round_id, &(total_power_voting - vote.power))?;
+// Adds (or subtracts, if `add` is false) an amount at `time` to a schedule, such as LOCK_EXPIRIES, and to the sum
+// of the schedule's amounts up to its time, such as EXPIRED_LOCKED, if `time` is not after it
+fn update_schedule(
+    storage: &mut dyn Storage,
+    schedule: &Map<u64, Uint128>,
+    sum: &Item<(Timestamp, Uint128)>,
+    time: Timestamp,
+    amount: Uint128,
+    add: bool,
+) -> StdResult<()> {
+    let apply = |value: Uint128| if add { value + amount } else { value - amount };
 
-        // Delete vote
-        VOTE_MAP.remove(deps.storage, (round_id, info.sender.clone()));
+    let scheduled = apply(
+        schedule
+            .may_load(storage, time.nanos())?
+            .unwrap_or_default(),
+    );
+    if scheduled.is_zero() {
+        schedule.remove(storage, time.nanos());
+    } else {
+        schedule.save(storage, time.nanos(), &scheduled)?;
     }
 
-    // Get sender's total locked power
-    let mut power: Uint128 = Uint128::zero();
-    let locks =
-        LOCKS_MAP
-            .prefix(info.sender.clone())
-            .range(deps.storage, None, None, Order::Ascending);
-
-    for lock in locks {
-        let (_, lock_entry) = lock?;
-
-        // Get the remaining lockup time at the end of this round.
-        // This means that their power will be scaled the same by this function no matter when they vote in the round
-        let lockup_time = lock_entry.lock_end.nanos() - round.round_end.nanos();
+    let (sum_at, sum_before) = sum.load(storage)?;
+    if time <= sum_at {
+        sum.save(storage, &(sum_at, apply(sum_before)))?;
+    }
 
-        // Scale power. This is what implements the different powers for different lockup times.
-        let scaled_power = scale_lockup_power(lockup_time, lock_entry.funds.amount);
+    Ok(())
+}
 
-        power += scaled_power;
+// Returns the sum of the schedule's amounts at or before `time`, starting from the sum recorded for it and only
+// summing up the amounts in between
+fn schedule_sum(
+    storage: &dyn Storage,
+    schedule: &Map<u64, Uint128>,
+    sum: &Item<(Timestamp, Uint128)>,
+    time: Timestamp,
+) -> StdResult<Uint128> {
+    let (sum_at, sum) = sum.load(storage)?;
+    if time >= sum_at {
+        Ok(sum + schedule_between(storage, schedule, sum_at, time)?)
+    } else {
+        Ok(sum - schedule_between(storage, schedule, time, sum_at)?)
     }
+}
 
-    // Load the proposal being voted on
-    let mut proposal = PROPOSAL_MAP.load(deps.storage, (round_id, proposal_id))?;
+// Returns the sum of the schedule's amounts after `from` and at or before `to`
+fn schedule_between(
+    storage: &dyn Storage,
+    schedule: &Map<u64, Uint128>,
+    from: Timestamp,
+    to: Timestamp,
+) -> StdResult<Uint128> {
+    schedule
+        .range(
+            storage,
+            Some(Bound::exclusive(from.nanos())),
+            Some(Bound::inclusive(to.nanos())),
+            Order::Ascending,
+        )
+        .map(|amount| amount.map(|(_, amount)| amount))
+        .sum()
+}
 
-    // Delete the proposal's old power in PROPS_BY_SCORE
-    PROPS_BY_SCORE.remove(deps.storage, (round_id, proposal.power.into(), proposal_id));
+// Moves EXPIRED_LOCKED and DROPPED_POWER forward to `time`
+fn expire_locks(storage: &mut dyn Storage, time: Timestamp) -> StdResult<()> {
+    for (schedule, sum) in [
+        (&LOCK_EXPIRIES, &EXPIRED_LOCKED),
+        (&POWER_DROPS, &DROPPED_POWER),
+    ] {
+        let (sum_at, _) = sum.load(storage)?;
+        if time > sum_at {
+            let summed = schedule_sum(storage, schedule, sum, time)?;
+            sum.save(storage, &(time, summed))?;
+        }
+    }
 
-    // Update proposal's power
-    proposal.power += power;
+    Ok(())
+}
 
-    // Save the proposal
-    PROPOSAL_MAP.save(deps.storage, (round_id, proposal_id), &proposal)?;
+// TransferNft(recipient, token_id):
+//     Validate that the sender owns the lock, or was approved to transfer it
+//     Move the lock, and its voting power, to the recipient
+fn transfer_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+    transfer_lock(deps, &env, &info.sender, &recipient, &token_id)?;
 
-    // Save the proposal's new power in PROPS_BY_SCORE
-    PROPS_BY_SCORE.save(
-        deps.storage,
-        (round_id, proposal.power.into(), proposal_id),
-        &proposal_id,
-    )?;
+    Ok(Response::new()
+        .add_attribute("action", "transfer_nft")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipient", recipient)
+        .add_attribute("token_id", token_id))
+}
 
-    // Increment total power voting
-    let total_power_voting = TOTAL_POWER_VOTING.load(deps.storage, round_id)?;
-    TOTAL_POWER_VOTING.save(deps.storage, round_id, &(total_power_voting + power))?;
+// SendNft(contract, token_id, msg):
+//     Validate that the sender owns the lock, or was approved to transfer it
+//     Move the lock, and its voting power, to the contract
+//     Notify the contract with a CW721 ReceiveNft message
+fn send_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    token_id: String,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    let contract = deps.api.addr_validate(&contract)?;
+    transfer_lock(deps, &env, &info.sender, &contract, &token_id)?;
 
-    // Create vote in Votemap
-    let vote = Vote {
-        prop_id: proposal_id,
-        power,
+    let receive_msg = WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_json_binary(&ReceiverExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: info.sender.to_string(),
+            token_id: token_id.clone(),
+            msg,
+        }))?,
+        funds: vec![],
     };
-    VOTE_MAP.save(deps.storage, (round_id, info.sender), &vote)?;
 
-    Ok(Response::new().add_attribute("action", "vote"))
+    Ok(Response::new()
+        .add_message(receive_msg)
+        .add_attribute("action", "send_nft")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipient", contract)
+        .add_attribute("token_id", token_id))
 }
 
-fn end_round(deps: DepsMut, env: Env, _info: MessageInfo) -> Result<Response, ContractError> {
-    // Check that round has ended by getting latest round and checking if round_end < now
-    let round_id = ROUND_ID.load(deps.storage)?;
-    let round = ROUND_MAP.load(deps.storage, round_id)?;
-
-    if round.round_end > env.block.time {
+// Moves the lock from its owner to the recipient, and clears its approvals. Votes cast in the current round of each
+// tranche are recast for the same proposals, so that the lock's power leaves the owner's vote and joins the
+// recipient's vote, if they already voted. Votes in earlier rounds are left as they are.
+fn transfer_lock(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    recipient: &Addr,
+    token_id: &str,
+) -> Result<(), ContractError> {
+    let lock_id = parse_token_id(token_id)?;
+    let owner = &LOCK_OWNERS.load(deps.storage, lock_id)?;
+    if !can_transfer(deps.as_ref(), env, owner, sender, lock_id)? {
         return Err(ContractError::Std(StdError::generic_err(
-            "Round has not ended yet",
+            "Sender is not the lock's owner and was not approved to transfer it",
+        )));
+    }
+    if owner == recipient {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Cannot transfer a lock to its owner",
         )));
     }
 
-    // Calculate the round_end for the next round
-    let round_end = env
-        .block
-        .time
-        .plus_nanos(CONSTANTS.load(deps.storage)?.round_length);
-
-    // Increment the round_id
-    let round_id = round.round_id + 1;
-    ROUND_ID.save(deps.storage, &(round_id))?;
-    // Save the round
-    ROUND_MAP.save(
-        deps.storage,
-        round_id,
-        &Round {
-            round_end,
-            round_id,
-        },
-    )?;
-
-    Ok(Response::new().add_attribute("action", "tally"))
-}
-
-fn do_covenant_stuff(
-    _deps: Deps,
-    _env: Env,
-    _info: MessageInfo,
-    _covenant_params: String,
-) -> Result<Response, ContractError> {
-    // Do covenant stuff
-    Ok(Response::new().add_attribute("action", "do_covenant_stuff"))
-}
-
-fn get_top_props(deps: Deps, round_id: u64, num: usize) -> Result<Vec<Proposal>, ContractError> {
-    // Iterate through PROPS_BY_SCORE to find the top ten props
-    let top_prop_ids: Vec<u64> = PROPS_BY_SCORE
-        .sub_prefix(round_id)
-        .range(deps.storage, None, None, Order::Descending)
-        .take(num)
-        .map(|x| match x {
-            Ok((_, prop_id)) => prop_id,
-            Err(_) => 0, // Handle the error case appropriately
-        })
-        .collect();
+    // Sync the rounds first, so that only votes in rounds that are still running get recast
+    let tranche_ids = sync_all_rounds(deps.storage, env)?;
 
-    let mut top_props = vec![];
+    let lock_entry = LOCKS_MAP.load(deps.storage, (owner.clone(), lock_id))?;
+    LOCKS_MAP.remove(deps.storage, (owner.clone(), lock_id));
+    LOCKS_MAP.save(deps.storage, (recipient.clone(), lock_id), &lock_entry)?;
+    LOCK_OWNERS.save(deps.storage, lock_id, recipient)?;
+    clear_token_approvals(deps.storage, lock_id)?;
 
-    for prop_id in top_prop_ids {
-        let prop = PROPOSAL_MAP.load(deps.storage, (round_id, prop_id))?;
-        top_props.push(prop);
-    }
+    checkpoint_lock(deps.storage, env.block.height, owner, &lock_entry, false)?;
+    checkpoint_lock(deps.storage, env.block.height, recipient, &lock_entry, true)?;
 
-    // loop to find sum of power
-    let mut sum_power = 0;
-    for prop in top_props {
-        sum_power += prop.power.u128();
+    for tranche_id in tranche_ids {
+        let round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+        for voter in [owner, recipient] {
+            if let Some(vote) =
+                VOTE_MAP.may_load(deps.storage, (tranche_id, round_id, voter.clone()))?
+            {
+                cast_vote(deps.storage, tranche_id, round_id, voter, vote.prop_id)?;
+            }
+        }
     }
 
-    // // loop to find percentage of power
-    // for mut prop in top_props {
-    //     prop.percentage = (prop.power.u128() / sum_power).into();
-    // }
+    Ok(())
+}
 
-    // // Multiply by total pool to find exact amount
-    // for mut prop in top_props {
-    //     prop.amount = prop.percentage * CONSTANTS.load(deps.storage)?.total_pool;
-    // }
+// Returns whether the sender can transfer the lock: they own it, were approved to transfer it, or are an
+// operator of its owner
+fn can_transfer(
+    deps: Deps,
+    env: &Env,
+    owner: &Addr,
+    sender: &Addr,
+    lock_id: u64,
+) -> StdResult<bool> {
+    if sender == owner || is_operator(deps, env, owner, sender)? {
+        return Ok(true);
+    }
 
-    // for top_props {
-    //     prop.percentage = (prop.power.u128() / sum_power).into();
-    //     prop.amount = prop.percentage * CONSTANTS.load(deps.storage)?.total_pool;
-    // }
+    Ok(TOKEN_APPROVALS
+        .may_load(deps.storage, (lock_id, sender))?
+        .is_some_and(|expires| !expires.is_expired(&env.block)))
+}
 
-    let total_pool = CONSTANTS.load(deps.storage)?.total_pool;
+// Returns whether the sender owns the lock or is an operator of its owner, i.e. whether they can approve others
+fn can_approve(deps: Deps, env: &Env, owner: &Addr, sender: &Addr) -> StdResult<bool> {
+    Ok(sender == owner || is_operator(deps, env, owner, sender)?)
+}
 
-    // return top props
-    return Ok(top_props
-        .into_iter() // Change from iter() to into_iter()
-        .map(|mut prop| {
-            // Change to mutable binding
-            prop.percentage = (prop.power.u128() / sum_power).into();
-            prop.amount = prop.percentage * total_pool;
-            prop
-        })
-        .collect());
-}
-
-// TODO: we need to do this differently to allocate liquidity proportionally to the top props instead of the old
-// winner takes all method
-// fn execute_proposal(
-//     deps: DepsMut,
-//     env: Env,
-//     info: MessageInfo,
-//     proposal_id: u64,
-// ) -> Result<Response, ContractError> {
-//     // Load the last round_id
-//     let last_round_id = ROUND_ID.load(deps.storage)? - 1;
-
-//     // Load the winning prop_id
-//     let winning_prop_id = get_winning_prop(deps.as_ref(), last_round_id)?;
-
-//     // Check that this prop is the one that won
-//     if winning_prop_id != proposal_id {
-//         return Err(ContractError::Std(StdError::generic_err(
-//             "Proposal did not win the last round",
-//         )));
-//     }
-
-//     // Load the proposal
-//     let mut proposal = PROPOSAL_MAP.load(deps.storage, (last_round_id, proposal_id))?;
-
-//     // Check that the proposal has not already been executed
-//     if proposal.executed {
-//         return Err(ContractError::Std(StdError::generic_err(
-//             "Proposal already executed",
-//         )));
-//     }
-
-//     // Execute proposal
-//     do_covenant_stuff(deps.as_ref(), env, info, proposal.clone().covenant_params)?;
-
-//     // Mark proposal as executed
-//     proposal.executed = true;
-//     PROPOSAL_MAP.save(deps.storage, (last_round_id, proposal_id), &proposal)?;
-
-//     Ok(Response::new().add_attribute("action", "execute_proposal"))
-// }
+fn is_operator(deps: Deps, env: &Env, owner: &Addr, operator: &Addr) -> StdResult<bool> {
+    Ok(OPERATORS
+        .may_load(deps.storage, (owner, operator))?
+        .is_some_and(|expires| !expires.is_expired(&env.block)))
+}
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetCount {} => query_count(deps),
+fn clear_token_approvals(storage: &mut dyn Storage, lock_id: u64) -> StdResult<()> {
+    let spenders = TOKEN_APPROVALS
+        .prefix(lock_id)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<Addr>>>()?;
+    for spender in spenders.iter() {
+        TOKEN_APPROVALS.remove(storage, (lock_id, spender));
     }
+
+    Ok(())
 }
 
-// pub fn query_winning_proposal(deps: Deps) -> StdResult<Binary> {
-//     let winning_prop_id = get_winning_prop(deps)?;
-//     to_json_binary(&winning_prop_id)
-// }
+// Approve(spender, token_id, expires):
+//     Validate that the sender owns the lock or is an operator of its owner
+//     Allow the spender to transfer the lock until the approval expires, or until the lock changes hands
+fn approve(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    token_id: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let spender = deps.api.addr_validate(&spender)?;
+    let lock_id = parse_token_id(&token_id)?;
+    let owner = LOCK_OWNERS.load(deps.storage, lock_id)?;
+    if !can_approve(deps.as_ref(), &env, &owner, &info.sender)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the lock's owner or an operator of its owner",
+        )));
+    }
+
+    let expires = expires.unwrap_or(Expiration::Never {});
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Approval has already expired",
+        )));
+    }
+    TOKEN_APPROVALS.save(deps.storage, (lock_id, &spender), &expires)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "approve")
+        .add_attribute("sender", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("token_id", token_id))
+}
+
+// Revoke(spender, token_id):
+//     Validate that the sender owns the lock or is an operator of its owner
+//     Remove the spender's approval to transfer the lock
+fn revoke(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let spender = deps.api.addr_validate(&spender)?;
+    let lock_id = parse_token_id(&token_id)?;
+    let owner = LOCK_OWNERS.load(deps.storage, lock_id)?;
+    if !can_approve(deps.as_ref(), &env, &owner, &info.sender)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the lock's owner or an operator of its owner",
+        )));
+    }
+
+    TOKEN_APPROVALS.remove(deps.storage, (lock_id, &spender));
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke")
+        .add_attribute("sender", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("token_id", token_id))
+}
+
+// ApproveAll(operator, expires):
+//     Allow the operator to transfer all of the sender's locks, and to approve others to, until the approval expires
+fn approve_all(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    operator: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let operator = deps.api.addr_validate(&operator)?;
+
+    let expires = expires.unwrap_or(Expiration::Never {});
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Approval has already expired",
+        )));
+    }
+    OPERATORS.save(deps.storage, (&info.sender, &operator), &expires)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "approve_all")
+        .add_attribute("sender", info.sender)
+        .add_attribute("operator", operator))
+}
+
+// RevokeAll(operator):
+//     Remove the operator's approval to transfer the sender's locks
+fn revoke_all(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator: String,
+) -> Result<Response, ContractError> {
+    let operator = deps.api.addr_validate(&operator)?;
+    OPERATORS.remove(deps.storage, (&info.sender, &operator));
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_all")
+        .add_attribute("sender", info.sender)
+        .add_attribute("operator", operator))
+}
+
+fn parse_token_id(token_id: &str) -> StdResult<u64> {
+    token_id
+        .parse::<u64>()
+        .map_err(|_| StdError::generic_err(format!("Invalid token_id: {}", token_id)))
+}
+
+// Validates covenant_params before the proposal is created, so that proposals that could never be executed
+// are rejected up front instead of failing at execution time
+fn validate_covenant_params(
+    deps: Deps,
+    tranche: &Tranche,
+    covenant_params: &CovenantParams,
+) -> Result<(), ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+
+    // Check that the tranche accepts this kind of proposal
+    let eligible = match &tranche.eligibility {
+        ProposalEligibility::Any => true,
+        ProposalEligibility::CounterpartyDenoms { denoms } => {
+            denoms.contains(&covenant_params.counterparty_denom)
+        }
+        ProposalEligibility::ConsumerChains => {
+            CONSUMER_CHAINS.has(deps.storage, covenant_params.counterparty_denom.clone())
+        }
+    };
+    if !eligible {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Tranche {} does not accept proposals for this counterparty denom",
+            tranche.name
+        ))));
+    }
+
+    // When proposal creation is curated, the pool or the counterparty denom has to be approved
+    if constants.proposal_creation_mode == ProposalCreationMode::Curated {
+        let pool = RegistryEntry::Pool {
+            dex: covenant_params.dex.clone(),
+            pool_id: covenant_params.pool_id.clone(),
+        };
+        let counterparty_denom = RegistryEntry::CounterpartyDenom {
+            denom: covenant_params.counterparty_denom.clone(),
+        };
+        if !APPROVED_POOLS.has(deps.storage, registry_key(&pool))
+            && !APPROVED_POOLS.has(deps.storage, registry_key(&counterparty_denom))
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Proposals can only target approved pools",
+            )));
+        }
+    }
+
+    if covenant_params.duration == 0 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Duration must be at least one round",
+        )));
+    }
+
+    if covenant_params.slippage_tolerance > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Slippage tolerance must be between 0 and 1",
+        )));
+    }
+
+    // Validate the price bounds
+    if covenant_params.min_price == Some(Decimal::zero())
+        || covenant_params.max_price == Some(Decimal::zero())
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Price bounds must be greater than zero",
+        )));
+    }
+
+    if let (Some(min_price), Some(max_price)) =
+        (covenant_params.min_price, covenant_params.max_price)
+    {
+        if min_price >= max_price {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Minimum price must be lower than maximum price",
+            )));
+        }
+    }
+
+    if let Some(streamed_withdrawal) = &covenant_params.streamed_withdrawal {
+        if streamed_withdrawal.tranches == 0 || streamed_withdrawal.interval == 0 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Streamed withdrawals need at least one tranche and an interval of at least one block",
+            )));
+        }
+
+        if covenant_params.deployment == DeploymentMethod::Covenant {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Covenant positions can't be withdrawn in tranches",
+            )));
+        }
+    }
+
+    if covenant_params.party_receiver_addr.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Party receiver address must be set",
+        )));
+    }
+
+    // Direct deployments need a pool on the right DEX, and a valid tick range for concentrated liquidity
+    match covenant_params.deployment {
+        DeploymentMethod::Covenant => {}
+        DeploymentMethod::OsmosisGamm | DeploymentMethod::OsmosisConcentrated { .. }
+            if covenant_params.dex != Dex::Osmosis =>
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Osmosis deployments can only target Osmosis pools",
+            )));
+        }
+        DeploymentMethod::Astroport if covenant_params.dex != Dex::Astroport => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Astroport deployments can only target Astroport pairs",
+            )));
+        }
+        DeploymentMethod::OsmosisConcentrated {
+            lower_tick,
+            upper_tick,
+        } if lower_tick >= upper_tick => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Lower tick must be lower than upper tick",
+            )));
+        }
+        _ => {}
+    }
+
+    if covenant_params.counterparty_denom == constants.pool_denom {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Counterparty denom must be different from the pool denom",
+        )));
+    }
+
+    // Check that the pool exists and that it pairs the pool denom with the counterparty denom
+    let pool_denoms: Vec<String> = match covenant_params.dex {
+        Dex::Astroport => {
+            let pair_addr = deps.api.addr_validate(&covenant_params.pool_id)?;
+            let pair_info: PairInfo = deps
+                .querier
+                .query_wasm_smart(pair_addr, &PairQueryMsg::Pair {})
+                .map_err(|_| ContractError::Std(StdError::generic_err("Unknown Astroport pair")))?;
+
+            pair_info.native_denoms()
+        }
+        Dex::Osmosis => {
+            let pool_id: u64 = covenant_params.pool_id.parse().map_err(|_| {
+                ContractError::Std(StdError::generic_err("Osmosis pool id must be a number"))
+            })?;
+            let liquidity = PoolmanagerQuerier::new(&deps.querier)
+                .total_pool_liquidity(pool_id)
+                .map_err(|_| ContractError::Std(StdError::generic_err("Unknown Osmosis pool")))?;
+
+            liquidity
+                .liquidity
+                .into_iter()
+                .map(|coin| coin.denom)
+                .collect()
+        }
+    };
+
+    if !pool_denoms.contains(&constants.pool_denom)
+        || !pool_denoms.contains(&covenant_params.counterparty_denom)
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Pool does not pair the pool denom with the counterparty denom",
+        )));
+    }
+
+    Ok(())
+}
+
+// Checks that the proposal has a title, and that none of its metadata is too long to be stored
+fn validate_metadata(title: &str, description: &str, url: &str) -> Result<(), ContractError> {
+    if title.trim().is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal must have a title",
+        )));
+    }
+
+    if title.len() > MAX_TITLE_LENGTH
+        || description.len() > MAX_DESCRIPTION_LENGTH
+        || url.len() > MAX_URL_LENGTH
+    {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Proposal title, description and url can be at most {}, {} and {} bytes long",
+            MAX_TITLE_LENGTH, MAX_DESCRIPTION_LENGTH, MAX_URL_LENGTH
+        ))));
+    }
+
+    Ok(())
+}
+
+// CreateProposal(tranche_id, title, description, url, covenant_params, standing_rounds):
+//     Validate the metadata
+//     Validate covenant_params against the tranche's eligibility rules
+//     Check that the proposal does not stand for more than the maximum number of rounds
+//     Check that the sender has not reached the maximum number of proposals for the round
+//     Hold the proposal deposit in contract's account
+//     Create in PropMap, in the tranche's current round
+#[allow(clippy::too_many_arguments)]
+fn create_proposal(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    tranche_id: u64,
+    title: String,
+    description: String,
+    url: String,
+    covenant_params: CovenantParams,
+    standing_rounds: Option<u64>,
+) -> Result<Response, ContractError> {
+    validate_metadata(&title, &description, &url)?;
+
+    let constants = CONSTANTS.load(deps.storage)?;
+
+    let standing_rounds = standing_rounds.unwrap_or(1);
+    if standing_rounds == 0 || standing_rounds > constants.max_standing_rounds {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Proposals can stand for 1 to {} rounds",
+            constants.max_standing_rounds
+        ))));
+    }
+    let tranche = TRANCHE_MAP.load(deps.storage, tranche_id)?;
+    validate_covenant_params(deps.as_ref(), &tranche, &covenant_params)?;
+
+    let round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+
+    // Check the sender's proposal count for the round
+    let proposal_count = PROPOSAL_COUNT
+        .may_load(deps.storage, (tranche_id, round_id, info.sender.clone()))?
+        .unwrap_or(0);
+    if let Some(max_proposals) = constants.max_proposals_per_address {
+        if proposal_count >= max_proposals {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "Cannot create more than {} proposals per round",
+                max_proposals
+            ))));
+        }
+    }
+    PROPOSAL_COUNT.save(
+        deps.storage,
+        (tranche_id, round_id, info.sender.clone()),
+        &(proposal_count + 1),
+    )?;
+
+    let prop_id = PROP_ID.load(deps.storage)?;
+    PROP_ID.save(deps.storage, &(prop_id + 1))?;
+
+    // Validate that the sender paid exactly the proposal deposit
+    match &constants.proposal_deposit {
+        Some(deposit) => {
+            if info.funds.len() != 1 || info.funds[0] != *deposit {
+                return Err(ContractError::Std(StdError::generic_err(format!(
+                    "Must send a proposal deposit of {}",
+                    deposit
+                ))));
+            }
+
+            PROPOSAL_DEPOSITS.save(
+                deps.storage,
+                (tranche_id, round_id, prop_id),
+                &ProposalDeposit {
+                    depositor: info.sender.clone(),
+                    amount: deposit.clone(),
+                    status: DepositStatus::Held,
+                },
+            )?;
+        }
+        None => {
+            if !info.funds.is_empty() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Proposals don't need a deposit",
+                )));
+            }
+        }
+    }
+
+    // Proposals for registered consumer chains get the consumer chain multiplier when the pool is allocated
+    let consumer_chain =
+        CONSUMER_CHAINS.has(deps.storage, covenant_params.counterparty_denom.clone());
+
+    // Create proposal in PropMap
+    let proposal = Proposal {
+        covenant_params,
+        tranche_id,
+        round_id,
+        prop_id,
+        creator: info.sender,
+        created_at: env.block.time,
+        title,
+        description,
+        url,
+        received_votes: false,
+        status: ExecutionStatus::Pending,
+        consumer_chain,
+        power: Uint128::zero(),
+        effective_power: Uint128::zero(),
+        percentage: Decimal::zero(),
+        amount: Uint128::zero(),
+        position: None,
+        deployed_amount: Uint128::zero(),
+        counterparty: None,
+    };
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+    // Standing proposals are carried over into the next rounds lazily, see load_proposal()
+    if standing_rounds > 1 {
+        STANDING_PROPOSALS.save(
+            deps.storage,
+            (tranche_id, prop_id),
+            &StandingProposal {
+                prop_id,
+                first_round_id: round_id,
+                last_round_id: round_id + standing_rounds - 1,
+            },
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "create_proposal")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("creator", proposal.creator))
+}
+
+// Loads a proposal of a round that has started. A standing proposal that was not needed in a later round yet
+// is copied from its first round, with no votes and nothing deployed.
+fn load_proposal(
+    storage: &dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> StdResult<Proposal> {
+    if let Some(proposal) = PROPOSAL_MAP.may_load(storage, (tranche_id, round_id, prop_id))? {
+        return Ok(proposal);
+    }
+
+    let standing = STANDING_PROPOSALS
+        .may_load(storage, (tranche_id, prop_id))?
+        .filter(|standing| round_id > standing.first_round_id && round_id <= standing.last_round_id)
+        .ok_or_else(|| StdError::not_found("Proposal"))?;
+    if round_id > ROUND_ID.load(storage, tranche_id)? {
+        return Err(StdError::not_found("Proposal"));
+    }
+
+    let proposal = PROPOSAL_MAP.load(storage, (tranche_id, standing.first_round_id, prop_id))?;
+    Ok(Proposal {
+        round_id,
+        received_votes: false,
+        status: ExecutionStatus::Pending,
+        power: Uint128::zero(),
+        effective_power: Uint128::zero(),
+        percentage: Decimal::zero(),
+        amount: Uint128::zero(),
+        position: None,
+        deployed_amount: Uint128::zero(),
+        counterparty: None,
+        ..proposal
+    })
+}
+
+// Loads a proposal of the tranche's current round that the sender created and that nobody voted for yet
+fn load_unvoted_proposal(
+    deps: Deps,
+    info: &MessageInfo,
+    tranche_id: u64,
+    prop_id: u64,
+) -> Result<Proposal, ContractError> {
+    let round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    let proposal = PROPOSAL_MAP
+        .may_load(deps.storage, (tranche_id, round_id, prop_id))?
+        .ok_or_else(|| {
+            ContractError::Std(StdError::generic_err(
+                "Proposal is not in the tranche's current round",
+            ))
+        })?;
+
+    if proposal.creator != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the proposal's creator",
+        )));
+    }
+
+    if proposal.received_votes {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal has already received votes",
+        )));
+    }
+
+    // Later rounds of a standing proposal are copies of its first round
+    if let Some(standing) = STANDING_PROPOSALS.may_load(deps.storage, (tranche_id, prop_id))? {
+        if standing.first_round_id != round_id {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Standing proposals can only be edited or withdrawn in their first round",
+            )));
+        }
+    }
+
+    Ok(proposal)
+}
+
+// EditProposal(tranche_id, prop_id, title, description, url):
+//     Check that the sender created the proposal, and that it has no votes yet
+//     Validate and replace the metadata
+fn edit_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+    tranche_id: u64,
+    prop_id: u64,
+    title: String,
+    description: String,
+    url: String,
+) -> Result<Response, ContractError> {
+    let mut proposal = load_unvoted_proposal(deps.as_ref(), &info, tranche_id, prop_id)?;
+    validate_metadata(&title, &description, &url)?;
+
+    proposal.title = title;
+    proposal.description = description;
+    proposal.url = url;
+    PROPOSAL_MAP.save(
+        deps.storage,
+        (tranche_id, proposal.round_id, prop_id),
+        &proposal,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "edit_proposal")
+        .add_attribute("prop_id", prop_id.to_string()))
+}
+
+// RetireProposal(tranche_id, prop_id):
+//     Check that the sender created the standing proposal
+//     Stop carrying it over after the current round
+fn retire_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+    tranche_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let mut standing = STANDING_PROPOSALS
+        .may_load(deps.storage, (tranche_id, prop_id))?
+        .ok_or_else(|| {
+            ContractError::Std(StdError::generic_err("Proposal is not a standing proposal"))
+        })?;
+
+    let proposal =
+        PROPOSAL_MAP.load(deps.storage, (tranche_id, standing.first_round_id, prop_id))?;
+    if proposal.creator != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the proposal's creator",
+        )));
+    }
+
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if standing.last_round_id <= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal does not stand past the current round",
+        )));
+    }
+
+    standing.last_round_id = current_round_id;
+    STANDING_PROPOSALS.save(deps.storage, (tranche_id, prop_id), &standing)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "retire_proposal")
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("last_round_id", current_round_id.to_string()))
+}
+
+// WithdrawProposal(tranche_id, prop_id):
+//     Check that the sender created the proposal, and that it has no votes yet
+//     Delete the proposal, and give it back to the sender's proposal count for the round
+//     Send the proposal deposit back to the sender, and the counterparty deposit back to its depositor
+fn withdraw_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+    tranche_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let proposal = load_unvoted_proposal(deps.as_ref(), &info, tranche_id, prop_id)?;
+    let round_id = proposal.round_id;
+
+    PROPOSAL_MAP.remove(deps.storage, (tranche_id, round_id, prop_id));
+    STANDING_PROPOSALS.remove(deps.storage, (tranche_id, prop_id));
+
+    let count_key = (tranche_id, round_id, info.sender.clone());
+    let proposal_count = PROPOSAL_COUNT.load(deps.storage, count_key.clone())?;
+    PROPOSAL_COUNT.save(deps.storage, count_key, &(proposal_count - 1))?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "withdraw_proposal")
+        .add_attribute("prop_id", prop_id.to_string());
+
+    if let Some(deposit) =
+        PROPOSAL_DEPOSITS.may_load(deps.storage, (tranche_id, round_id, prop_id))?
+    {
+        PROPOSAL_DEPOSITS.remove(deps.storage, (tranche_id, round_id, prop_id));
+        response = response.add_message(BankMsg::Send {
+            to_address: deposit.depositor.to_string(),
+            amount: vec![deposit.amount],
+        });
+    }
+
+    if let Some(counterparty) = proposal.counterparty {
+        response = response.add_message(BankMsg::Send {
+            to_address: counterparty.depositor.to_string(),
+            amount: vec![Coin {
+                denom: proposal.covenant_params.counterparty_denom,
+                amount: counterparty.amount,
+            }],
+        });
+    }
+
+    Ok(response)
+}
+
+fn scale_lockup_power(lockup_time: u64, raw_power: Uint128) -> Uint128 {
+    let one_month_in_nanos: u64 = 2629746000000000;
+
+    let two: Uint128 = 2u16.into();
+
+    // Scale lockup power
+    // 1x if lockup is between 0 and 1 months
+    // 1.5x if lockup is between 1 and 3 months
+    // 2x if lockup is between 3 and 6 months
+    // 4x if lockup is between 6 and 12 months
+    // TODO: is there a less funky way to do Uint128 math???
+    match lockup_time {
+        // 4x if lockup is over 6 months
+        lockup_time if lockup_time > one_month_in_nanos * 6 => raw_power * two * two,
+        // 2x if lockup is between 3 and 6 months
+        lockup_time if lockup_time > one_month_in_nanos * 3 => raw_power * two,
+        // 1.5x if lockup is between 1 and 3 months
+        lockup_time if lockup_time > one_month_in_nanos => raw_power + (raw_power / two),
+        // Covers 0 and 1 month which have no scaling
+        _ => raw_power,
+    }
+}
+
+// Returns the minimum number of months of remaining lockup that a lock exceeds, using the same
+// brackets as scale_lockup_power. Tribute contracts use this to tell committed lockers apart.
+fn lockup_tier(lockup_time: u64) -> u64 {
+    let one_month_in_nanos: u64 = 2629746000000000;
+
+    match lockup_time {
+        lockup_time if lockup_time > one_month_in_nanos * 6 => 6,
+        lockup_time if lockup_time > one_month_in_nanos * 3 => 3,
+        lockup_time if lockup_time > one_month_in_nanos => 1,
+        _ => 0,
+    }
+}
+
+// Adds (or subtracts, if `add` is false) the power of each lock backing a vote to the proposal's
+// per-tier power in PROP_POWER_BY_TIER
+fn update_prop_power_by_tier(
+    storage: &mut dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+    locks: &[LockPower],
+    add: bool,
+) -> Result<(), ContractError> {
+    for lock in locks {
+        let key = ((tranche_id, round_id), prop_id, lock.lockup_tier);
+        let tier_power = PROP_POWER_BY_TIER
+            .may_load(storage, key)?
+            .unwrap_or_else(Uint128::zero);
+
+        let tier_power = if add {
+            tier_power + lock.scaled_power
+        } else {
+            tier_power - lock.scaled_power
+        };
+
+        PROP_POWER_BY_TIER.save(storage, key, &tier_power)?;
+    }
+
+    Ok(())
+}
+
+// Returns the amount of locked tokens backing a vote, before lockup scaling
+fn raw_power(locks: &[LockPower]) -> Uint128 {
+    locks.iter().map(|lock| lock.raw_power).sum()
+}
+
+fn vote(
+    deps: DepsMut,
+    info: MessageInfo,
+    tranche_id: u64,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    // This voting system is designed to allow for an unlimited number of proposals and an unlimited number of votes
+    // to be created, without being vulnerable to DOS. A naive implementation, where all votes or all proposals were iterated
+    // at the end of the round could be DOSed by creating a large number of votes or proposals. This is not a problem
+    // for this implementation, but this leads to some subtlety in the implementation.
+    // I will explain the overall principle here:
+    // - The information on which proposal is winning is updated each time someone votes, instead of being calculated at the end of the round.
+    // - This information is stored in a map called PROPS_BY_SCORE, which maps the score of a proposal to the proposal id.
+    // - At the end of the round, a single access to PROPS_BY_SCORE is made to get the winning proposal.
+    // - To enable switching votes (and for other stuff too), we store the vote in VOTE_MAP.
+    // - When a user votes the second time in a round, the information about their previous vote from VOTE_MAP is used to reverse the effect of their previous vote.
+    // - This leads to slightly higher gas costs for each vote, in exchange for a much lower gas cost at the end of the round.
+    // Each tranche is voted on independently: a user can vote in every tranche, with the same locked power in each.
+
+    // Load the tranche's round_id
+    let round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+
+    let vote = cast_vote(
+        deps.storage,
+        tranche_id,
+        round_id,
+        &info.sender,
+        proposal_id,
+    )?;
+    if vote.power.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender has no voting power in this round",
+        )));
+    }
+
+    Ok(Response::new().add_attribute("action", "vote"))
+}
+
+// Casts the voter's vote for the proposal in the round, replacing any vote they already cast in it. A vote without
+// power is not recorded, so the voter ends up without a vote in the round.
+fn cast_vote(
+    storage: &mut dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    voter: &Addr,
+    proposal_id: u64,
+) -> Result<Vote, ContractError> {
+    // Load the round
+    let round = ROUND_MAP.load(storage, (tranche_id, round_id))?;
+
+    // Get any existing vote for this sender and reverse it- this may be a vote for a different proposal (if they are switching their vote),
+    // or it may be a vote for the same proposal (if they have increased their power by locking more and want to update their vote).
+    // TODO: this could be made more gas-efficient by using a separate path with fewer writes if the vote is for the same proposal
+    let vote = VOTE_MAP.load(storage, (tranche_id, round_id, voter.clone()));
+    if let Ok(vote) = vote {
+        // Load the proposal in the vote
+        let mut proposal = PROPOSAL_MAP.load(storage, (tranche_id, round_id, vote.prop_id))?;
+
+        // Remove proposal's old power in PROPS_BY_SCORE
+        PROPS_BY_SCORE.remove(
+            storage,
+            ((tranche_id, round_id), proposal.power.into(), vote.prop_id),
+        );
+
+        // Decrement proposal's power
+        proposal.power -= vote.power;
+
+        // Save the proposal
+        PROPOSAL_MAP.save(storage, (tranche_id, round_id, vote.prop_id), &proposal)?;
+
+        // Add proposal's new power in PROPS_BY_SCORE
+        PROPS_BY_SCORE.save(
+            storage,
+            ((tranche_id, round_id), proposal.power.into(), vote.prop_id),
+            &vote.prop_id,
+        )?;
+
+        // Remove the old vote's power from the proposal's per-tier power
+        update_prop_power_by_tier(
+            storage,
+            tranche_id,
+            round_id,
+            vote.prop_id,
+            &vote.locks,
+            false,
+        )?;
+
+        // Decrement total power voting
+        let total_power_voting = TOTAL_POWER_VOTING.load(storage, (tranche_id, round_id))?;
+        TOTAL_POWER_VOTING.save(
+            storage,
+            (tranche_id, round_id),
+            &(total_power_voting - vote.power),
+        )?;
+
+        // Decrement total tokens voting
+        let total_tokens_voting = TOTAL_TOKENS_VOTING.load(storage, (tranche_id, round_id))?;
+        TOTAL_TOKENS_VOTING.save(
+            storage,
+            (tranche_id, round_id),
+            &(total_tokens_voting - raw_power(&vote.locks)),
+        )?;
+
+        // Delete vote
+        VOTE_MAP.remove(storage, (tranche_id, round_id, voter.clone()));
+    }
+
+    // Get sender's total locked power, keeping track of how much each lock contributes
+    let mut power: Uint128 = Uint128::zero();
+    let mut lock_powers: Vec<LockPower> = vec![];
+    let locks = LOCKS_MAP
+        .prefix(voter.clone())
+        .range(storage, None, None, Order::Ascending);
+
+    for lock in locks {
+        let (lock_id, lock_entry) = lock?;
+
+        // Locks that end before the round does have no power in it, and neither do locks created after it ended
+        // (sticky votes can be carried over into a round that is over but has not been tallied yet)
+        if lock_entry.lock_end <= round.round_end || lock_entry.lock_start >= round.round_end {
+            continue;
+        }
+
+        // Get the remaining lockup time at the end of this round.
+        // This means that their power will be scaled the same by this function no matter when they vote in the round
+        let lockup_time = lock_entry.lock_end.nanos() - round.round_end.nanos();
+
+        // Scale power. This is what implements the different powers for different lockup times.
+        let scaled_power = scale_lockup_power(lockup_time, lock_entry.funds.amount);
+
+        power += scaled_power;
+
+        lock_powers.push(LockPower {
+            lock_id,
+            lockup_time,
+            lockup_tier: lockup_tier(lockup_time),
+            raw_power: lock_entry.funds.amount,
+            scaled_power,
+        });
+    }
+
+    if power.is_zero() {
+        return Ok(Vote {
+            prop_id: proposal_id,
+            power,
+            locks: lock_powers,
+        });
+    }
+
+    // Load the proposal being voted on
+    let mut proposal = load_proposal(storage, tranche_id, round_id, proposal_id)?;
+
+    // Delete the proposal's old power in PROPS_BY_SCORE
+    PROPS_BY_SCORE.remove(
+        storage,
+        ((tranche_id, round_id), proposal.power.into(), proposal_id),
+    );
+
+    // Update proposal's power. Once a proposal received a vote, its creator can no longer edit or withdraw it.
+    proposal.power += power;
+    proposal.received_votes = true;
+
+    // Save the proposal
+    PROPOSAL_MAP.save(storage, (tranche_id, round_id, proposal_id), &proposal)?;
+
+    // Save the proposal's new power in PROPS_BY_SCORE
+    PROPS_BY_SCORE.save(
+        storage,
+        ((tranche_id, round_id), proposal.power.into(), proposal_id),
+        &proposal_id,
+    )?;
+
+    // Add the new vote's power to the proposal's per-tier power
+    update_prop_power_by_tier(
+        storage,
+        tranche_id,
+        round_id,
+        proposal_id,
+        &lock_powers,
+        true,
+    )?;
+
+    // Increment total power voting
+    let total_power_voting = TOTAL_POWER_VOTING.load(storage, (tranche_id, round_id))?;
+    TOTAL_POWER_VOTING.save(
+        storage,
+        (tranche_id, round_id),
+        &(total_power_voting + power),
+    )?;
+
+    // Increment total tokens voting
+    let total_tokens_voting = TOTAL_TOKENS_VOTING.load(storage, (tranche_id, round_id))?;
+    TOTAL_TOKENS_VOTING.save(
+        storage,
+        (tranche_id, round_id),
+        &(total_tokens_voting + raw_power(&lock_powers)),
+    )?;
+
+    // Create vote in Votemap
+    let vote = Vote {
+        prop_id: proposal_id,
+        power,
+        locks: lock_powers,
+    };
+    VOTE_MAP.save(storage, (tranche_id, round_id, voter.clone()), &vote)?;
+
+    Ok(vote)
+}
+
+// SetStickyVote(tranche_id, sticky):
+//     Add the sender to (or remove them from) the sticky voters of the tranche
+//     When opting in, carry the sender's vote over into the current round right away, since ApplyStickyVotes may
+//     already have gone past them in this round
+fn set_sticky_vote(
+    deps: DepsMut,
+    info: MessageInfo,
+    tranche_id: u64,
+    sticky: bool,
+) -> Result<Response, ContractError> {
+    TRANCHE_MAP.load(deps.storage, tranche_id)?;
+
+    let mut applied = false;
+    if sticky {
+        STICKY_VOTERS.save(deps.storage, (tranche_id, info.sender.clone()), &())?;
+
+        let round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+        if round_id > 0 {
+            applied = apply_sticky_vote(deps.storage, tranche_id, round_id, &info.sender)?;
+        }
+    } else {
+        STICKY_VOTERS.remove(deps.storage, (tranche_id, info.sender.clone()));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_sticky_vote")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("sticky", sticky.to_string())
+        .add_attribute("applied", applied.to_string()))
+}
+
+// DesignateSuccessor(tranche_id, round_id, prop_id, successor_prop_id):
+//     Check that the sender created the proposal
+//     Check that the successor is a proposal of the tranche's current round, of a later round than the proposal
+//     Save the successor, so that the proposal's sticky votes go to it
+fn designate_successor(
+    deps: DepsMut,
+    info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+    successor_prop_id: u64,
+) -> Result<Response, ContractError> {
+    let proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+    if proposal.creator != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the proposal's creator",
+        )));
+    }
+
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if round_id >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Successor must be a proposal of a later round",
+        )));
+    }
+    load_proposal(
+        deps.storage,
+        tranche_id,
+        current_round_id,
+        successor_prop_id,
+    )?;
+
+    SUCCESSORS.save(deps.storage, (tranche_id, prop_id), &successor_prop_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "designate_successor")
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("successor_prop_id", successor_prop_id.to_string()))
+}
+
+// ApplyStickyVotes(tranche_id, limit):
+//     Go through the sticky voters of the tranche, starting after the last one handled in the current round
+//     For each voter that has not voted in the current round yet, vote again for the proposal they voted for in the
+//     previous round if it is standing in the current one, or else for its successor, with their current power
+//     Tick does the same in every tranche, and EndRound carries the remaining ones over before a round is tallied,
+//     so that this only needs to be called to spread the work over more transactions
+fn apply_sticky_votes(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if round_id == 0 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "There are no votes to carry over in the first round",
+        )));
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_APPLY_STICKY_VOTES_LIMIT) as usize;
+    let (applied, done) = apply_sticky_votes_batch(deps.storage, tranche_id, round_id, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "apply_sticky_votes")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("applied", applied.to_string())
+        .add_attribute("done", done.to_string()))
+}
+
+// Carries the votes of the next `limit` sticky voters of the tranche over into the round (see ApplyStickyVotes).
+// Returns how many votes were carried over, and whether all sticky voters have been handled.
+fn apply_sticky_votes_batch(
+    storage: &mut dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    limit: usize,
+) -> Result<(u64, bool), ContractError> {
+    // Votes are carried over once per round: start from the beginning when a new round has started
+    let start_after = STICKY_VOTES_CURSOR
+        .may_load(storage, tranche_id)?
+        .filter(|(cursor_round_id, _)| *cursor_round_id == round_id)
+        .map(|(_, voter)| voter);
+
+    let voters = STICKY_VOTERS
+        .prefix(tranche_id)
+        .keys(
+            storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<Addr>>>()?;
+
+    let mut applied = 0u64;
+    for voter in voters.iter() {
+        if apply_sticky_vote(storage, tranche_id, round_id, voter)? {
+            applied += 1;
+        }
+    }
+
+    if let Some(last_voter) = voters.last() {
+        STICKY_VOTES_CURSOR.save(storage, tranche_id, &(round_id, last_voter.clone()))?;
+    }
+
+    Ok((applied, voters.len() < limit))
+}
+
+// Carries a sticky voter's vote over into the round, unless they already voted in it. Returns whether it was
+// carried over.
+fn apply_sticky_vote(
+    storage: &mut dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    voter: &Addr,
+) -> Result<bool, ContractError> {
+    // Votes cast in the current round take precedence
+    if VOTE_MAP.has(storage, (tranche_id, round_id, voter.clone())) {
+        return Ok(false);
+    }
+
+    let previous_vote =
+        match VOTE_MAP.may_load(storage, (tranche_id, round_id - 1, voter.clone()))? {
+            Some(previous_vote) => previous_vote,
+            None => return Ok(false),
+        };
+
+    // The proposal itself if it is standing in the current round, or else its successor
+    let prop_id = if load_proposal(storage, tranche_id, round_id, previous_vote.prop_id).is_ok() {
+        previous_vote.prop_id
+    } else {
+        match SUCCESSORS.may_load(storage, (tranche_id, previous_vote.prop_id))? {
+            Some(successor_prop_id)
+                if load_proposal(storage, tranche_id, round_id, successor_prop_id).is_ok() =>
+            {
+                successor_prop_id
+            }
+            _ => return Ok(false),
+        }
+    };
+
+    let vote = cast_vote(storage, tranche_id, round_id, voter, prop_id)?;
+    Ok(!vote.power.is_zero())
+}
+
+// EndRound():
+//     End the rounds of the tranche that are over, up to MAX_ROUNDS_ENDED_PER_CALL of them
+//     Before a round is tallied, carry the sticky votes that have not been carried over into it yet, a batch at a
+//     time; if there are more of them than fit in one batch, the round is only ended by a later call
+fn end_round(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+) -> Result<Response, ContractError> {
+    if !round_is_over(deps.storage, &env, tranche_id)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+    let ended_rounds = end_rounds(deps.storage, &env, tranche_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "tally")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_events(round_ended_events(tranche_id, &ended_rounds)))
+}
+
+// Ends the rounds of the tranche whose end time has passed, in order, so that the tranche's current round is
+// the one the block time falls in. Fails if there are more of them than can be ended in one call, in which case
+// they have to be ended with EndRound first.
+fn sync_rounds(
+    storage: &mut dyn Storage,
+    env: &Env,
+    tranche_id: u64,
+) -> Result<Vec<(u64, bool)>, ContractError> {
+    let ended_rounds = end_rounds(storage, env, tranche_id)?;
+    if round_is_over(storage, env, tranche_id)? {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Too many rounds of tranche {} to end at once, end them with EndRound first",
+            tranche_id
+        ))));
+    }
+
+    Ok(ended_rounds)
+}
+
+// Ends up to MAX_ROUNDS_ENDED_PER_CALL rounds of the tranche whose end time has passed, in order. Returns the
+// ended rounds, with whether they reached quorum.
+fn end_rounds(
+    storage: &mut dyn Storage,
+    env: &Env,
+    tranche_id: u64,
+) -> Result<Vec<(u64, bool)>, ContractError> {
+    let mut ended_rounds = vec![];
+
+    while ended_rounds.len() < MAX_ROUNDS_ENDED_PER_CALL && round_is_over(storage, env, tranche_id)?
+    {
+        let round_id = ROUND_ID.load(storage, tranche_id)?;
+
+        // Sticky votes must not be lost when Tick didn't get to them during the round, so the ones left are
+        // carried over before the round is tallied. Stop here if that takes more than one batch.
+        if round_id > 0 {
+            let (_, done) = apply_sticky_votes_batch(
+                storage,
+                tranche_id,
+                round_id,
+                DEFAULT_APPLY_STICKY_VOTES_LIMIT as usize,
+            )?;
+            if !done {
+                break;
+            }
+        }
+
+        ended_rounds.push((round_id, finish_round(storage, env, tranche_id)?));
+    }
+
+    Ok(ended_rounds)
+}
+
+// Returns whether the end time of the tranche's current round has passed
+fn round_is_over(storage: &dyn Storage, env: &Env, tranche_id: u64) -> StdResult<bool> {
+    let round_id = ROUND_ID.load(storage, tranche_id)?;
+    Ok(ROUND_MAP.load(storage, (tranche_id, round_id))?.round_end <= env.block.time)
+}
+
+// Syncs the rounds of every tranche (see sync_rounds()). Returns the ids of the tranches.
+fn sync_all_rounds(storage: &mut dyn Storage, env: &Env) -> Result<Vec<u64>, ContractError> {
+    let tranche_ids = TRANCHE_MAP
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u64>>>()?;
+
+    for tranche_id in tranche_ids.iter() {
+        sync_rounds(storage, env, *tranche_id)?;
+    }
+
+    Ok(tranche_ids)
+}
+
+fn round_ended_events(tranche_id: u64, ended_rounds: &[(u64, bool)]) -> Vec<Event> {
+    ended_rounds
+        .iter()
+        .map(|(round_id, quorum_reached)| {
+            Event::new("round_ended")
+                .add_attribute("tranche_id", tranche_id.to_string())
+                .add_attribute("round_id", round_id.to_string())
+                .add_attribute("quorum_reached", quorum_reached.to_string())
+        })
+        .collect()
+}
+
+// Ends the tranche's current round if its end time has passed, and starts the next one. Returns whether the
+// ended round reached quorum.
+fn finish_round(
+    storage: &mut dyn Storage,
+    env: &Env,
+    tranche_id: u64,
+) -> Result<bool, ContractError> {
+    // Check that round has ended by getting latest round and checking if round_end < now
+    let round_id = ROUND_ID.load(storage, tranche_id)?;
+    let mut round = ROUND_MAP.load(storage, (tranche_id, round_id))?;
+
+    if round.round_end > env.block.time {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+
+    // Check whether enough of the locked tokens voted. If not, none of the round's proposals receive liquidity.
+    expire_locks(storage, round.round_end)?;
+    let quorum_reached = quorum_reached(storage, tranche_id, round_id, round.round_end)?;
+    round.quorum_reached = Some(quorum_reached);
+    ROUND_MAP.save(storage, (tranche_id, round_id), &round)?;
+
+    // Record the outcome of the round, and the allocation of each funded proposal
+    let result = round_result(storage, tranche_id, round_id, quorum_reached)?;
+    for funded in result.funded.iter() {
+        let mut proposal = PROPOSAL_MAP.load(storage, (tranche_id, round_id, funded.prop_id))?;
+        proposal.effective_power = funded.effective_power;
+        proposal.percentage = funded.percentage;
+        proposal.amount = funded.amount;
+        PROPOSAL_MAP.save(storage, (tranche_id, round_id, funded.prop_id), &proposal)?;
+    }
+    ROUND_RESULTS.save(storage, (tranche_id, round_id), &result)?;
+
+    // Start the tranche's next round. Its end is derived from the genesis time rather than from the time the
+    // previous round was ended at, so that rounds don't drift when they are ended late.
+    let tranche = TRANCHE_MAP.load(storage, tranche_id)?;
+    start_round(
+        storage,
+        env.block.height,
+        tranche_id,
+        round_id + 1,
+        round_end(&tranche, round_id + 1),
+    )?;
+
+    Ok(quorum_reached)
+}
+
+// Returns the outcome of a round that has ended but was not recorded yet
+fn round_result(
+    storage: &dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    quorum_reached: bool,
+) -> Result<RoundResult, ContractError> {
+    let constants = CONSTANTS.load(storage)?;
+
+    let funded = if quorum_reached {
+        get_top_props(
+            storage,
+            tranche_id,
+            round_id,
+            constants.top_n_props_count as usize,
+        )?
+    } else {
+        vec![]
+    };
+
+    Ok(RoundResult {
+        round_id,
+        quorum_reached,
+        total_power_voting: TOTAL_POWER_VOTING
+            .may_load(storage, (tranche_id, round_id))?
+            .unwrap_or_else(Uint128::zero),
+        total_pool: TRANCHE_MAP.load(storage, tranche_id)?.total_pool
+            + EXTRA_POOL
+                .may_load(storage, (tranche_id, round_id))?
+                .unwrap_or_else(Uint128::zero),
+        funded: funded
+            .into_iter()
+            .map(|prop| FundedProposal {
+                prop_id: prop.prop_id,
+                power: prop.power,
+                effective_power: prop.effective_power,
+                percentage: prop.percentage,
+                amount: prop.amount,
+            })
+            .collect(),
+    })
+}
+
+// Returns whether enough of the tokens that could vote in the round voted in it. Locks that end before the round
+// does can't vote in it, so they don't count. Every action that changes the locked tokens ends the rounds that
+// are over first, so the tokens locked now are the ones that were locked at the end of the round.
+fn quorum_reached(
+    storage: &dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    round_end: Timestamp,
+) -> StdResult<bool> {
+    let quorum = CONSTANTS.load(storage)?.quorum;
+    let total_tokens_voting = TOTAL_TOKENS_VOTING
+        .may_load(storage, (tranche_id, round_id))?
+        .unwrap_or_else(Uint128::zero);
+
+    Ok(total_tokens_voting >= active_locked(storage, round_end)? * quorum)
+}
+
+// Returns the round as it is once the rounds of the tranche are synced with the block time (see sync_rounds()),
+// without writing anything. Queries use this, so that they don't depend on whether an action synced the rounds.
+fn derived_round(deps: Deps, env: &Env, tranche_id: u64, round_id: u64) -> StdResult<Round> {
+    let tranche = TRANCHE_MAP.load(deps.storage, tranche_id)?;
+    if round_id > derived_round_id(deps, env, &tranche)? {
+        return Err(StdError::generic_err("Round has not started yet"));
+    }
+
+    let mut round = ROUND_MAP
+        .may_load(deps.storage, (tranche_id, round_id))?
+        .unwrap_or_else(|| Round {
+            round_id,
+            round_end: round_end(&tranche, round_id),
+            quorum_reached: None,
+        });
+    if round.quorum_reached.is_none() && round.round_end <= env.block.time {
+        round.quorum_reached = Some(quorum_reached(
+            deps.storage,
+            tranche_id,
+            round_id,
+            round.round_end,
+        )?);
+    }
+
+    Ok(round)
+}
+
+// Returns the id of the round that the block time falls in
+fn derived_round_id(deps: Deps, env: &Env, tranche: &Tranche) -> StdResult<u64> {
+    let round_id = ROUND_ID.load(deps.storage, tranche.tranche_id)?;
+    if env.block.time < tranche.genesis_time {
+        return Ok(round_id);
+    }
+
+    let elapsed_rounds =
+        (env.block.time.nanos() - tranche.genesis_time.nanos()) / tranche.round_length;
+    Ok(round_id.max(elapsed_rounds))
+}
+
+// Returns the end time of a round of the tranche
+fn round_end(tranche: &Tranche, round_id: u64) -> Timestamp {
+    tranche
+        .genesis_time
+        .plus_nanos(tranche.round_length * (round_id + 1))
+}
+
+// Makes `round_id` the current round of the tranche, and checkpoints the tokens that can vote in it and their power
+fn start_round(
+    storage: &mut dyn Storage,
+    height: u64,
+    tranche_id: u64,
+    round_id: u64,
+    round_end: Timestamp,
+) -> StdResult<()> {
+    ROUND_ID.save(storage, tranche_id, &round_id)?;
+    ROUND_HEIGHTS.save(storage, (tranche_id, round_id), &height)?;
+    ROUNDS_BY_HEIGHT.save(storage, (tranche_id, height), &round_id)?;
+    let round_power = (
+        active_locked(storage, round_end)?,
+        active_power(storage, round_end)?,
+    );
+    ROUND_POWER.save(storage, (tranche_id, round_id), &round_power, height)?;
+    ROUND_MAP.save(
+        storage,
+        (tranche_id, round_id),
+        &Round {
+            round_end,
+            round_id,
+            quorum_reached: None,
+        },
+    )?;
+    TOTAL_POWER_VOTING.save(storage, (tranche_id, round_id), &Uint128::zero())?;
+    TOTAL_TOKENS_VOTING.save(storage, (tranche_id, round_id), &Uint128::zero())?;
+
+    Ok(())
+}
+
+fn get_top_props(
+    storage: &dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+    num: usize,
+) -> Result<Vec<Proposal>, ContractError> {
+    // Rounds that have ended fund the proposals recorded in their result
+    if let Some(result) = ROUND_RESULTS.may_load(storage, (tranche_id, round_id))? {
+        return Ok(result
+            .funded
+            .iter()
+            .take(num)
+            .map(|funded| PROPOSAL_MAP.load(storage, (tranche_id, round_id, funded.prop_id)))
+            .collect::<StdResult<Vec<Proposal>>>()?);
+    }
+
+    // Rounds that did not reach quorum don't fund any proposal
+    let round = ROUND_MAP.may_load(storage, (tranche_id, round_id))?;
+    if round.and_then(|round| round.quorum_reached) == Some(false) {
+        return Ok(vec![]);
+    }
+
+    // Iterate through PROPS_BY_SCORE to find the top props
+    let top_prop_ids = PROPS_BY_SCORE
+        .sub_prefix((tranche_id, round_id))
+        .range(storage, None, None, Order::Descending)
+        .take(num)
+        .map(|x| x.map(|(_, prop_id)| prop_id))
+        .collect::<StdResult<Vec<u64>>>()?;
+
+    let constants = CONSTANTS.load(storage)?;
+
+    // Proposals need a minimum share of the round's votes to receive liquidity
+    let total_power_voting = TOTAL_POWER_VOTING
+        .may_load(storage, (tranche_id, round_id))?
+        .unwrap_or_else(Uint128::zero);
+    let min_power = total_power_voting * constants.min_vote_share;
+
+    let mut top_props = vec![];
+
+    for prop_id in top_prop_ids {
+        let prop = PROPOSAL_MAP.load(storage, (tranche_id, round_id, prop_id))?;
+
+        // Proposals that lost all of their votes stay in PROPS_BY_SCORE with a score of zero
+        if prop.power.is_zero() || prop.power < min_power {
+            continue;
+        }
+
+        top_props.push(prop);
+    }
+
+    // Apply the consumer chain multiplier, so that the same share of the vote gets consumer chains more liquidity
+    for prop in top_props.iter_mut() {
+        prop.effective_power = if prop.consumer_chain {
+            prop.power * constants.consumer_chain_multiplier
+        } else {
+            prop.power
+        };
+    }
+
+    // Find the share of the pool for each of the top props, capped at the max allocation share
+    let shares = capped_shares(
+        &top_props
+            .iter()
+            .map(|prop| prop.effective_power)
+            .collect::<Vec<Uint128>>(),
+        constants.max_allocation_share,
+    );
+
+    // Liquidity redistributed from expired proposals is deployed on top of the regular pool
+    let total_pool = TRANCHE_MAP.load(storage, tranche_id)?.total_pool
+        + EXTRA_POOL
+            .may_load(storage, (tranche_id, round_id))?
+            .unwrap_or_else(Uint128::zero);
+
+    // Multiply each share by total pool to find exact amount
+    Ok(top_props
+        .into_iter()
+        .zip(shares)
+        .map(|(mut prop, share)| {
+            prop.percentage = share;
+            prop.amount = total_pool * prop.percentage;
+            prop
+        })
+        .collect())
+}
+
+// Splits the pool between proposals proportionally to their power, without giving any of them more than
+// `max_share`. What a capped proposal would have gotten above the cap is split between the others, again
+// proportionally to their power. If every proposal is capped, the rest of the pool is not allocated.
+pub fn capped_shares(powers: &[Uint128], max_share: Decimal) -> Vec<Decimal> {
+    let mut capped = vec![false; powers.len()];
+
+    loop {
+        let (uncapped_power, remaining_share) =
+            uncapped_power_and_share(powers, &capped, max_share);
+        if uncapped_power.is_zero() {
+            break;
+        }
+
+        // Cap every proposal whose proportional share of what is left goes over the cap, then try again
+        let mut newly_capped = false;
+        for (i, power) in powers.iter().enumerate() {
+            if !capped[i]
+                && remaining_share * Decimal::from_ratio(*power, uncapped_power) > max_share
+            {
+                capped[i] = true;
+                newly_capped = true;
+            }
+        }
+
+        if !newly_capped {
+            break;
+        }
+    }
+
+    let (uncapped_power, remaining_share) = uncapped_power_and_share(powers, &capped, max_share);
+
+    powers
+        .iter()
+        .zip(capped)
+        .map(|(power, capped)| {
+            if capped {
+                max_share
+            } else {
+                remaining_share * Decimal::from_ratio(*power, uncapped_power)
+            }
+        })
+        .collect()
+}
+
+// Returns the total power of the proposals that are not capped, and the share of the pool left for them
+fn uncapped_power_and_share(
+    powers: &[Uint128],
+    capped: &[bool],
+    max_share: Decimal,
+) -> (Uint128, Decimal) {
+    let uncapped_power: Uint128 = powers
+        .iter()
+        .zip(capped)
+        .filter(|(_, capped)| !**capped)
+        .map(|(power, _)| *power)
+        .sum();
+    let capped_count = capped.iter().filter(|capped| **capped).count() as u128;
+    let remaining_share =
+        Decimal::one().saturating_sub(max_share * Decimal::from_ratio(capped_count, 1u128));
+
+    (uncapped_power, remaining_share)
+}
+
+// ExecuteProposal(round_id, prop_id):
+//     Check that the round has ended, and that we are still in the round after it
+//     Check that the proposal is one of the top props of the round
+//     Check that the proposal has not already been executed
+//     If an earlier proposal for the same position has finished its term, take its position over and resize it
+//     to the new allocation, instead of withdrawing and redeploying it
+//     Otherwise deploy the proposal's share of the pool, either through a covenant or directly into a pool
+//     Save the outcome of the deployment on the proposal (see reply). If it failed, anyone can retry
+//     until the end of the round after the proposal's round.
+fn execute_proposal(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+
+    // Check that the round has ended
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if round_id >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+
+    // Proposals can only be executed (or retried) during the round after their own
+    if round_id + 1 < current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Execution deadline has passed",
+        )));
+    }
+
+    // Check that this prop is one of the top props, and find out how much liquidity it gets
+    let top_props = get_top_props(
+        deps.storage,
+        tranche_id,
+        round_id,
+        constants.top_n_props_count as usize,
+    )?;
+    let mut proposal = top_props
+        .into_iter()
+        .find(|prop| prop.prop_id == prop_id)
+        .ok_or_else(|| {
+            ContractError::Std(StdError::generic_err("Proposal did not win the round"))
+        })?;
+
+    // Check that the proposal has not already been executed. Failed executions can be retried.
+    match proposal.status {
+        ExecutionStatus::Pending | ExecutionStatus::Failed { .. } => {}
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Proposal already executed",
+            )))
+        }
+    }
+
+    // Take over the position of an earlier proposal for the same position whose term has ended. A proposal
+    // that already took a position over (and failed to resize it) keeps it when it is retried.
+    let previous = match rollover_key(&proposal) {
+        Some(key) if proposal.position.is_none() => ACTIVE_POSITIONS
+            .may_load(deps.storage, key)?
+            .map(|key| PROPOSAL_MAP.load(deps.storage, key))
+            .transpose()?
+            .filter(|previous| {
+                previous.status == ExecutionStatus::Executed
+                    && current_round_id > previous.round_id + previous.covenant_params.duration
+            }),
+        _ => None,
+    };
+
+    if let Some(mut previous) = previous {
+        proposal.position = previous.position.take();
+        proposal.deployed_amount = previous.deployed_amount;
+
+        previous.status = ExecutionStatus::RolledOver { round_id, prop_id };
+        previous.deployed_amount = Uint128::zero();
+        PROPOSAL_MAP.save(
+            deps.storage,
+            (tranche_id, previous.round_id, previous.prop_id),
+            &previous,
+        )?;
+    }
+
+    if proposal.position.is_some() {
+        return rollover_position(deps, &env, &constants, proposal);
+    }
+
+    // Execute proposal
+    let deploy_msg = match proposal.covenant_params.deployment.clone() {
+        DeploymentMethod::Covenant => do_covenant_stuff(&env, &constants, &proposal)?,
+        DeploymentMethod::OsmosisGamm => SubMsg::reply_always(
+            osmosis::join_pool_msg(
+                deps.as_ref(),
+                env.contract.address.to_string(),
+                osmosis_pool_id(&proposal.covenant_params)?,
+                Coin {
+                    denom: constants.pool_denom.clone(),
+                    amount: proposal.amount,
+                },
+                proposal.covenant_params.slippage_tolerance,
+            )?,
+            OSMOSIS_JOIN_POOL_REPLY_ID,
+        ),
+        DeploymentMethod::OsmosisConcentrated {
+            lower_tick,
+            upper_tick,
+        } => SubMsg::reply_always(
+            osmosis::create_position_msg(
+                deps.as_ref(),
+                env.contract.address.to_string(),
+                osmosis_pool_id(&proposal.covenant_params)?,
+                lower_tick,
+                upper_tick,
+                Coin {
+                    denom: constants.pool_denom.clone(),
+                    amount: proposal.amount,
+                },
+                proposal.covenant_params.slippage_tolerance,
+            )?,
+            OSMOSIS_CREATE_POSITION_REPLY_ID,
+        ),
+        DeploymentMethod::Astroport => {
+            let pair = proposal.covenant_params.pool_id.clone();
+            let counterparty_denom = proposal.covenant_params.counterparty_denom.clone();
+
+            // Provide the counterparty in the same ratio as the pair's current reserves
+            let (reserve, counterparty_reserve) = astroport::query_reserves(
+                deps.as_ref(),
+                &pair,
+                &constants.pool_denom,
+                &counterparty_denom,
+            )?;
+            if reserve.is_zero() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Astroport pair has no liquidity",
+                )));
+            }
+            let counterparty_amount = proposal
+                .amount
+                .multiply_ratio(counterparty_reserve, reserve);
+
+            let deposit = proposal
+                .counterparty
+                .as_mut()
+                .filter(|deposit| deposit.amount >= counterparty_amount)
+                .ok_or_else(|| {
+                    ContractError::Std(StdError::generic_err(format!(
+                        "Not enough counterparty deposited, {}{} needed",
+                        counterparty_amount, counterparty_denom
+                    )))
+                })?;
+            deposit.provided = counterparty_amount;
+
+            SubMsg::reply_always(
+                astroport::provide_liquidity_msg(
+                    &pair,
+                    vec![
+                        Coin {
+                            denom: constants.pool_denom.clone(),
+                            amount: proposal.amount,
+                        },
+                        Coin {
+                            denom: counterparty_denom,
+                            amount: counterparty_amount,
+                        },
+                    ],
+                    proposal.covenant_params.slippage_tolerance,
+                )?,
+                ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID,
+            )
+        }
+    };
+
+    // Save the proposal's share of the pool. Its status is updated in the reply once we know whether the
+    // liquidity was deployed.
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+    PENDING_EXECUTION.save(deps.storage, &(tranche_id, round_id, prop_id))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "execute_proposal")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("amount", proposal.amount)
+        .add_submessage(deploy_msg))
+}
+
+// Resizes a position that a proposal took over from an earlier proposal to the proposal's allocation: tops it up
+// if the allocation is bigger than what is deployed, or withdraws the excess if it is smaller
+fn rollover_position(
+    deps: DepsMut,
+    env: &Env,
+    constants: &Constants,
+    mut proposal: Proposal,
+) -> Result<Response, ContractError> {
+    let tranche_id = proposal.tranche_id;
+    let round_id = proposal.round_id;
+    let prop_id = proposal.prop_id;
+    let sender = env.contract.address.to_string();
+    let pool_id = osmosis_pool_id(&proposal.covenant_params)?;
+    let slippage_tolerance = proposal.covenant_params.slippage_tolerance;
+    let position = proposal.position.clone().ok_or_else(|| {
+        ContractError::Std(StdError::generic_err(
+            "Proposal has no position to roll over",
+        ))
+    })?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "rollover_position")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("amount", proposal.amount);
+
+    // Top the position up to the new allocation. The status is updated in the reply.
+    if proposal.amount > proposal.deployed_amount {
+        let top_up = Coin {
+            denom: constants.pool_denom.clone(),
+            amount: proposal.amount - proposal.deployed_amount,
+        };
+
+        let top_up_msg = match position {
+            LiquidityPosition::OsmosisGamm { .. } => SubMsg::reply_always(
+                osmosis::join_pool_msg(deps.as_ref(), sender, pool_id, top_up, slippage_tolerance)?,
+                OSMOSIS_JOIN_POOL_REPLY_ID,
+            ),
+            LiquidityPosition::OsmosisConcentrated { position_id, .. } => SubMsg::reply_always(
+                osmosis::add_to_position_msg(
+                    deps.as_ref(),
+                    sender,
+                    pool_id,
+                    position_id,
+                    top_up,
+                    slippage_tolerance,
+                )?,
+                OSMOSIS_ADD_TO_POSITION_REPLY_ID,
+            ),
+            _ => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Position can't be rolled over",
+                )))
+            }
+        };
+
+        PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+        PENDING_EXECUTION.save(deps.storage, &(tranche_id, round_id, prop_id))?;
+
+        return Ok(response.add_submessage(top_up_msg));
+    }
+
+    // Withdraw the part of the position that is above the new allocation. The withdrawn liquidity stays in the
+    // contract, and the counterparty that comes out with it is swapped back into the pool denom (see reply).
+    let mut withdraw_msg = None;
+    if proposal.amount < proposal.deployed_amount {
+        let excess = Decimal::from_ratio(
+            proposal.deployed_amount - proposal.amount,
+            proposal.deployed_amount,
+        );
+
+        withdraw_msg = Some(match position {
+            LiquidityPosition::OsmosisGamm { pool_id, shares } => {
+                let shares_out = shares * excess;
+                proposal.position = Some(LiquidityPosition::OsmosisGamm {
+                    pool_id,
+                    shares: shares - shares_out,
+                });
+                osmosis::exit_pool_msg(
+                    deps.as_ref(),
+                    sender,
+                    pool_id,
+                    shares_out,
+                    slippage_tolerance,
+                )?
+            }
+            LiquidityPosition::OsmosisConcentrated { position_id, .. } => {
+                osmosis::withdraw_position_msg(deps.as_ref(), sender, position_id, excess)?
+            }
+            _ => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Position can't be rolled over",
+                )))
+            }
+        });
+    }
+
+    proposal.status = ExecutionStatus::Executed;
+    proposal.deployed_amount = proposal.amount;
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+    save_active_position(deps.storage, &proposal)?;
+
+    if let Some(withdraw_msg) = withdraw_msg {
+        response = response.add_submessage(pending_withdrawal(
+            deps,
+            env,
+            &proposal,
+            withdraw_msg,
+            OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID,
+        )?);
+    }
+
+    Ok(response)
+}
+
+// Returns the key that identifies the position a proposal deploys into, if that position can be rolled over into
+// a later proposal of the same tranche. Covenants manage their own positions, and Astroport positions are partly
+// owned by the counterparty depositor, so only direct Osmosis deployments can be rolled over.
+fn rollover_key(proposal: &Proposal) -> Option<String> {
+    let covenant_params = &proposal.covenant_params;
+
+    match covenant_params.deployment {
+        DeploymentMethod::OsmosisGamm => Some(format!(
+            "{}/osmosis_gamm/{}",
+            proposal.tranche_id, covenant_params.pool_id
+        )),
+        DeploymentMethod::OsmosisConcentrated {
+            lower_tick,
+            upper_tick,
+        } => Some(format!(
+            "{}/osmosis_concentrated/{}/{}/{}",
+            proposal.tranche_id, covenant_params.pool_id, lower_tick, upper_tick
+        )),
+        _ => None,
+    }
+}
+
+// Makes the proposal the one that holds its position, so that later proposals for the same position can take it over
+fn save_active_position(storage: &mut dyn Storage, proposal: &Proposal) -> StdResult<()> {
+    if let Some(key) = rollover_key(proposal) {
+        ACTIVE_POSITIONS.save(
+            storage,
+            key,
+            &(proposal.tranche_id, proposal.round_id, proposal.prop_id),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn osmosis_pool_id(covenant_params: &CovenantParams) -> Result<u64, ContractError> {
+    covenant_params
+        .pool_id
+        .parse()
+        .map_err(|_| ContractError::Std(StdError::generic_err("Osmosis pool id must be a number")))
+}
+
+// Builds the message that instantiates the Timewave covenant for a proposal, funded with the proposal's
+// share of the pool. The instantiate message is the configured template with the fields of the covenant
+// that depend on the proposal merged into it (see covenant::ProposalFields).
+fn do_covenant_stuff(
+    env: &Env,
+    constants: &Constants,
+    proposal: &Proposal,
+) -> Result<SubMsg, ContractError> {
+    let instantiate_msg = covenant::instantiate_msg(
+        &constants.covenant_instantiate_template,
+        &proposal.covenant_params,
+    )?;
+
+    Ok(SubMsg::reply_always(
+        WasmMsg::Instantiate {
+            admin: Some(env.contract.address.to_string()),
+            code_id: constants.covenant_code_id,
+            msg: instantiate_msg,
+            funds: vec![Coin {
+                denom: constants.pool_denom.clone(),
+                amount: proposal.amount,
+            }],
+            label: format!(
+                "atom_wars_covenant_{}_{}",
+                proposal.round_id, proposal.prop_id
+            ),
+        },
+        INSTANTIATE_COVENANT_REPLY_ID,
+    ))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        INSTANTIATE_COVENANT_REPLY_ID
+        | OSMOSIS_JOIN_POOL_REPLY_ID
+        | OSMOSIS_CREATE_POSITION_REPLY_ID
+        | OSMOSIS_ADD_TO_POSITION_REPLY_ID
+        | ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID => liquidity_deployed(deps, msg),
+        ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID | OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID => {
+            liquidity_withdrawn(deps, env, msg)
+        }
+        WITHDRAW_TRANCHE_REPLY_ID => tranche_failed(msg),
+        EXECUTE_PROPOSAL_REPLY_ID => execution_failed(msg),
+        id => Err(ContractError::Std(StdError::generic_err(format!(
+            "Unknown reply id: {}",
+            id
+        )))),
+    }
+}
+
+// Records the outcome of a deployment on the proposal it was made for. If the deployment failed, its state
+// changes (including the funds sent) have been reverted, so the proposal can be retried.
+fn liquidity_deployed(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let (tranche_id, round_id, prop_id) = PENDING_EXECUTION.load(deps.storage)?;
+    PENDING_EXECUTION.remove(deps.storage);
+
+    let mut proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+
+    let response = match msg.result.into_result() {
+        Ok(response) => response,
+        Err(reason) => {
+            proposal.status = ExecutionStatus::Failed {
+                reason: reason.clone(),
+            };
+            PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+            return Ok(Response::new()
+                .add_attribute("action", "deployment_failed")
+                .add_attribute("tranche_id", tranche_id.to_string())
+                .add_attribute("round_id", round_id.to_string())
+                .add_attribute("prop_id", prop_id.to_string())
+                .add_attribute("reason", reason));
+        }
+    };
+
+    let position = match msg.id {
+        INSTANTIATE_COVENANT_REPLY_ID => {
+            // The address of the new contract is in the instantiate event
+            let address = response
+                .events
+                .iter()
+                .filter(|event| event.ty == "instantiate")
+                .flat_map(|event| event.attributes.iter())
+                .find(|attr| attr.key == "_contract_address")
+                .map(|attr| Addr::unchecked(attr.value.clone()))
+                .ok_or_else(|| {
+                    ContractError::Std(StdError::generic_err(
+                        "Covenant instantiation did not return a contract address",
+                    ))
+                })?;
+
+            LiquidityPosition::Covenant { address }
+        }
+        OSMOSIS_JOIN_POOL_REPLY_ID => {
+            // Positions that were rolled over get topped up, so the new shares add to the ones taken over
+            let previous_shares = match proposal.position {
+                Some(LiquidityPosition::OsmosisGamm { shares, .. }) => shares,
+                _ => Uint128::zero(),
+            };
+
+            LiquidityPosition::OsmosisGamm {
+                pool_id: osmosis_pool_id(&proposal.covenant_params)?,
+                shares: previous_shares + osmosis::parse_join_pool_response(response.data)?,
+            }
+        }
+        OSMOSIS_CREATE_POSITION_REPLY_ID => LiquidityPosition::OsmosisConcentrated {
+            pool_id: osmosis_pool_id(&proposal.covenant_params)?,
+            position_id: osmosis::parse_create_position_response(response.data)?,
+        },
+        // Adding to a concentrated liquidity position replaces it with a new one
+        OSMOSIS_ADD_TO_POSITION_REPLY_ID => LiquidityPosition::OsmosisConcentrated {
+            pool_id: osmosis_pool_id(&proposal.covenant_params)?,
+            position_id: osmosis::parse_add_to_position_response(response.data)?,
+        },
+        _ => {
+            let pair = deps.api.addr_validate(&proposal.covenant_params.pool_id)?;
+            let pair_info: PairInfo = deps
+                .querier
+                .query_wasm_smart(&pair, &PairQueryMsg::Pair {})?;
+
+            // The pair reports the amount of liquidity tokens minted in the `share` attribute
+            let lp_amount = response
+                .events
+                .iter()
+                .filter(|event| event.ty == "wasm")
+                .flat_map(|event| event.attributes.iter())
+                .find(|attr| attr.key == "share")
+                .map(|attr| attr.value.parse::<Uint128>())
+                .transpose()?
+                .ok_or_else(|| {
+                    ContractError::Std(StdError::generic_err(
+                        "Astroport pair did not return the liquidity provided",
+                    ))
+                })?;
+
+            LiquidityPosition::Astroport {
+                pair,
+                lp_token: pair_info.liquidity_token,
+                lp_amount,
+            }
+        }
+    };
+
+    proposal.status = ExecutionStatus::Executed;
+    proposal.position = Some(position);
+    proposal.deployed_amount = proposal.amount;
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+    save_active_position(deps.storage, &proposal)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "liquidity_deployed")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string()))
+}
+
+// ExpireProposal(round_id, prop_id):
+//     Check that the execution deadline of the proposal has passed
+//     Check that the proposal was funded but never executed successfully
+//     Mark the proposal as expired. A proposal that took a position over but never managed to resize it keeps
+//     the position as it was.
+//     Depending on the failed allocation policy, add its allocation to the current round's pool or leave it in the contract
+fn expire_proposal(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+
+    // Check that the execution deadline (the end of the round after the proposal's round) has passed
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if round_id + 1 >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Execution deadline has not passed yet",
+        )));
+    }
+
+    // Check that this prop was one of the top props
+    let top_props = get_top_props(
+        deps.storage,
+        tranche_id,
+        round_id,
+        constants.top_n_props_count as usize,
+    )?;
+    let mut proposal = top_props
+        .into_iter()
+        .find(|prop| prop.prop_id == prop_id)
+        .ok_or_else(|| {
+            ContractError::Std(StdError::generic_err("Proposal did not win the round"))
+        })?;
+
+    // Check that the proposal was never executed successfully
+    match proposal.status {
+        ExecutionStatus::Pending | ExecutionStatus::Failed { .. } => {}
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Proposal was executed or has already expired",
+            )))
+        }
+    }
+
+    let undeployed_amount = proposal.amount - proposal.deployed_amount;
+    if proposal.position.is_some() {
+        proposal.status = ExecutionStatus::Executed;
+        save_active_position(deps.storage, &proposal)?;
+    } else {
+        proposal.status = ExecutionStatus::Expired;
+    }
+
+    // Give any counterparty deposit back, since it will never be provided
+    let mut response = Response::new();
+    if let Some(deposit) = proposal.counterparty.take() {
+        response = response.add_message(BankMsg::Send {
+            to_address: deposit.depositor.to_string(),
+            amount: vec![Coin {
+                denom: proposal.covenant_params.counterparty_denom.clone(),
+                amount: deposit.amount,
+            }],
+        });
+    }
+
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+    // The current round has not been allocated yet, so its funded proposals get the expired allocation
+    if constants.failed_allocation_policy == FailedAllocationPolicy::Redistribute {
+        let extra_pool = EXTRA_POOL
+            .may_load(deps.storage, (tranche_id, current_round_id))?
+            .unwrap_or_else(Uint128::zero);
+        EXTRA_POOL.save(
+            deps.storage,
+            (tranche_id, current_round_id),
+            &(extra_pool + undeployed_amount),
+        )?;
+    }
+
+    Ok(response
+        .add_attribute("action", "expire_proposal")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("amount", undeployed_amount))
+}
+
+// DepositCounterparty(round_id, prop_id):
+//     Check that the proposal deploys to Astroport and has not been executed
+//     Check that the sender sent the proposal's counterparty denom
+//     Check that the sender is the proposal's counterparty depositor, if there is one already
+//     Add the funds to the proposal's counterparty deposit
+fn deposit_counterparty(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let mut proposal = load_proposal(deps.storage, tranche_id, round_id, prop_id)?;
+
+    // Check that the proposal needs a counterparty deposit
+    if proposal.covenant_params.deployment != DeploymentMethod::Astroport {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal does not need a counterparty deposit",
+        )));
+    }
+
+    // Check that the proposal has not been executed
+    match proposal.status {
+        ExecutionStatus::Pending | ExecutionStatus::Failed { .. } => {}
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Proposal has already been executed or has expired",
+            )))
+        }
+    }
+
+    // Validate that sent funds are the counterparty denom
+    if info.funds.len() != 1 || info.funds[0].denom != proposal.covenant_params.counterparty_denom {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Must send exactly one coin of the counterparty denom",
+        )));
+    }
+
+    let deposit = proposal.counterparty.get_or_insert(CounterpartyDeposit {
+        depositor: info.sender.clone(),
+        amount: Uint128::zero(),
+        provided: Uint128::zero(),
+    });
+
+    // Only one address can deposit counterparty for a proposal, so that it is clear who gets it back
+    if deposit.depositor != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the proposal's counterparty depositor",
+        )));
+    }
+
+    deposit.amount += info.funds[0].amount;
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deposit_counterparty")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("amount", info.funds[0].amount))
+}
+
+// RefundCounterparty(round_id, prop_id):
+//     Check that the sender is the proposal's counterparty depositor
+//     Check that the round has ended and the proposal was not one of the top props
+//     Send the deposit back to the sender
+fn refund_counterparty(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+    let mut proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+
+    // Check that the sender is the depositor
+    let deposit = match proposal.counterparty.take() {
+        Some(deposit) if deposit.depositor == info.sender => deposit,
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Sender has no counterparty deposit on this proposal",
+            )))
+        }
+    };
+
+    // Check that the round has ended
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if round_id >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+
+    // Check that this prop lost. Deposits of props that won are returned when they expire or are withdrawn.
+    let top_props = get_top_props(
+        deps.storage,
+        tranche_id,
+        round_id,
+        constants.top_n_props_count as usize,
+    )?;
+    if top_props.iter().any(|prop| prop.prop_id == prop_id) {
+        return Err(ContractError::Std(StdError::generic_err("Proposal won")));
+    }
+
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "refund_counterparty")
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin {
+                denom: proposal.covenant_params.counterparty_denom,
+                amount: deposit.amount,
+            }],
+        }))
+}
+
+// ClaimDeposit(round_id, prop_id):
+//     Check that the proposal has a deposit that was not claimed yet
+//     Check that the round has ended
+//     If the proposal got the minimum vote share, send the deposit back to its depositor
+//     Otherwise burn it or send it to the community pool
+fn claim_deposit(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+
+    let mut deposit = PROPOSAL_DEPOSITS
+        .may_load(deps.storage, (tranche_id, round_id, prop_id))?
+        .ok_or_else(|| ContractError::Std(StdError::generic_err("Proposal has no deposit")))?;
+    if deposit.status != DepositStatus::Held {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Deposit was already claimed",
+        )));
+    }
+
+    // Check that the round has ended, so that the proposal's power is final
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if round_id >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+
+    let proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+    let total_power_voting = TOTAL_POWER_VOTING.load(deps.storage, (tranche_id, round_id))?;
+    let refunded = !total_power_voting.is_zero()
+        && proposal.power >= total_power_voting * constants.deposit_refund_vote_share;
+
+    let msg: CosmosMsg = if refunded {
+        deposit.status = DepositStatus::Refunded;
+        BankMsg::Send {
+            to_address: deposit.depositor.to_string(),
+            amount: vec![deposit.amount.clone()],
+        }
+        .into()
+    } else {
+        deposit.status = DepositStatus::Forfeited;
+        match constants.forfeited_deposit_destination {
+            ForfeitedDepositDestination::Burn => BankMsg::Burn {
+                amount: vec![deposit.amount.clone()],
+            }
+            .into(),
+            ForfeitedDepositDestination::CommunityPool => MsgFundCommunityPool {
+                amount: vec![deposit.amount.clone().into()],
+                depositor: env.contract.address.to_string(),
+            }
+            .into(),
+        }
+    };
+
+    PROPOSAL_DEPOSITS.save(deps.storage, (tranche_id, round_id, prop_id), &deposit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "claim_deposit")
+        .add_attribute("refunded", refunded.to_string())
+        .add_message(msg))
+}
+
+// WithdrawLiquidity(round_id, prop_id):
+//     Check that the proposal's term has ended, or that a round during its term did not reach quorum and the
+//     quorum failure policy is to withdraw positions
+//     Check that the proposal's liquidity is deployed
+//     Check that the position is not about to be rolled over into a winning proposal of the last round
+//     Withdraw the position, or its first tranche if the proposal asked for a streamed withdrawal. The pool denom
+//     stays in the contract. Astroport counterparty goes back to its depositor, and the counterparty that comes
+//     out of Osmosis pools is swapped back into the pool denom (see reply). Covenants are asked to unwind their
+//     position themselves.
+fn withdraw_liquidity(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+    let proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+
+    // Liquidity is deployed during the round after the proposal's round, and stays deployed for `duration` rounds
+    let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+    if current_round_id <= round_id + proposal.covenant_params.duration
+        && !(constants.quorum_failure_policy == QuorumFailurePolicy::WithdrawPositions
+            && quorum_failed_during_term(deps.as_ref(), &proposal, current_round_id)?)
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal's term has not ended yet",
+        )));
+    }
+
+    // Check that the proposal's liquidity is deployed
+    if proposal.status != ExecutionStatus::Executed {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal's liquidity is not deployed",
+        )));
+    }
+
+    // If a proposal for the same position won the last round, it takes the position over when it is executed
+    if let Some(key) = rollover_key(&proposal) {
+        let last_round_props = get_top_props(
+            deps.storage,
+            tranche_id,
+            current_round_id - 1,
+            constants.top_n_props_count as usize,
+        )?;
+        let rolling_over = last_round_props.iter().any(|prop| {
+            prop.position.is_none()
+                && matches!(
+                    prop.status,
+                    ExecutionStatus::Pending | ExecutionStatus::Failed { .. }
+                )
+                && rollover_key(prop).as_ref() == Some(&key)
+        });
+        if rolling_over {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Position is going to be rolled over",
+            )));
+        }
+
+        if ACTIVE_POSITIONS.may_load(deps.storage, key.clone())?
+            == Some((tranche_id, round_id, prop_id))
+        {
+            ACTIVE_POSITIONS.remove(deps.storage, key);
+        }
+    }
+
+    let tranches = proposal
+        .covenant_params
+        .streamed_withdrawal
+        .as_ref()
+        .map_or(1, |streamed_withdrawal| streamed_withdrawal.tranches);
+
+    withdraw_tranche(deps, &env, proposal, tranches)
+}
+
+// Returns whether one of the rounds that ended while the proposal's liquidity was deployed did not reach quorum
+fn quorum_failed_during_term(
+    deps: Deps,
+    proposal: &Proposal,
+    current_round_id: u64,
+) -> StdResult<bool> {
+    for round_id in proposal.round_id + 1..current_round_id {
+        let round = ROUND_MAP.load(deps.storage, (proposal.tranche_id, round_id))?;
+        if round.quorum_reached == Some(false) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+// WithdrawTranche(round_id, prop_id):
+//     Check that the proposal's liquidity is being withdrawn in tranches
+//     Check that the next tranche is due
+//     Withdraw the next tranche
+fn withdraw_next_tranche(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    let proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+
+    let (tranches_left, next_height) = match proposal.status {
+        ExecutionStatus::Withdrawing {
+            tranches_left,
+            next_height,
+        } => (tranches_left, next_height),
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Proposal's liquidity is not being withdrawn in tranches",
+            )))
+        }
+    };
+
+    if env.block.height < next_height {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Next tranche can't be withdrawn before height {}",
+            next_height
+        ))));
+    }
+
+    withdraw_tranche(deps, &env, proposal, tranches_left)
+}
+
+// Withdraws an equal part of what is left of the position, spread over the `tranches_left` remaining tranches,
+// as long as the pool's price is within the proposal's bounds
+fn withdraw_tranche(
+    deps: DepsMut,
+    env: &Env,
+    mut proposal: Proposal,
+    tranches_left: u64,
+) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+    let tranche_id = proposal.tranche_id;
+    let round_id = proposal.round_id;
+    let prop_id = proposal.prop_id;
+
+    check_price_bounds(deps.as_ref(), &constants, &proposal.covenant_params)?;
+
+    // Withdraw whatever is left in the last tranche, so that no dust is left behind
+    let fraction = Decimal::from_ratio(1u64, tranches_left);
+    let tranche = |amount: Uint128| {
+        if tranches_left == 1 {
+            amount
+        } else {
+            amount * fraction
+        }
+    };
+
+    if tranches_left == 1 {
+        proposal.status = ExecutionStatus::Withdrawn;
+        proposal.deployed_amount = Uint128::zero();
+        unschedule_withdrawal(deps.storage, (tranche_id, round_id, prop_id))?;
+    } else {
+        let interval = proposal
+            .covenant_params
+            .streamed_withdrawal
+            .as_ref()
+            .map_or(1, |streamed_withdrawal| streamed_withdrawal.interval);
+        let next_height = env.block.height + interval;
+
+        proposal.status = ExecutionStatus::Withdrawing {
+            tranches_left: tranches_left - 1,
+            next_height,
+        };
+        proposal.deployed_amount -= tranche(proposal.deployed_amount);
+        schedule_withdrawal(deps.storage, (tranche_id, round_id, prop_id), next_height)?;
+    }
+
+    let response = Response::new()
+        .add_attribute("action", "withdraw_liquidity")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string())
+        .add_attribute("tranches_left", (tranches_left - 1).to_string());
+    let sender = env.contract.address.to_string();
+    let slippage_tolerance = proposal.covenant_params.slippage_tolerance;
+
+    let (withdraw_msg, position) = match proposal.position.clone() {
+        Some(LiquidityPosition::Astroport {
+            pair,
+            lp_token,
+            lp_amount,
+        }) => {
+            let lp_out = tranche(lp_amount);
+            let withdraw_msg = astroport::withdraw_liquidity_msg(
+                deps.as_ref(),
+                pair.as_str(),
+                &lp_token,
+                lp_out,
+                slippage_tolerance,
+            )?;
+
+            (
+                withdraw_msg,
+                LiquidityPosition::Astroport {
+                    pair,
+                    lp_token,
+                    lp_amount: lp_amount - lp_out,
+                },
+            )
+        }
+        Some(LiquidityPosition::OsmosisGamm { pool_id, shares }) => {
+            let shares_out = tranche(shares);
+            let withdraw_msg = osmosis::exit_pool_msg(
+                deps.as_ref(),
+                sender,
+                pool_id,
+                shares_out,
+                slippage_tolerance,
+            )?;
+
+            (
+                withdraw_msg,
+                LiquidityPosition::OsmosisGamm {
+                    pool_id,
+                    shares: shares - shares_out,
+                },
+            )
+        }
+        Some(LiquidityPosition::OsmosisConcentrated {
+            pool_id,
+            position_id,
+        }) => (
+            osmosis::withdraw_position_msg(deps.as_ref(), sender, position_id, fraction)?,
+            LiquidityPosition::OsmosisConcentrated {
+                pool_id,
+                position_id,
+            },
+        ),
+        // Covenants unwind their positions themselves, and can't be withdrawn in tranches (see
+        // validate_covenant_params())
+        Some(LiquidityPosition::Covenant { address }) => (
+            WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: covenant::withdraw_msg(&constants.covenant_withdraw_msg)?,
+                funds: vec![],
+            }
+            .into(),
+            LiquidityPosition::Covenant { address },
+        ),
+        None => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Proposal has no position to withdraw",
+            )))
+        }
+    };
+
+    proposal.position = if tranches_left == 1 {
+        None
+    } else {
+        Some(position)
+    };
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+    // The counterparty that comes out of the pool is accounted for once we know how much of it was withdrawn
+    let reply_id = match proposal.covenant_params.deployment {
+        DeploymentMethod::Covenant => return Ok(response.add_message(withdraw_msg)),
+        DeploymentMethod::Astroport => ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID,
+        DeploymentMethod::OsmosisGamm | DeploymentMethod::OsmosisConcentrated { .. } => {
+            OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID
+        }
+    };
+
+    Ok(response.add_submessage(pending_withdrawal(
+        deps,
+        env,
+        &proposal,
+        withdraw_msg,
+        reply_id,
+    )?))
+}
+
+// Remembers the contract's counterparty balance before withdrawing from the proposal's position, so that the reply
+// knows how much of the counterparty was withdrawn
+fn pending_withdrawal(
+    deps: DepsMut,
+    env: &Env,
+    proposal: &Proposal,
+    withdraw_msg: CosmosMsg,
+    reply_id: u64,
+) -> StdResult<SubMsg> {
+    let counterparty_balance = deps
+        .querier
+        .query_balance(
+            &env.contract.address,
+            &proposal.covenant_params.counterparty_denom,
+        )?
+        .amount;
+    PENDING_WITHDRAWAL.save(
+        deps.storage,
+        &(
+            proposal.tranche_id,
+            proposal.round_id,
+            proposal.prop_id,
+            counterparty_balance,
+        ),
+    )?;
+
+    Ok(SubMsg::reply_on_success(withdraw_msg, reply_id))
+}
+
+// Checks that the price of the pool denom, in units of the counterparty denom, is within the proposal's bounds
+fn check_price_bounds(
+    deps: Deps,
+    constants: &Constants,
+    covenant_params: &CovenantParams,
+) -> Result<(), ContractError> {
+    if covenant_params.min_price.is_none() && covenant_params.max_price.is_none() {
+        return Ok(());
+    }
+
+    let price = match covenant_params.dex {
+        Dex::Osmosis => osmosis::spot_price(
+            deps,
+            osmosis_pool_id(covenant_params)?,
+            &constants.pool_denom,
+            &covenant_params.counterparty_denom,
+        )?,
+        Dex::Astroport => {
+            let (reserve, counterparty_reserve) = astroport::query_reserves(
+                deps,
+                &covenant_params.pool_id,
+                &constants.pool_denom,
+                &covenant_params.counterparty_denom,
+            )?;
+            if reserve.is_zero() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Astroport pair has no liquidity",
+                )));
+            }
+
+            Decimal::from_ratio(counterparty_reserve, reserve)
+        }
+    };
+
+    if covenant_params
+        .min_price
+        .is_some_and(|min_price| price < min_price)
+        || covenant_params
+            .max_price
+            .is_some_and(|max_price| price > max_price)
+    {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Pool price {} is outside of the proposal's price bounds",
+            price
+        ))));
+    }
+
+    Ok(())
+}
+
+// Makes ProcessWithdrawals try to withdraw the proposal's next tranche from `height` on
+fn schedule_withdrawal(
+    storage: &mut dyn Storage,
+    key: (u64, u64, u64),
+    height: u64,
+) -> StdResult<()> {
+    unschedule_withdrawal(storage, key)?;
+    STREAMED_WITHDRAWALS.save(storage, key, &height)?;
+    DUE_WITHDRAWALS.save(storage, (height, key), &())
+}
+
+fn unschedule_withdrawal(storage: &mut dyn Storage, key: (u64, u64, u64)) -> StdResult<()> {
+    if let Some(height) = STREAMED_WITHDRAWALS.may_load(storage, key)? {
+        STREAMED_WITHDRAWALS.remove(storage, key);
+        DUE_WITHDRAWALS.remove(storage, (height, key));
+    }
+
+    Ok(())
+}
+
+// ProcessWithdrawals(limit):
+//     Find the streamed withdrawals whose next tranche is due, oldest first
+//     Withdraw the next tranche of each of them in a separate message, so that a tranche that can't be withdrawn
+//     (e.g. because the price is out of bounds) doesn't hold back the others
+//     Retry them from the next block on, after the ones that are already due. Tranches that are withdrawn are
+//     rescheduled for their next tranche instead.
+fn process_withdrawals(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_PROCESS_WITHDRAWALS_LIMIT) as usize;
+
+    let due_withdrawals = DUE_WITHDRAWALS
+        .keys(
+            deps.storage,
+            None,
+            Some(Bound::inclusive((
+                env.block.height,
+                (u64::MAX, u64::MAX, u64::MAX),
+            ))),
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "process_withdrawals")
+        .add_attribute("count", due_withdrawals.len().to_string());
+
+    for (_, key) in due_withdrawals {
+        schedule_withdrawal(deps.storage, key, env.block.height + 1)?;
+
+        let (tranche_id, round_id, prop_id) = key;
+        response = response.add_submessage(SubMsg::reply_on_error(
+            WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_json_binary(&ExecuteMsg::WithdrawTranche {
+                    tranche_id,
+                    round_id,
+                    prop_id,
+                })?,
+                funds: vec![],
+            },
+            WITHDRAW_TRANCHE_REPLY_ID,
+        ));
+    }
+
+    Ok(response)
+}
+
+// A tranche processed by ProcessWithdrawals could not be withdrawn. Its state changes have been reverted, so it
+// will be retried by a ProcessWithdrawals in a later block.
+fn tranche_failed(msg: Reply) -> Result<Response, ContractError> {
+    let reason = msg.result.into_result().err().unwrap_or_default();
+
+    Ok(Response::new()
+        .add_attribute("action", "tranche_failed")
+        .add_attribute("reason", reason))
+}
+
+// A proposal executed by Tick could not be executed. Its state changes have been reverted, so it is still pending,
+// and Tick tries it again once it has tried the other proposals to execute.
+fn execution_failed(msg: Reply) -> Result<Response, ContractError> {
+    let reason = msg.result.into_result().err().unwrap_or_default();
+
+    Ok(Response::new()
+        .add_attribute("action", "execution_failed")
+        .add_attribute("reason", reason))
+}
+
+// Accounts for the counterparty that came out of a withdrawal. Astroport counterparty is sent back to the depositor,
+// and once the whole position has been withdrawn, whatever part of the deposit was never provided goes back too.
+// Osmosis positions were deployed with the pool denom only, so their counterparty is swapped back into it.
+fn liquidity_withdrawn(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let (tranche_id, round_id, prop_id, counterparty_balance) =
+        PENDING_WITHDRAWAL.load(deps.storage)?;
+    PENDING_WITHDRAWAL.remove(deps.storage);
+
+    let mut proposal = PROPOSAL_MAP.load(deps.storage, (tranche_id, round_id, prop_id))?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "liquidity_withdrawn")
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("prop_id", prop_id.to_string());
+
+    let counterparty_denom = proposal.covenant_params.counterparty_denom.clone();
+    let withdrawn = deps
+        .querier
+        .query_balance(&env.contract.address, &counterparty_denom)?
+        .amount
+        - counterparty_balance;
+
+    if msg.id == OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID {
+        if withdrawn.is_zero() {
+            return Ok(response);
+        }
+
+        let constants = CONSTANTS.load(deps.storage)?;
+        return Ok(response.add_message(osmosis::swap_msg(
+            deps.as_ref(),
+            env.contract.address.to_string(),
+            osmosis_pool_id(&proposal.covenant_params)?,
+            Coin {
+                denom: counterparty_denom,
+                amount: withdrawn,
+            },
+            &constants.pool_denom,
+            proposal.covenant_params.slippage_tolerance,
+        )?));
+    }
+
+    let refund = if proposal.status == ExecutionStatus::Withdrawn {
+        proposal.counterparty.take().map(|deposit| {
+            (
+                deposit.depositor,
+                withdrawn + deposit.amount - deposit.provided,
+            )
+        })
+    } else {
+        proposal
+            .counterparty
+            .as_ref()
+            .map(|deposit| (deposit.depositor.clone(), withdrawn))
+    };
+
+    if let Some((depositor, amount)) = refund {
+        response = response.add_message(BankMsg::Send {
+            to_address: depositor.to_string(),
+            amount: vec![Coin {
+                denom: counterparty_denom,
+                amount,
+            }],
+        });
+    }
+
+    PROPOSAL_MAP.save(deps.storage, (tranche_id, round_id, prop_id), &proposal)?;
+
+    Ok(response)
+}
+
+// AddConsumerChain(denom, chain_id):
+//     Check that the sender is the admin
+//     Register the denom as the token of an ICS consumer chain. Proposals created for it from now on get the
+//     consumer chain multiplier.
+fn add_consumer_chain(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    chain_id: String,
+) -> Result<Response, ContractError> {
+    validate_admin(deps.as_ref(), &info)?;
+
+    CONSUMER_CHAINS.save(
+        deps.storage,
+        denom.clone(),
+        &ConsumerChain {
+            denom: denom.clone(),
+            chain_id: chain_id.clone(),
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_consumer_chain")
+        .add_attribute("denom", denom)
+        .add_attribute("chain_id", chain_id))
+}
+
+// RemoveConsumerChain(denom):
+//     Check that the sender is the admin
+//     Remove the denom from the registry. Proposals that were already created keep their consumer chain flag.
+fn remove_consumer_chain(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response, ContractError> {
+    validate_admin(deps.as_ref(), &info)?;
+
+    CONSUMER_CHAINS.remove(deps.storage, denom.clone());
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_consumer_chain")
+        .add_attribute("denom", denom))
+}
+
+// Key of a registry entry in APPROVED_POOLS
+fn registry_key(entry: &RegistryEntry) -> String {
+    match entry {
+        RegistryEntry::Pool {
+            dex: Dex::Astroport,
+            pool_id,
+        } => format!("pool/astroport/{}", pool_id),
+        RegistryEntry::Pool {
+            dex: Dex::Osmosis,
+            pool_id,
+        } => format!("pool/osmosis/{}", pool_id),
+        RegistryEntry::CounterpartyDenom { denom } => format!("denom/{}", denom),
+    }
+}
+
+// Event emitted for each change to the registry of approved pools, so that indexers can follow it
+fn registry_event(action: &str, entry: &RegistryEntry) -> Event {
+    let event = Event::new("approved_pools").add_attribute("action", action);
+
+    match entry {
+        RegistryEntry::Pool { dex, pool_id } => event
+            .add_attribute("dex", format!("{:?}", dex))
+            .add_attribute("pool_id", pool_id),
+        RegistryEntry::CounterpartyDenom { denom } => {
+            event.add_attribute("counterparty_denom", denom)
+        }
+    }
+}
+
+// ApprovePool(entry):
+//     Check that the sender is the admin or governance
+//     Add the pool or counterparty denom to the registry
+fn approve_pool(
+    deps: DepsMut,
+    info: MessageInfo,
+    entry: RegistryEntry,
+) -> Result<Response, ContractError> {
+    validate_registry_manager(deps.as_ref(), &info)?;
+
+    APPROVED_POOLS.save(deps.storage, registry_key(&entry), &entry)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "approve_pool")
+        .add_event(registry_event("approve", &entry)))
+}
+
+// RevokePool(entry):
+//     Check that the sender is the admin or governance
+//     Remove the pool or counterparty denom from the registry. Proposals that were already created are not affected.
+fn revoke_pool(
+    deps: DepsMut,
+    info: MessageInfo,
+    entry: RegistryEntry,
+) -> Result<Response, ContractError> {
+    validate_registry_manager(deps.as_ref(), &info)?;
+
+    let key = registry_key(&entry);
+    if !APPROVED_POOLS.has(deps.storage, key.clone()) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Pool is not approved",
+        )));
+    }
+    APPROVED_POOLS.remove(deps.storage, key);
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_pool")
+        .add_event(registry_event("revoke", &entry)))
+}
+
+// SetProposalCreationMode(mode):
+//     Check that the sender is the admin or governance
+//     Switch between open and curated proposal creation
+fn set_proposal_creation_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    mode: ProposalCreationMode,
+) -> Result<Response, ContractError> {
+    validate_registry_manager(deps.as_ref(), &info)?;
+
+    let mut constants = CONSTANTS.load(deps.storage)?;
+    constants.proposal_creation_mode = mode.clone();
+    CONSTANTS.save(deps.storage, &constants)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_proposal_creation_mode")
+        .add_event(
+            Event::new("approved_pools")
+                .add_attribute("action", "set_mode")
+                .add_attribute("mode", format!("{:?}", mode)),
+        ))
+}
+
+// The registry of approved pools can be maintained by the admin, and by governance if it is set
+fn validate_registry_manager(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+    if info.sender != constants.admin && Some(&info.sender) != constants.governance.as_ref() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is neither the admin nor governance",
+        )));
+    }
+
+    Ok(())
+}
+
+fn validate_admin(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    if info.sender != CONSTANTS.load(deps.storage)?.admin {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the admin",
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::Tick { limit } => tick(deps, env, limit),
+    }
+}
+
+// Tick(limit):
+//     For each tranche, end the rounds that are over, up to MAX_ROUNDS_ENDED_PER_CALL of them
+//     Carry the next batch of sticky votes over into the current round (see ApplyStickyVotes)
+//     Execute the proposals funded in the last round that have not been executed yet, or whose deployment failed,
+//     each in a separate message so that one proposal that can't be executed doesn't hold back the others
+//     Start after the last proposal tried by the previous Tick, so that proposals that keep failing don't
+//     starve the ones after them
+fn tick(deps: DepsMut, env: Env, limit: Option<u32>) -> Result<Response, ContractError> {
+    let constants = CONSTANTS.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_TICK_EXECUTION_LIMIT) as usize;
+
+    let tranches = TRANCHE_MAP
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.map(|(_, tranche)| tranche))
+        .collect::<StdResult<Vec<Tranche>>>()?;
+
+    let mut response = Response::new().add_attribute("action", "tick");
+
+    for tranche in tranches {
+        let tranche_id = tranche.tranche_id;
+
+        let ended_rounds = end_rounds(deps.storage, &env, tranche_id)?;
+        response = response.add_events(round_ended_events(tranche_id, &ended_rounds));
+
+        // The proposals of the last round can only be executed once the tranche has caught up
+        if round_is_over(deps.storage, &env, tranche_id)? {
+            continue;
+        }
+
+        let current_round_id = ROUND_ID.load(deps.storage, tranche_id)?;
+        if current_round_id == 0 {
+            continue;
+        }
+
+        apply_sticky_votes_batch(
+            deps.storage,
+            tranche_id,
+            current_round_id,
+            DEFAULT_APPLY_STICKY_VOTES_LIMIT as usize,
+        )?;
+
+        let round_id = current_round_id - 1;
+        let top_props = get_top_props(
+            deps.storage,
+            tranche_id,
+            round_id,
+            constants.top_n_props_count as usize,
+        )?;
+
+        // Go through the proposals in turn, starting after the last one tried in this round
+        let start = match TICK_CURSOR.may_load(deps.storage, tranche_id)? {
+            Some((cursor_round_id, last_prop_id)) if cursor_round_id == round_id => top_props
+                .iter()
+                .position(|prop| prop.prop_id == last_prop_id)
+                .map_or(0, |position| position + 1),
+            _ => 0,
+        };
+        let pending_props = top_props
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(top_props.len())
+            .filter(|prop| {
+                matches!(
+                    prop.status,
+                    ExecutionStatus::Pending | ExecutionStatus::Failed { .. }
+                )
+            })
+            .take(limit)
+            .collect::<Vec<_>>();
+
+        if let Some(last_prop) = pending_props.last() {
+            TICK_CURSOR.save(deps.storage, tranche_id, &(round_id, last_prop.prop_id))?;
+        }
+
+        for prop in pending_props {
+            response = response.add_submessage(SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    msg: to_json_binary(&ExecuteMsg::ExecuteProposal {
+                        tranche_id,
+                        round_id: prop.round_id,
+                        prop_id: prop.prop_id,
+                    })?,
+                    funds: vec![],
+                },
+                EXECUTE_PROPOSAL_REPLY_ID,
+            ));
+        }
+    }
+
+    Ok(response)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Tranches {} => query_tranches(deps),
+        QueryMsg::ConsumerChains {} => query_consumer_chains(deps),
+        QueryMsg::ApprovedPools {} => query_approved_pools(deps),
+        QueryMsg::StandingProposals { tranche_id } => query_standing_proposals(deps, tranche_id),
+        QueryMsg::TotalLocked { height } => query_total_locked(deps, env, height),
+        QueryMsg::LockedTokens { address, height } => {
+            query_locked_tokens(deps, env, address, height)
+        }
+        QueryMsg::VotingPowerAtHeight { address, height } => {
+            query_voting_power_at_height(deps, env, address, height)
+        }
+        QueryMsg::TotalPowerAtHeight { height } => query_total_power_at_height(deps, env, height),
+        QueryMsg::VotingPowerAtRound {
+            address,
+            tranche_id,
+            round_id,
+        } => query_voting_power_at_round(deps, address, tranche_id, round_id),
+        QueryMsg::TotalPowerAtRound {
+            tranche_id,
+            round_id,
+        } => query_total_power_at_round(deps, tranche_id, round_id),
+        QueryMsg::Info {} => query_info(),
+        QueryMsg::Dao {} => query_dao(deps),
+        QueryMsg::OwnerOf {
+            token_id,
+            include_expired,
+        } => query_owner_of(deps, env, token_id, include_expired),
+        QueryMsg::NftInfo { token_id } => query_nft_info(deps, token_id),
+        QueryMsg::AllNftInfo {
+            token_id,
+            include_expired,
+        } => query_all_nft_info(deps, env, token_id, include_expired),
+        QueryMsg::Approval {
+            token_id,
+            spender,
+            include_expired,
+        } => query_approval(deps, env, token_id, spender, include_expired),
+        QueryMsg::Approvals {
+            token_id,
+            include_expired,
+        } => query_approvals(deps, env, token_id, include_expired),
+        QueryMsg::Operator {
+            owner,
+            operator,
+            include_expired,
+        } => query_operator(deps, env, owner, operator, include_expired),
+        QueryMsg::AllOperators {
+            owner,
+            include_expired,
+            start_after,
+            limit,
+        } => query_all_operators(deps, env, owner, include_expired, start_after, limit),
+        QueryMsg::NumTokens {} => query_num_tokens(deps),
+        QueryMsg::Tokens {
+            owner,
+            start_after,
+            limit,
+        } => query_tokens(deps, owner, start_after, limit),
+        QueryMsg::AllTokens { start_after, limit } => query_all_tokens(deps, start_after, limit),
+        QueryMsg::CurrentRound { tranche_id } => query_current_round(deps, env, tranche_id),
+        QueryMsg::Round {
+            tranche_id,
+            round_id,
+        } => query_round(deps, env, tranche_id, round_id),
+        QueryMsg::RoundResult {
+            tranche_id,
+            round_id,
+        } => query_round_result(deps, env, tranche_id, round_id),
+        QueryMsg::Proposal {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => query_proposal(deps, tranche_id, round_id, prop_id),
+        QueryMsg::UserVote {
+            tranche_id,
+            round_id,
+            address,
+        } => query_user_vote(deps, tranche_id, round_id, address),
+        QueryMsg::ProposalPowerByTier {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => query_proposal_power_by_tier(deps, tranche_id, round_id, prop_id),
+        QueryMsg::ProposalDeposit {
+            tranche_id,
+            round_id,
+            prop_id,
+        } => query_proposal_deposit(deps, tranche_id, round_id, prop_id),
+        QueryMsg::TopNProposals {
+            tranche_id,
+            round_id,
+            number_of_proposals,
+        } => query_top_n_proposals(deps, env, tranche_id, round_id, number_of_proposals),
+    }
+}
+
+pub fn query_tranches(deps: Deps) -> StdResult<Binary> {
+    let tranches = TRANCHE_MAP
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.map(|(_, tranche)| tranche))
+        .collect::<StdResult<Vec<Tranche>>>()?;
+    to_json_binary(&TranchesResponse { tranches })
+}
+
+pub fn query_consumer_chains(deps: Deps) -> StdResult<Binary> {
+    let consumer_chains = CONSUMER_CHAINS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.map(|(_, consumer_chain)| consumer_chain))
+        .collect::<StdResult<Vec<ConsumerChain>>>()?;
+    to_json_binary(&ConsumerChainsResponse { consumer_chains })
+}
+
+pub fn query_approved_pools(deps: Deps) -> StdResult<Binary> {
+    let mode = CONSTANTS.load(deps.storage)?.proposal_creation_mode;
+    let entries = APPROVED_POOLS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.map(|(_, entry)| entry))
+        .collect::<StdResult<Vec<RegistryEntry>>>()?;
+    to_json_binary(&ApprovedPoolsResponse { mode, entries })
+}
+
+pub fn query_standing_proposals(deps: Deps, tranche_id: u64) -> StdResult<Binary> {
+    let standing_proposals = STANDING_PROPOSALS
+        .prefix(tranche_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.map(|(_, standing)| standing))
+        .collect::<StdResult<Vec<StandingProposal>>>()?;
+    to_json_binary(&StandingProposalsResponse { standing_proposals })
+}
+
+pub fn query_total_locked(deps: Deps, env: Env, height: Option<u64>) -> StdResult<Binary> {
+    let height = height.unwrap_or(env.block.height);
+    let total_locked = TOTAL_LOCKED
+        .may_load_at_height(deps.storage, height)?
+        .unwrap_or_else(Uint128::zero);
+    to_json_binary(&TotalLockedResponse { total_locked })
+}
+
+pub fn query_locked_tokens(
+    deps: Deps,
+    env: Env,
+    address: String,
+    height: Option<u64>,
+) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let height = height.unwrap_or(env.block.height);
+    let locked = LOCKED_TOKENS
+        .may_load_at_height(deps.storage, &address, height)?
+        .unwrap_or_else(Uint128::zero);
+    to_json_binary(&LockedTokensResponse { locked, height })
+}
+
+// Returns the round of the tranche that was the current one at the height, i.e. the last one started before it
+fn round_at_height(
+    storage: &dyn Storage,
+    tranche_id: u64,
+    height: u64,
+) -> StdResult<Option<Round>> {
+    ROUNDS_BY_HEIGHT
+        .prefix(tranche_id)
+        .range(
+            storage,
+            None,
+            Some(Bound::exclusive(height)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?
+        .map(|(_, round_id)| ROUND_MAP.load(storage, (tranche_id, round_id)))
+        .transpose()
+}
+
+// Returns the height at which the round's voting power was final, i.e. the height the next round started at, or
+// None if the round is still the tranche's current one
+fn round_end_height(
+    storage: &dyn Storage,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Option<u64>> {
+    ROUND_HEIGHTS.may_load(storage, (tranche_id, round_id + 1))
+}
+
+// Picks the voting power or the tokens, depending on whether voting power is time weighted
+fn weighted_power(deps: Deps, (locked, power): (Uint128, Uint128)) -> StdResult<Uint128> {
+    if CONSTANTS.load(deps.storage)?.time_weighted_voting_power {
+        Ok(power)
+    } else {
+        Ok(locked)
+    }
+}
+
+fn dao_tranche_id(deps: Deps) -> StdResult<Option<u64>> {
+    TRANCHE_MAP
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next()
+        .transpose()
+}
+
+pub fn query_voting_power_at_height(
+    deps: Deps,
+    env: Env,
+    address: String,
+    height: Option<u64>,
+) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let height = height.unwrap_or(env.block.height);
+
+    // Without time weighting, the voting power is the tokens locked at the height
+    if !CONSTANTS.load(deps.storage)?.time_weighted_voting_power {
+        let power = LOCKED_TOKENS
+            .may_load_at_height(deps.storage, &address, height)?
+            .unwrap_or_else(Uint128::zero);
+        return to_json_binary(&VotingPowerAtHeightResponse { power, height });
+    }
+
+    let mut power = Uint128::zero();
+    if let Some(tranche_id) = dao_tranche_id(deps)? {
+        if let Some(round) = round_at_height(deps.storage, tranche_id, height)? {
+            let lock_ends = LOCK_ENDS
+                .may_load_at_height(deps.storage, &address, height)?
+                .unwrap_or_default();
+            power = lock_ends_power(&lock_ends, round.round_end).1;
+        }
+    }
+
+    to_json_binary(&VotingPowerAtHeightResponse { power, height })
+}
+
+pub fn query_total_power_at_height(deps: Deps, env: Env, height: Option<u64>) -> StdResult<Binary> {
+    let height = height.unwrap_or(env.block.height);
+
+    // Without time weighting, the total power is the total of the tokens locked at the height
+    if !CONSTANTS.load(deps.storage)?.time_weighted_voting_power {
+        let power = TOTAL_LOCKED
+            .may_load_at_height(deps.storage, height)?
+            .unwrap_or_else(Uint128::zero);
+        return to_json_binary(&TotalPowerAtHeightResponse { power, height });
+    }
+
+    let mut power = Uint128::zero();
+    if let Some(tranche_id) = dao_tranche_id(deps)? {
+        if let Some(round) = round_at_height(deps.storage, tranche_id, height)? {
+            power = ROUND_POWER
+                .may_load_at_height(deps.storage, (tranche_id, round.round_id), height)?
+                .unwrap_or_default()
+                .1;
+        }
+    }
+
+    to_json_binary(&TotalPowerAtHeightResponse { power, height })
+}
+
+pub fn query_voting_power_at_round(
+    deps: Deps,
+    address: String,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let round = ROUND_MAP.load(deps.storage, (tranche_id, round_id))?;
+
+    let lock_ends = match round_end_height(deps.storage, tranche_id, round_id)? {
+        Some(height) => LOCK_ENDS.may_load_at_height(deps.storage, &address, height)?,
+        None => LOCK_ENDS.may_load(deps.storage, &address)?,
+    }
+    .unwrap_or_default();
+
+    let power = weighted_power(deps, lock_ends_power(&lock_ends, round.round_end))?;
+    to_json_binary(&VotingPowerAtRoundResponse { power, round_id })
+}
+
+pub fn query_total_power_at_round(deps: Deps, tranche_id: u64, round_id: u64) -> StdResult<Binary> {
+    // The round's power doesn't change after it ended
+    let round_power = ROUND_POWER.load(deps.storage, (tranche_id, round_id))?;
+
+    let power = weighted_power(deps, round_power)?;
+    to_json_binary(&TotalPowerAtRoundResponse { power, round_id })
+}
+
+pub fn query_info() -> StdResult<Binary> {
+    to_json_binary(&InfoResponse {
+        info: ContractInfo {
+            contract: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+    })
+}
+
+pub fn query_dao(deps: Deps) -> StdResult<Binary> {
+    to_json_binary(&CONSTANTS.load(deps.storage)?.dao)
+}
+
+fn owner_of(
+    deps: Deps,
+    env: &Env,
+    token_id: &str,
+    include_expired: Option<bool>,
+) -> StdResult<OwnerOfResponse> {
+    let owner = LOCK_OWNERS.load(deps.storage, parse_token_id(token_id)?)?;
+    Ok(OwnerOfResponse {
+        owner: owner.to_string(),
+        approvals: token_approvals(deps, env, token_id, include_expired)?,
+    })
+}
+
+// Returns the approvals of the lock, leaving out the expired ones unless `include_expired` is set
+fn token_approvals(
+    deps: Deps,
+    env: &Env,
+    token_id: &str,
+    include_expired: Option<bool>,
+) -> StdResult<Vec<Approval>> {
+    let include_expired = include_expired.unwrap_or(false);
+    TOKEN_APPROVALS
+        .prefix(parse_token_id(token_id)?)
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|approval| {
+            approval.as_ref().map_or(true, |(_, expires)| {
+                include_expired || !expires.is_expired(&env.block)
+            })
+        })
+        .map(|approval| {
+            approval.map(|(spender, expires)| Approval {
+                spender: spender.to_string(),
+                expires,
+            })
+        })
+        .collect()
+}
+
+fn nft_info(deps: Deps, token_id: &str) -> StdResult<NftInfoResponse> {
+    let lock_id = parse_token_id(token_id)?;
+    let owner = LOCK_OWNERS.load(deps.storage, lock_id)?;
+    Ok(NftInfoResponse {
+        token_uri: None,
+        extension: LOCKS_MAP.load(deps.storage, (owner, lock_id))?,
+    })
+}
+
+pub fn query_owner_of(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: Option<bool>,
+) -> StdResult<Binary> {
+    to_json_binary(&owner_of(deps, &env, &token_id, include_expired)?)
+}
+
+pub fn query_nft_info(deps: Deps, token_id: String) -> StdResult<Binary> {
+    to_json_binary(&nft_info(deps, &token_id)?)
+}
+
+pub fn query_all_nft_info(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: Option<bool>,
+) -> StdResult<Binary> {
+    to_json_binary(&AllNftInfoResponse {
+        access: owner_of(deps, &env, &token_id, include_expired)?,
+        info: nft_info(deps, &token_id)?,
+    })
+}
+
+pub fn query_approval(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    spender: String,
+    include_expired: Option<bool>,
+) -> StdResult<Binary> {
+    let lock_id = parse_token_id(&token_id)?;
+    let spender = deps.api.addr_validate(&spender)?;
+    let owner = LOCK_OWNERS.load(deps.storage, lock_id)?;
+
+    // The owner can always transfer the lock
+    if spender == owner {
+        return to_json_binary(&ApprovalResponse {
+            approval: Approval {
+                spender: spender.to_string(),
+                expires: Expiration::Never {},
+            },
+        });
+    }
+
+    let expires = TOKEN_APPROVALS
+        .may_load(deps.storage, (lock_id, &spender))?
+        .filter(|expires| include_expired.unwrap_or(false) || !expires.is_expired(&env.block))
+        .ok_or_else(|| StdError::not_found("Approval"))?;
+    to_json_binary(&ApprovalResponse {
+        approval: Approval {
+            spender: spender.to_string(),
+            expires,
+        },
+    })
+}
+
+pub fn query_approvals(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: Option<bool>,
+) -> StdResult<Binary> {
+    to_json_binary(&ApprovalsResponse {
+        approvals: token_approvals(deps, &env, &token_id, include_expired)?,
+    })
+}
+
+pub fn query_operator(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    operator: String,
+    include_expired: Option<bool>,
+) -> StdResult<Binary> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let operator = deps.api.addr_validate(&operator)?;
+
+    let expires = OPERATORS
+        .may_load(deps.storage, (&owner, &operator))?
+        .filter(|expires| include_expired.unwrap_or(false) || !expires.is_expired(&env.block))
+        .ok_or_else(|| StdError::not_found("Operator"))?;
+    to_json_binary(&OperatorResponse {
+        approval: Approval {
+            spender: operator.to_string(),
+            expires,
+        },
+    })
+}
+
+pub fn query_all_operators(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    include_expired: Option<bool>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let start_after = start_after
+        .map(|operator| deps.api.addr_validate(&operator))
+        .transpose()?;
+    let limit = limit
+        .unwrap_or(DEFAULT_TOKENS_QUERY_LIMIT)
+        .min(MAX_TOKENS_QUERY_LIMIT) as usize;
+    let include_expired = include_expired.unwrap_or(false);
+
+    let operators = OPERATORS
+        .prefix(&owner)
+        .range(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .filter(|operator| {
+            operator.as_ref().map_or(true, |(_, expires)| {
+                include_expired || !expires.is_expired(&env.block)
+            })
+        })
+        .take(limit)
+        .map(|operator| {
+            operator.map(|(operator, expires)| Approval {
+                spender: operator.to_string(),
+                expires,
+            })
+        })
+        .collect::<StdResult<Vec<Approval>>>()?;
+    to_json_binary(&OperatorsResponse { operators })
+}
+
+pub fn query_num_tokens(deps: Deps) -> StdResult<Binary> {
+    let count = NUM_TOKENS.load(deps.storage)?;
+    to_json_binary(&NumTokensResponse { count })
+}
+
+pub fn query_tokens(
+    deps: Deps,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let start = start_after
+        .map(|token_id| parse_token_id(&token_id))
+        .transpose()?
+        .map(Bound::exclusive);
+    let limit = limit
+        .unwrap_or(DEFAULT_TOKENS_QUERY_LIMIT)
+        .min(MAX_TOKENS_QUERY_LIMIT) as usize;
+
+    let tokens = LOCKS_MAP
+        .prefix(owner)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|lock_id| lock_id.map(|lock_id| lock_id.to_string()))
+        .collect::<StdResult<Vec<String>>>()?;
+    to_json_binary(&TokensResponse { tokens })
+}
+
+pub fn query_all_tokens(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let start = start_after
+        .map(|token_id| parse_token_id(&token_id))
+        .transpose()?
+        .map(Bound::exclusive);
+    let limit = limit
+        .unwrap_or(DEFAULT_TOKENS_QUERY_LIMIT)
+        .min(MAX_TOKENS_QUERY_LIMIT) as usize;
+
+    let tokens = LOCK_OWNERS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|lock_id| lock_id.map(|lock_id| lock_id.to_string()))
+        .collect::<StdResult<Vec<String>>>()?;
+    to_json_binary(&TokensResponse { tokens })
+}
+
+pub fn query_current_round(deps: Deps, env: Env, tranche_id: u64) -> StdResult<Binary> {
+    let tranche = TRANCHE_MAP.load(deps.storage, tranche_id)?;
+    let round_id = derived_round_id(deps, &env, &tranche)?;
+    let round = derived_round(deps, &env, tranche_id, round_id)?;
+    to_json_binary(&CurrentRoundResponse { round })
+}
+
+pub fn query_round(deps: Deps, env: Env, tranche_id: u64, round_id: u64) -> StdResult<Binary> {
+    let round = derived_round(deps, &env, tranche_id, round_id)?;
+    to_json_binary(&RoundResponse { round })
+}
+
+pub fn query_round_result(
+    deps: Deps,
+    env: Env,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Binary> {
+    let result = match ROUND_RESULTS.may_load(deps.storage, (tranche_id, round_id))? {
+        Some(result) => result,
+        // The round may have ended without being synced yet, in which case its result is not recorded
+        None => match derived_round(deps, &env, tranche_id, round_id)?.quorum_reached {
+            Some(quorum_reached) => {
+                round_result(deps.storage, tranche_id, round_id, quorum_reached)
+                    .map_err(|err| StdError::generic_err(err.to_string()))?
+            }
+            None => return Err(StdError::generic_err("Round has not ended yet")),
+        },
+    };
+    to_json_binary(&RoundResultResponse { result })
+}
+
+pub fn query_proposal(
+    deps: Deps,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> StdResult<Binary> {
+    let proposal = load_proposal(deps.storage, tranche_id, round_id, prop_id)?;
+    to_json_binary(&ProposalResponse { proposal })
+}
+
+pub fn query_user_vote(
+    deps: Deps,
+    tranche_id: u64,
+    round_id: u64,
+    address: String,
+) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let vote = VOTE_MAP.load(deps.storage, (tranche_id, round_id, address.clone()))?;
+    to_json_binary(&UserVoteResponse { address, vote })
+}
+
+pub fn query_proposal_power_by_tier(
+    deps: Deps,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> StdResult<Binary> {
+    let tiers = PROP_POWER_BY_TIER
+        .prefix(((tranche_id, round_id), prop_id))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.map(|(lockup_tier, power)| TierPower { lockup_tier, power }))
+        .collect::<StdResult<Vec<TierPower>>>()?;
+    to_json_binary(&ProposalPowerByTierResponse { tiers })
+}
+
+pub fn query_proposal_deposit(
+    deps: Deps,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> StdResult<Binary> {
+    let deposit = PROPOSAL_DEPOSITS.load(deps.storage, (tranche_id, round_id, prop_id))?;
+    to_json_binary(&ProposalDepositResponse { deposit })
+}
+
+pub fn query_top_n_proposals(
+    deps: Deps,
+    env: Env,
+    tranche_id: u64,
+    round_id: u64,
+    number_of_proposals: usize,
+) -> StdResult<Binary> {
+    // The round may have ended without being synced yet, in which case its quorum is not recorded
+    if derived_round(deps, &env, tranche_id, round_id)?.quorum_reached == Some(false) {
+        return to_json_binary(&TopNProposalsResponse { proposals: vec![] });
+    }
 
-pub fn query_count(_deps: Deps) -> StdResult<Binary> {
-    to_json_binary(&(CountResponse { count: 0 }))
+    let proposals = get_top_props(deps.storage, tranche_id, round_id, number_of_proposals)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    to_json_binary(&TopNProposalsResponse { proposals })
 }
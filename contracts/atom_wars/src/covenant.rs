@@ -0,0 +1,104 @@
+// Builds the instantiate message of the Timewave covenant that a proposal's liquidity is deployed with.
+// Covenant messages deny unknown fields, so only the fields below are taken from the proposal. Everything
+// else (code ids, timeouts, clock settings, ...) comes from the instantiate template in the config.
+
+use std::collections::BTreeMap;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_json, to_json_binary, Binary, Decimal, StdError, StdResult};
+use serde_cw_value::Value;
+
+use crate::state::CovenantParams;
+
+#[cw_serde]
+pub struct ProposalFields {
+    pub pool_address: String,
+    // Only set when the proposal bounds the price on both sides
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_price_config: Option<PoolPriceConfig>,
+    pub covenant_party_config: CovenantPartyConfig,
+}
+
+#[cw_serde]
+pub struct PoolPriceConfig {
+    pub expected_spot_price: Decimal,
+    pub acceptable_price_spread: Decimal,
+}
+
+// Only the receiving party's address comes from the proposal, the rest of the party config comes from the template
+#[cw_serde]
+pub struct CovenantPartyConfig {
+    pub addr: String,
+}
+
+impl ProposalFields {
+    pub fn new(covenant_params: &CovenantParams) -> Self {
+        let pool_price_config = match (covenant_params.min_price, covenant_params.max_price) {
+            (Some(min_price), Some(max_price)) => Some(PoolPriceConfig {
+                expected_spot_price: (min_price + max_price) * Decimal::percent(50),
+                acceptable_price_spread: (max_price - min_price) * Decimal::percent(50),
+            }),
+            _ => None,
+        };
+
+        ProposalFields {
+            pool_address: covenant_params.pool_id.clone(),
+            pool_price_config,
+            covenant_party_config: CovenantPartyConfig {
+                addr: covenant_params.party_receiver_addr.clone(),
+            },
+        }
+    }
+}
+
+// Parses the instantiate template, which has to be a JSON object. JSON is handled with serde-json-wasm, which
+// rejects floats, so that no float operations end up in the contract.
+pub fn parse_template(template: &str) -> StdResult<BTreeMap<Value, Value>> {
+    parse_object(template)
+        .ok_or_else(|| StdError::generic_err("Covenant instantiate template must be a JSON object"))
+}
+
+// Returns the message that unwinds a covenant's position, which has to be a JSON object
+pub fn withdraw_msg(msg: &str) -> StdResult<Binary> {
+    let msg = parse_object(msg)
+        .ok_or_else(|| StdError::generic_err("Covenant withdraw message must be a JSON object"))?;
+
+    to_json_binary(&Value::Map(msg))
+}
+
+fn parse_object(json: &str) -> Option<BTreeMap<Value, Value>> {
+    match from_json(json) {
+        Ok(Value::Map(object)) => Some(object),
+        _ => None,
+    }
+}
+
+// Merges the proposal's fields into the template. Nested objects are merged key by key, and keys set by the
+// template can't be overridden by proposals.
+pub fn instantiate_msg(template: &str, covenant_params: &CovenantParams) -> StdResult<Binary> {
+    let mut msg = parse_template(template)?;
+
+    let fields = match serde_cw_value::to_value(ProposalFields::new(covenant_params)) {
+        Ok(Value::Map(fields)) => fields,
+        _ => {
+            return Err(StdError::generic_err(
+                "Covenant params must serialize to a JSON object",
+            ))
+        }
+    };
+    merge(&mut msg, fields);
+
+    to_json_binary(&Value::Map(msg))
+}
+
+fn merge(template: &mut BTreeMap<Value, Value>, fields: BTreeMap<Value, Value>) {
+    for (key, value) in fields {
+        match (template.get_mut(&key), value) {
+            (Some(Value::Map(template_value)), Value::Map(value)) => merge(template_value, value),
+            (Some(_), _) => {}
+            (None, value) => {
+                template.insert(key, value);
+            }
+        }
+    }
+}
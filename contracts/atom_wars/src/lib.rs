@@ -1,9 +1,29 @@
+mod astroport;
 pub mod contract;
+mod covenant;
 mod error;
 mod msg;
+mod osmosis;
 mod state;
 
+#[cfg(test)]
+mod testing;
+
 pub use msg::{
-    ExecuteMsg,InstantiateMsg, QueryMsg, CountResponse, 
+    AllNftInfoResponse, Approval, ApprovalResponse, ApprovalsResponse, ApprovedPoolsResponse,
+    ConsumerChainsResponse, ContractInfo, CurrentRoundResponse, Cw721ReceiveMsg, ExecuteMsg,
+    InfoResponse, InstantiateMsg, LockedTokensResponse, NftInfoResponse, NumTokensResponse,
+    OperatorResponse, OperatorsResponse, OwnerOfResponse, ProposalDepositResponse,
+    ProposalPowerByTierResponse, ProposalResponse, QueryMsg, ReceiverExecuteMsg, RoundResponse,
+    RoundResultResponse, StandingProposalsResponse, SudoMsg, TierPower, TokensResponse,
+    TopNProposalsResponse, TotalLockedResponse, TotalPowerAtHeightResponse,
+    TotalPowerAtRoundResponse, TrancheInfo, TranchesResponse, UserVoteResponse,
+    VotingPowerAtHeightResponse, VotingPowerAtRoundResponse,
+};
+pub use state::{
+    Constants, ConsumerChain, CounterpartyDeposit, CovenantParams, DeploymentMethod, DepositStatus,
+    Dex, ExecutionStatus, Expiration, FailedAllocationPolicy, ForfeitedDepositDestination,
+    FundedProposal, LiquidityPosition, LockEntry, LockPower, Proposal, ProposalCreationMode,
+    ProposalDeposit, ProposalEligibility, QuorumFailurePolicy, RegistryEntry, Round, RoundResult,
+    StandingProposal, StreamedWithdrawal, Tranche, Vote,
 };
-pub use state::Constants;
@@ -1,34 +1,510 @@
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::state::{
+    ConsumerChain, CovenantParams, Expiration, FailedAllocationPolicy, ForfeitedDepositDestination,
+    LockEntry, Proposal, ProposalCreationMode, ProposalDeposit, ProposalEligibility,
+    QuorumFailurePolicy, RegistryEntry, Round, RoundResult, StandingProposal, Tranche, Vote,
+};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub denom: String,
-    pub round_length: u64,
+    // Tranches are given ids in the order they are listed in
+    pub tranches: Vec<TrancheInfo>,
+    pub pool_denom: String,
+    pub top_n_props_count: u64,
+    pub covenant_code_id: u64,
+    pub covenant_instantiate_template: String,
+    pub covenant_withdraw_msg: String,
+    pub failed_allocation_policy: FailedAllocationPolicy,
+    pub consumer_chain_multiplier: Decimal,
+    pub min_vote_share: Decimal,
+    pub max_allocation_share: Decimal,
+    pub quorum: Decimal,
+    pub quorum_failure_policy: QuorumFailurePolicy,
+    pub proposal_deposit: Option<Coin>,
+    pub deposit_refund_vote_share: Decimal,
+    pub forfeited_deposit_destination: ForfeitedDepositDestination,
+    pub max_proposals_per_address: Option<u64>,
+    pub max_standing_rounds: u64,
+    // Defaults to the sender
+    pub admin: Option<String>,
+    // Defaults to the sender, which is the DAO when it instantiates this contract as its voting module
+    pub dao: Option<String>,
+    pub time_weighted_voting_power: bool,
+    pub governance: Option<String>,
+    pub proposal_creation_mode: ProposalCreationMode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TrancheInfo {
+    pub name: String,
     pub total_pool: Uint128,
+    pub round_length: u64,
+    // Defaults to the time of instantiation
+    pub genesis_time: Option<Timestamp>,
+    pub eligibility: ProposalEligibility,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    LockTokens { lock_duration: u64 },
+    LockTokens {
+        lock_duration: u64,
+    },
     UnlockTokens {},
-    CreateProposal { covenant_params: String },
-    Vote { proposal_id: u64 },
-    EndRound {},
-    // ExecuteProposal { proposal_id: u64 },
+    CreateProposal {
+        tranche_id: u64,
+        title: String,
+        description: String,
+        url: String,
+        covenant_params: Box<CovenantParams>,
+        // Number of rounds that the proposal stays available in, starting with the current one. Defaults to 1.
+        standing_rounds: Option<u64>,
+    },
+    // Only the creator can edit or withdraw a proposal, and only until it receives its first vote
+    EditProposal {
+        tranche_id: u64,
+        prop_id: u64,
+        title: String,
+        description: String,
+        url: String,
+    },
+    WithdrawProposal {
+        tranche_id: u64,
+        prop_id: u64,
+    },
+    // Stops carrying a standing proposal over into new rounds. It stays in the current round.
+    RetireProposal {
+        tranche_id: u64,
+        prop_id: u64,
+    },
+    Vote {
+        tranche_id: u64,
+        proposal_id: u64,
+    },
+    // Opts in (or out) of having the sender's vote carried over into the next rounds of the tranche
+    SetStickyVote {
+        tranche_id: u64,
+        sticky: bool,
+    },
+    // Sticky votes for the proposal go to the successor in the next round, unless the proposal is standing in it
+    DesignateSuccessor {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+        successor_prop_id: u64,
+    },
+    // Carries the sticky votes of the previous round over into the current one, for at most `limit` voters. Can be
+    // called by anyone, e.g. by a Neutron cron schedule, as many times as needed during the round.
+    ApplyStickyVotes {
+        tranche_id: u64,
+        limit: Option<u32>,
+    },
+    EndRound {
+        tranche_id: u64,
+    },
+    ExecuteProposal {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    ExpireProposal {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    DepositCounterparty {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    RefundCounterparty {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    WithdrawLiquidity {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    WithdrawTranche {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    // Refunds the proposal's deposit to its depositor, or forfeits it if the proposal did not get enough votes.
+    // Can be called by anyone once the proposal's round has ended.
+    ClaimDeposit {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    // Withdraws the next tranche of every streamed withdrawal that is due. Can be called by anyone, e.g. by a
+    // Neutron cron schedule.
+    ProcessWithdrawals {
+        limit: Option<u32>,
+    },
+    AddConsumerChain {
+        denom: String,
+        chain_id: String,
+    },
+    RemoveConsumerChain {
+        denom: String,
+    },
+    // Registry of approved pools, maintained by the admin and governance
+    ApprovePool {
+        entry: RegistryEntry,
+    },
+    RevokePool {
+        entry: RegistryEntry,
+    },
+    SetProposalCreationMode {
+        mode: ProposalCreationMode,
+    },
+    // CW721 interface for locks. The token_id of a lock is its lock_id.
+    TransferNft {
+        recipient: String,
+        token_id: String,
+    },
+    SendNft {
+        contract: String,
+        token_id: String,
+        msg: Binary,
+    },
+    Approve {
+        spender: String,
+        token_id: String,
+        expires: Option<Expiration>,
+    },
+    Revoke {
+        spender: String,
+        token_id: String,
+    },
+    ApproveAll {
+        operator: String,
+        expires: Option<Expiration>,
+    },
+    RevokeAll {
+        operator: String,
+    },
+}
+
+// Message sent to the receiving contract of SendNft, as defined by CW721
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiverExecuteMsg {
+    ReceiveNft(Cw721ReceiveMsg),
+}
+
+// Messages that only the chain can send, e.g. from a Neutron cron schedule
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    // Ends the current round of every tranche whose round is over, and executes at most `limit` of the
+    // proposals funded in the last round of each tranche
+    Tick { limit: Option<u32> },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetCount returns the current count as a json-encoded number
-    GetCount {},
+    Tranches {},
+    ConsumerChains {},
+    ApprovedPools {},
+    StandingProposals {
+        tranche_id: u64,
+    },
+    // Defaults to the current height
+    TotalLocked {
+        height: Option<u64>,
+    },
+    LockedTokens {
+        address: String,
+        height: Option<u64>,
+    },
+    // DAO DAO voting module interface. Voting power at a height is the tokens locked at that height, or if
+    // time_weighted_voting_power is set, the power that a vote cast at that height would have had in the then
+    // current round of the first tranche: the tokens in locks that end after the round does, scaled by the time
+    // left after its end. Defaults to the current height.
+    VotingPowerAtHeight {
+        address: String,
+        height: Option<u64>,
+    },
+    TotalPowerAtHeight {
+        height: Option<u64>,
+    },
+    // The power that a vote cast in a round of any tranche has: the tokens in locks that end after the round does,
+    // scaled by the time left after its end if time_weighted_voting_power is set. The power of a round that ended
+    // is the one it had at its end.
+    VotingPowerAtRound {
+        address: String,
+        tranche_id: u64,
+        round_id: u64,
+    },
+    TotalPowerAtRound {
+        tranche_id: u64,
+        round_id: u64,
+    },
+    Info {},
+    Dao {},
+    // CW721 queries for locks
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    NftInfo {
+        token_id: String,
+    },
+    AllNftInfo {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    Approval {
+        token_id: String,
+        spender: String,
+        include_expired: Option<bool>,
+    },
+    Approvals {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    Operator {
+        owner: String,
+        operator: String,
+        include_expired: Option<bool>,
+    },
+    AllOperators {
+        owner: String,
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    NumTokens {},
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    CurrentRound {
+        tranche_id: u64,
+    },
+    Round {
+        tranche_id: u64,
+        round_id: u64,
+    },
+    RoundResult {
+        tranche_id: u64,
+        round_id: u64,
+    },
+    Proposal {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    UserVote {
+        tranche_id: u64,
+        round_id: u64,
+        address: String,
+    },
+    ProposalPowerByTier {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    ProposalDeposit {
+        tranche_id: u64,
+        round_id: u64,
+        prop_id: u64,
+    },
+    TopNProposals {
+        tranche_id: u64,
+        round_id: u64,
+        number_of_proposals: usize,
+    },
 }
 
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct CountResponse {
-    pub count: i32,
+pub struct TranchesResponse {
+    pub tranches: Vec<Tranche>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConsumerChainsResponse {
+    pub consumer_chains: Vec<ConsumerChain>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ApprovedPoolsResponse {
+    pub mode: ProposalCreationMode,
+    pub entries: Vec<RegistryEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StandingProposalsResponse {
+    pub standing_proposals: Vec<StandingProposal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalLockedResponse {
+    pub total_locked: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockedTokensResponse {
+    pub locked: Uint128,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VotingPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
+}
+
+// Same shape as the cw2 contract version that DAO DAO modules report
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContractInfo {
+    pub contract: String,
+    pub version: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InfoResponse {
+    pub info: ContractInfo,
+}
+
+// Locks cannot be approved for transfer by other addresses, so approvals are always empty
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnerOfResponse {
+    pub owner: String,
+    pub approvals: Vec<Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Approval {
+    pub spender: String,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ApprovalResponse {
+    pub approval: Approval,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ApprovalsResponse {
+    pub approvals: Vec<Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OperatorResponse {
+    pub approval: Approval,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OperatorsResponse {
+    pub operators: Vec<Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NftInfoResponse {
+    pub token_uri: Option<String>,
+    pub extension: LockEntry,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllNftInfoResponse {
+    pub access: OwnerOfResponse,
+    pub info: NftInfoResponse,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NumTokensResponse {
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VotingPowerAtRoundResponse {
+    pub power: Uint128,
+    pub round_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalPowerAtRoundResponse {
+    pub power: Uint128,
+    pub round_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CurrentRoundResponse {
+    pub round: Round,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundResponse {
+    pub round: Round,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundResultResponse {
+    pub result: RoundResult,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProposalResponse {
+    pub proposal: Proposal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UserVoteResponse {
+    pub address: Addr,
+    pub vote: Vote,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TierPower {
+    pub lockup_tier: u64,
+    pub power: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProposalPowerByTierResponse {
+    pub tiers: Vec<TierPower>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProposalDepositResponse {
+    pub deposit: ProposalDeposit,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TopNProposalsResponse {
+    pub proposals: Vec<Proposal>,
 }
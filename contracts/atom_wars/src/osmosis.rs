@@ -0,0 +1,246 @@
+// Builds the messages that deploy a proposal's liquidity directly into Osmosis pools (and withdraw it again),
+// without going through a Timewave covenant, and reads the resulting positions back from the message responses.
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use cosmwasm_std::{
+    Binary, Coin, CosmosMsg, Decimal, Decimal256, Deps, StdError, StdResult, Uint128,
+};
+use osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
+    ConcentratedliquidityQuerier, MsgAddToPosition, MsgAddToPositionResponse, MsgCreatePosition,
+    MsgCreatePositionResponse, MsgWithdrawPosition, Pool as ConcentratedPool,
+};
+use osmosis_std::types::osmosis::gamm::v1beta1::{
+    GammQuerier, MsgExitPool, MsgJoinSwapExternAmountIn, MsgJoinSwapExternAmountInResponse,
+};
+use osmosis_std::types::osmosis::poolmanager::v1beta1::{
+    MsgSwapExactAmountIn, PoolmanagerQuerier, SwapAmountInRoute,
+};
+
+// Joins a GAMM pool with only `token_in`, accepting at most `slippage_tolerance` fewer shares than the pool
+// would give right now
+pub fn join_pool_msg(
+    deps: Deps,
+    sender: String,
+    pool_id: u64,
+    token_in: Coin,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    let expected_shares = GammQuerier::new(&deps.querier)
+        .calc_join_pool_shares(pool_id, vec![token_in.clone().into()])?
+        .share_out_amount;
+    let expected_shares: Uint128 = expected_shares.parse()?;
+    let share_out_min_amount = expected_shares * (Decimal::one() - slippage_tolerance);
+
+    Ok(MsgJoinSwapExternAmountIn {
+        sender,
+        pool_id,
+        token_in: Some(token_in.into()),
+        share_out_min_amount: share_out_min_amount.to_string(),
+    }
+    .into())
+}
+
+// Creates a concentrated liquidity position between `lower_tick` and `upper_tick` with only `token_in`,
+// accepting at most `slippage_tolerance` less of it being used than what was provided
+pub fn create_position_msg(
+    deps: Deps,
+    sender: String,
+    pool_id: u64,
+    lower_tick: i64,
+    upper_tick: i64,
+    token_in: Coin,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    let (token_min_amount0, token_min_amount1) = token_amounts_by_pool_order(
+        deps,
+        pool_id,
+        &token_in.denom,
+        token_in.amount * (Decimal::one() - slippage_tolerance),
+    )?;
+
+    Ok(MsgCreatePosition {
+        pool_id,
+        sender,
+        lower_tick,
+        upper_tick,
+        tokens_provided: vec![token_in.into()],
+        token_min_amount0,
+        token_min_amount1,
+    }
+    .into())
+}
+
+// Adds `token_in` to an existing concentrated liquidity position. Osmosis replaces the position with a new one.
+pub fn add_to_position_msg(
+    deps: Deps,
+    sender: String,
+    pool_id: u64,
+    position_id: u64,
+    token_in: Coin,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    let (amount0, amount1) =
+        token_amounts_by_pool_order(deps, pool_id, &token_in.denom, token_in.amount)?;
+    let (token_min_amount0, token_min_amount1) = token_amounts_by_pool_order(
+        deps,
+        pool_id,
+        &token_in.denom,
+        token_in.amount * (Decimal::one() - slippage_tolerance),
+    )?;
+
+    Ok(MsgAddToPosition {
+        position_id,
+        sender,
+        amount0,
+        amount1,
+        token_min_amount0,
+        token_min_amount1,
+    }
+    .into())
+}
+
+// Exits a GAMM pool with `shares`, accepting at most `slippage_tolerance` less of each token than the shares
+// are worth right now
+pub fn exit_pool_msg(
+    deps: Deps,
+    sender: String,
+    pool_id: u64,
+    shares: Uint128,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    let tokens_out = GammQuerier::new(&deps.querier)
+        .calc_exit_pool_coins_from_shares(pool_id, shares.to_string())?
+        .tokens_out;
+
+    let token_out_mins = tokens_out
+        .into_iter()
+        .map(|coin| {
+            let amount: Uint128 = coin.amount.parse()?;
+            Ok(Coin {
+                denom: coin.denom,
+                amount: amount * (Decimal::one() - slippage_tolerance),
+            }
+            .into())
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(MsgExitPool {
+        sender,
+        pool_id,
+        share_in_amount: shares.to_string(),
+        token_out_mins,
+    }
+    .into())
+}
+
+// Withdraws `fraction` of the liquidity in a concentrated liquidity position
+pub fn withdraw_position_msg(
+    deps: Deps,
+    sender: String,
+    position_id: u64,
+    fraction: Decimal,
+) -> StdResult<CosmosMsg> {
+    let liquidity = ConcentratedliquidityQuerier::new(&deps.querier)
+        .position_by_id(position_id)?
+        .position
+        .and_then(|position| position.position)
+        .ok_or_else(|| StdError::not_found("Osmosis position"))?
+        .liquidity;
+
+    // Withdraw the exact liquidity when withdrawing the whole position, so that no dust is left behind
+    let liquidity_amount = if fraction == Decimal::one() {
+        liquidity
+    } else {
+        (Decimal256::from_str(&liquidity)? * Decimal256::from(fraction)).to_string()
+    };
+
+    Ok(MsgWithdrawPosition {
+        position_id,
+        sender,
+        liquidity_amount,
+    }
+    .into())
+}
+
+// Swaps `token_in` for `token_out_denom` in the pool, accepting at most `slippage_tolerance` less than what it is
+// worth at the pool's spot price
+pub fn swap_msg(
+    deps: Deps,
+    sender: String,
+    pool_id: u64,
+    token_in: Coin,
+    token_out_denom: &str,
+    slippage_tolerance: Decimal,
+) -> StdResult<CosmosMsg> {
+    let price = spot_price(deps, pool_id, &token_in.denom, token_out_denom)?;
+    let token_out_min_amount = token_in.amount * price * (Decimal::one() - slippage_tolerance);
+
+    Ok(MsgSwapExactAmountIn {
+        sender,
+        routes: vec![SwapAmountInRoute {
+            pool_id,
+            token_out_denom: token_out_denom.to_string(),
+        }],
+        token_in: Some(token_in.into()),
+        token_out_min_amount: token_out_min_amount.to_string(),
+    }
+    .into())
+}
+
+// Returns the spot price of `base_denom` in units of `quote_denom`
+pub fn spot_price(
+    deps: Deps,
+    pool_id: u64,
+    base_denom: &str,
+    quote_denom: &str,
+) -> StdResult<Decimal> {
+    let spot_price = PoolmanagerQuerier::new(&deps.querier)
+        .spot_price(pool_id, base_denom.to_string(), quote_denom.to_string())?
+        .spot_price;
+
+    Decimal::from_str(&spot_price)
+}
+
+// Returns `amount` as the (amount0, amount1) pair expected by concentrated liquidity messages, depending on
+// whether `denom` is the pool's token0 or token1
+fn token_amounts_by_pool_order(
+    deps: Deps,
+    pool_id: u64,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<(String, String)> {
+    let pool = PoolmanagerQuerier::new(&deps.querier)
+        .pool(pool_id)?
+        .pool
+        .ok_or_else(|| StdError::not_found("Osmosis pool"))?;
+    let pool = ConcentratedPool::try_from(Binary::from(pool.value))?;
+
+    if pool.token0 == denom {
+        Ok((amount.to_string(), "0".to_string()))
+    } else {
+        Ok(("0".to_string(), amount.to_string()))
+    }
+}
+
+// Returns the number of pool shares received from a MsgJoinSwapExternAmountIn
+pub fn parse_join_pool_response(data: Option<Binary>) -> StdResult<Uint128> {
+    let data = data.ok_or_else(|| StdError::generic_err("Join pool response is empty"))?;
+    let response = MsgJoinSwapExternAmountInResponse::try_from(data)?;
+    response.share_out_amount.parse()
+}
+
+// Returns the id of the position created by a MsgCreatePosition
+pub fn parse_create_position_response(data: Option<Binary>) -> StdResult<u64> {
+    let data = data.ok_or_else(|| StdError::generic_err("Create position response is empty"))?;
+    let response = MsgCreatePositionResponse::try_from(data)?;
+    Ok(response.position_id)
+}
+
+// Returns the id of the position that replaced the old one after a MsgAddToPosition
+pub fn parse_add_to_position_response(data: Option<Binary>) -> StdResult<u64> {
+    let data = data.ok_or_else(|| StdError::generic_err("Add to position response is empty"))?;
+    let response = MsgAddToPositionResponse::try_from(data)?;
+    Ok(response.position_id)
+}
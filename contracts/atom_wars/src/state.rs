@@ -1,21 +1,196 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Coin, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, BlockInfo, Coin, Decimal, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map, SnapshotItem, SnapshotMap, Strategy};
 
 pub const CONSTANTS: Item<Constants> = Item::new("constants");
 
 #[cw_serde]
 pub struct Constants {
     pub denom: String,
-    pub round_length: u64,
+    // Address that maintains the consumer chain registry and the registry of approved pools
+    pub admin: Addr,
+    // Address that can also maintain the registry of approved pools, e.g. the chain's governance module
+    pub governance: Option<Addr>,
+    // Whether proposals can target any pool, or only the ones in the registry of approved pools
+    pub proposal_creation_mode: ProposalCreationMode,
+    // Denom of the liquidity that is deployed to funded proposals
+    pub pool_denom: String,
+    // Number of proposals that receive liquidity each round
+    pub top_n_props_count: u64,
+    // Code ID of the Timewave covenant contract instantiated for each funded proposal
+    pub covenant_code_id: u64,
+    // JSON object used as the base of the covenant's instantiate message. The fields that depend on the
+    // proposal (see covenant::ProposalFields) are merged into it, but cannot override any of its keys.
+    pub covenant_instantiate_template: String,
+    // JSON message executed on a proposal's covenant to unwind its position when the proposal's term ends
+    pub covenant_withdraw_msg: String,
+    // What happens to the allocation of a funded proposal that could not be executed before its deadline
+    pub failed_allocation_policy: FailedAllocationPolicy,
+    // Multiplier applied to the power of proposals for ICS consumer chains when allocating the pool
+    pub consumer_chain_multiplier: Decimal,
+    // Minimum share of the round's total voting power that a proposal needs to receive liquidity
+    pub min_vote_share: Decimal,
+    // Maximum share of the pool that a single proposal can receive. The excess goes to the other funded proposals.
+    pub max_allocation_share: Decimal,
+    // Minimum share of the locked tokens that has to vote in a round for its proposals to receive liquidity
+    pub quorum: Decimal,
+    // What happens to the positions deployed in earlier rounds when a round does not reach quorum
+    pub quorum_failure_policy: QuorumFailurePolicy,
+    // Deposit that has to be sent with each new proposal, if any
+    pub proposal_deposit: Option<Coin>,
+    // Minimum share of the round's total voting power that a proposal needs for its deposit to be refunded
+    pub deposit_refund_vote_share: Decimal,
+    // What happens to the deposits of proposals that did not get enough votes
+    pub forfeited_deposit_destination: ForfeitedDepositDestination,
+    // Maximum number of proposals that an address can create in each round of a tranche, if any
+    pub max_proposals_per_address: Option<u64>,
+    // Maximum number of rounds that a standing proposal can be carried over into, including its first round
+    pub max_standing_rounds: u64,
+    // DAO that uses this contract as its voting module, reported by the Dao query
+    pub dao: Addr,
+    // Whether the voting power reported to the DAO is scaled by lock duration, or is just the locked tokens
+    pub time_weighted_voting_power: bool,
+}
+
+#[cw_serde]
+pub enum FailedAllocationPolicy {
+    // The allocation is added to the pool of the current round, and so goes to the proposals funded in it
+    Redistribute,
+    // The allocation stays in the contract and is not deployed
+    ReturnToPool,
+}
+
+#[cw_serde]
+pub enum QuorumFailurePolicy {
+    // Positions stay deployed until the end of their term
+    KeepPositions,
+    // Positions can be withdrawn right away, without waiting for the end of their term
+    WithdrawPositions,
+}
+
+#[cw_serde]
+pub enum ForfeitedDepositDestination {
+    Burn,
+    CommunityPool,
+}
+
+#[cw_serde]
+pub enum ProposalCreationMode {
+    // Proposals can target any pool that pairs the pool denom with their counterparty denom
+    Open,
+    // Proposals can only target approved pools, or pools paired with an approved counterparty denom
+    Curated,
+}
+
+// TRANCHE_MAP: key(tranche_id) -> Tranche {
+//     tranche_id: u64,
+//     name: String,
+//     total_pool: Uint128,
+//     round_length: u64,
+//     genesis_time: Timestamp,
+//     eligibility: ProposalEligibility
+// }
+pub const TRANCHE_MAP: Map<u64, Tranche> = Map::new("tranche_map");
+#[cw_serde]
+pub struct Tranche {
+    pub tranche_id: u64,
+    pub name: String,
+    // Liquidity deployed to the proposals funded in each round of this tranche
     pub total_pool: Uint128,
+    // Length of this tranche's rounds, in nanos
+    pub round_length: u64,
+    // Start of the tranche's first round. Round n ends at genesis_time + (n + 1) * round_length, no matter when
+    // the rounds before it were ended.
+    pub genesis_time: Timestamp,
+    // Which proposals can be created in this tranche
+    pub eligibility: ProposalEligibility,
+}
+
+#[cw_serde]
+pub enum ProposalEligibility {
+    // Any proposal can be created in the tranche
+    Any,
+    // Only proposals pairing the pool denom with one of these denoms can be created in the tranche
+    CounterpartyDenoms { denoms: Vec<String> },
+    // Only proposals for registered ICS consumer chains can be created in the tranche
+    ConsumerChains,
 }
 
 pub const LOCK_ID: Item<u64> = Item::new("lock_id");
 
+// TOTAL_LOCKED: Amount of tokens held in LOCKS_MAP, including locks that have expired but were not unlocked yet,
+// checkpointed at every height it changes at
+pub const TOTAL_LOCKED: SnapshotItem<Uint128> = SnapshotItem::new(
+    "total_locked",
+    "total_locked__checkpoints",
+    "total_locked__changelog",
+    Strategy::EveryBlock,
+);
+
+// LOCK_EXPIRIES: key(lock_end nanos) -> Uint128
+// Amount of tokens held in LOCKS_MAP in locks that end at that time
+pub const LOCK_EXPIRIES: Map<u64, Uint128> = Map::new("lock_expiries");
+
+// EXPIRED_LOCKED: (time, amount)
+// Amount of tokens held in LOCKS_MAP in locks that ended at or before the time. The time is moved forward as rounds
+// end (see expire_locks()), so that each entry of LOCK_EXPIRIES is only summed up once.
+pub const EXPIRED_LOCKED: Item<(Timestamp, Uint128)> = Item::new("expired_locked");
+
+// LOCKED_TOKENS: key(address) -> Uint128
+// Amount of tokens held in LOCKS_MAP for each address, checkpointed at every height it changes at
+pub const LOCKED_TOKENS: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "locked_tokens",
+    "locked_tokens__checkpoints",
+    "locked_tokens__changelog",
+    Strategy::EveryBlock,
+);
+
+// LOCK_ENDS: key(address) -> Vec<(lock_end nanos, Uint128)>
+// End and amount of each lock held in LOCKS_MAP for each address, from which its voting power in any round can be
+// derived (see lock_ends_power()). Checkpointed at every height it changes at.
+pub const LOCK_ENDS: SnapshotMap<&Addr, Vec<(u64, Uint128)>> = SnapshotMap::new(
+    "lock_ends",
+    "lock_ends__checkpoints",
+    "lock_ends__changelog",
+    Strategy::EveryBlock,
+);
+
+// POWER_DROPS: key(time nanos) -> Uint128
+// Voting power that locks held in LOCKS_MAP lose at that time, as the time left until they end drops below each
+// lockup tier (see scale_lockup_power()). A lock of `amount` tokens has 4 * `amount` power when it has more than
+// 6 months left, and loses all of it by the time it ends.
+pub const POWER_DROPS: Map<u64, Uint128> = Map::new("power_drops");
+
+// DROPPED_POWER: (time, power)
+// Voting power that locks held in LOCKS_MAP lost at or before the time. Moved forward as rounds end, like
+// EXPIRED_LOCKED.
+pub const DROPPED_POWER: Item<(Timestamp, Uint128)> = Item::new("dropped_power");
+
+// ROUND_POWER: key(tranche_id, round_id) -> (Uint128, Uint128)
+// Tokens that can vote in the round, and their voting power, i.e. the sums over all locks of what cast_vote()
+// counts for them. Only changes while the round is the tranche's current one, and is checkpointed at every
+// height it changes at.
+pub const ROUND_POWER: SnapshotMap<(u64, u64), (Uint128, Uint128)> = SnapshotMap::new(
+    "round_power",
+    "round_power__checkpoints",
+    "round_power__changelog",
+    Strategy::EveryBlock,
+);
+
+// ROUND_HEIGHTS: key(tranche_id, round_id) -> height
+// Height at which the round became the tranche's current one
+pub const ROUND_HEIGHTS: Map<(u64, u64), u64> = Map::new("round_heights");
+
+// ROUNDS_BY_HEIGHT: key(tranche_id, height) -> round_id
+// Last round of the tranche that started at that height
+pub const ROUNDS_BY_HEIGHT: Map<(u64, u64), u64> = Map::new("rounds_by_height");
+
+// Proposal ids are unique across tranches and rounds
 pub const PROP_ID: Item<u64> = Item::new("prop_id");
 
-pub const ROUND_ID: Item<u64> = Item::new("round_id");
+// ROUND_ID: key(tranche_id) -> round_id
+// Each tranche has its own round schedule
+pub const ROUND_ID: Map<u64, u64> = Map::new("round_id");
 
 // LOCKS_MAP: key(sender_address, lock_id) -> LockEntry {
 //     funds: Coin,
@@ -23,6 +198,40 @@ pub const ROUND_ID: Item<u64> = Item::new("round_id");
 //     lock_end: Timestamp
 // }
 pub const LOCKS_MAP: Map<(Addr, u64), LockEntry> = Map::new("locks_map");
+
+// LOCK_OWNERS: key(lock_id) -> owner_address
+// Each lock is a CW721 token whose token_id is the lock_id, so that it can be transferred to another address
+pub const LOCK_OWNERS: Map<u64, Addr> = Map::new("lock_owners");
+
+// NUM_TOKENS: Number of entries in LOCK_OWNERS, i.e. of CW721 tokens
+pub const NUM_TOKENS: Item<u64> = Item::new("num_tokens");
+
+// TOKEN_APPROVALS: key(lock_id, spender) -> expiration
+// Addresses that the owner of a lock allowed to transfer it. Cleared when the lock changes hands.
+pub const TOKEN_APPROVALS: Map<(u64, &Addr), Expiration> = Map::new("token_approvals");
+
+// OPERATORS: key(owner, operator) -> expiration
+// Addresses that an owner allowed to transfer (and approve spenders of) all of their locks
+pub const OPERATORS: Map<(&Addr, &Addr), Expiration> = Map::new("operators");
+
+// When a CW721 approval ends, as defined by CW721
+#[cw_serde]
+#[derive(Copy)]
+pub enum Expiration {
+    AtHeight(u64),
+    AtTime(Timestamp),
+    Never {},
+}
+
+impl Expiration {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        match self {
+            Expiration::AtHeight(height) => block.height >= *height,
+            Expiration::AtTime(time) => block.time >= *time,
+            Expiration::Never {} => false,
+        }
+    }
+}
 #[cw_serde]
 pub struct LockEntry {
     pub funds: Coin,
@@ -30,48 +239,350 @@ pub struct LockEntry {
     pub lock_end: Timestamp,
 }
 
-// PROP_MAP: key(round_id, prop_id) -> Proposal {
+// PROP_MAP: key(tranche_id, round_id, prop_id) -> Proposal {
+//     tranche_id: u64,
 //     round_id: u64,
-//     covenant_params: String,
-//     executed: bool,
-//     power: Uint128
+//     prop_id: u64,
+//     creator: Addr,
+//     created_at: Timestamp,
+//     title: String,
+//     description: String,
+//     url: String,
+//     received_votes: bool,
+//     covenant_params: CovenantParams,
+//     status: ExecutionStatus,
+//     consumer_chain: bool,
+//     power: Uint128,
+//     effective_power: Uint128,
+//     percentage: Decimal,
+//     amount: Uint128,
+//     position: Option<LiquidityPosition>,
+//     deployed_amount: Uint128,
+//     counterparty: Option<CounterpartyDeposit>
 // }
-pub const PROPOSAL_MAP: Map<(u64, u64), Proposal> = Map::new("prop_map");
+pub const PROPOSAL_MAP: Map<(u64, u64, u64), Proposal> = Map::new("prop_map");
 #[cw_serde]
 pub struct Proposal {
+    pub tranche_id: u64,
     pub round_id: u64,
-    pub covenant_params: String,
-    pub executed: bool,
+    pub prop_id: u64,
+    pub creator: Addr,
+    pub created_at: Timestamp,
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    // Whether anyone ever voted for the proposal. Proposals can only be edited or withdrawn before that.
+    pub received_votes: bool,
+    pub covenant_params: CovenantParams,
+    pub status: ExecutionStatus,
+    // Whether the counterparty denom belonged to a registered ICS consumer chain when the proposal was created
+    pub consumer_chain: bool,
+    // Power of the votes for the proposal
     pub power: Uint128,
-    pub percentage: Uint128,
+    // Power used to allocate the pool, i.e. the power with the consumer chain multiplier applied
+    pub effective_power: Uint128,
+    // Share of the pool allocated to the proposal, after the max allocation cap
+    pub percentage: Decimal,
     pub amount: Uint128,
+    // Position that the liquidity was deployed into when the proposal was executed
+    pub position: Option<LiquidityPosition>,
+    // Amount of the pool denom currently deployed in the position
+    pub deployed_amount: Uint128,
+    // Counterparty funds escrowed for deployments that need both sides of the pool
+    pub counterparty: Option<CounterpartyDeposit>,
 }
 
-// VOTE_MAP: key(round_id, sender_addr) -> Vote {
+#[cw_serde]
+pub struct CounterpartyDeposit {
+    pub depositor: Addr,
+    pub amount: Uint128,
+    // How much of the deposit was provided to the pool with the last execution
+    pub provided: Uint128,
+}
+
+#[cw_serde]
+pub enum LiquidityPosition {
+    Covenant {
+        address: Addr,
+    },
+    OsmosisGamm {
+        pool_id: u64,
+        shares: Uint128,
+    },
+    OsmosisConcentrated {
+        pool_id: u64,
+        position_id: u64,
+    },
+    Astroport {
+        pair: Addr,
+        lp_token: String,
+        lp_amount: Uint128,
+    },
+}
+
+// Specification of the liquidity position that a proposal wants the pool to deploy
+#[cw_serde]
+pub struct CovenantParams {
+    pub dex: Dex,
+    // Astroport: address of the pair contract. Osmosis: numeric pool id.
+    pub pool_id: String,
+    // Denom that the pool's liquidity is paired with
+    pub counterparty_denom: String,
+    // Bounds on the price of the pool denom, in units of the counterparty denom, that the position may be
+    // deployed or withdrawn at
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+    // Maximum slippage allowed when providing or withdrawing liquidity
+    pub slippage_tolerance: Decimal,
+    // Number of rounds that the liquidity stays deployed for
+    pub duration: u64,
+    // Party that receives the position from the covenant
+    pub party_receiver_addr: String,
+    // How the liquidity is deployed when the proposal is executed
+    pub deployment: DeploymentMethod,
+    // Withdraw the position in several tranches at the end of its term, instead of all at once
+    pub streamed_withdrawal: Option<StreamedWithdrawal>,
+}
+
+// Spreads a withdrawal over `tranches` equal parts, at least `interval` blocks apart, so that someone who skews
+// the pool's price in a single block can only take advantage of one of them
+#[cw_serde]
+pub struct StreamedWithdrawal {
+    pub tranches: u64,
+    pub interval: u64,
+}
+
+#[cw_serde]
+pub enum DeploymentMethod {
+    // Instantiate a Timewave covenant with the proposal's params
+    Covenant,
+    // Join an Osmosis GAMM pool directly, providing only the pool denom
+    OsmosisGamm,
+    // Create an Osmosis concentrated liquidity position directly, providing only the pool denom
+    OsmosisConcentrated { lower_tick: i64, upper_tick: i64 },
+    // Provide liquidity to an Astroport pair directly, with the pool denom plus counterparty funds deposited
+    // on the proposal
+    Astroport,
+}
+
+#[cw_serde]
+pub enum ExecutionStatus {
+    // The proposal has not been executed (yet)
+    Pending,
+    // The covenant was instantiated successfully
+    Executed,
+    // The last execution attempt failed. It can be retried until the execution deadline.
+    Failed {
+        reason: String,
+    },
+    // The proposal was not executed before the deadline and its allocation was reclaimed
+    Expired,
+    // The liquidity is being withdrawn in tranches, the next of which can be withdrawn from `next_height` on
+    Withdrawing {
+        tranches_left: u64,
+        next_height: u64,
+    },
+    // The liquidity was withdrawn at the end of the proposal's term
+    Withdrawn,
+    // The position was taken over by a later proposal for the same position when the term ended
+    RolledOver {
+        round_id: u64,
+        prop_id: u64,
+    },
+}
+
+#[cw_serde]
+pub enum Dex {
+    Astroport,
+    Osmosis,
+}
+
+// VOTE_MAP: key(tranche_id, round_id, sender_addr) -> Vote {
 //     prop_id: u64,
 //     power: Uint128,
-//     tribute_claimed: bool
+//     locks: Vec<LockPower>
 // }
-pub const VOTE_MAP: Map<(u64, Addr), Vote> = Map::new("vote_map");
+pub const VOTE_MAP: Map<(u64, u64, Addr), Vote> = Map::new("vote_map");
 #[cw_serde]
 pub struct Vote {
     pub prop_id: u64,
     pub power: Uint128,
+    // Breakdown of the vote's power by the lock that backs it, so that tribute contracts
+    // can reward voters differently depending on how long they are locked for
+    pub locks: Vec<LockPower>,
 }
 
-// ROUND_MAP: key(round_id) -> Round {
+#[cw_serde]
+pub struct LockPower {
+    pub lock_id: u64,
+    // Remaining lockup time at the end of the round, in nanos
+    pub lockup_time: u64,
+    // Minimum number of remaining months that this lock exceeds (0, 1, 3 or 6), see lockup_tier()
+    pub lockup_tier: u64,
+    pub raw_power: Uint128,
+    pub scaled_power: Uint128,
+}
+
+// ROUND_MAP: key(tranche_id, round_id) -> Round {
 //     round_id: u64,
-//     round_end: Timestamp
+//     round_end: Timestamp,
+//     quorum_reached: Option<bool>
 // }
-pub const ROUND_MAP: Map<u64, Round> = Map::new("round_map");
+pub const ROUND_MAP: Map<(u64, u64), Round> = Map::new("round_map");
 #[cw_serde]
 pub struct Round {
     pub round_id: u64,
     pub round_end: Timestamp,
+    // Whether enough of the locked tokens voted in the round. Set when the round ends.
+    pub quorum_reached: Option<bool>,
 }
 
-// PROPS_BY_SCORE: key(round_id, score, prop_id) -> prop_id
-pub const PROPS_BY_SCORE: Map<(u64, u128, u64), u64> = Map::new("props_by_score");
+// ROUND_RESULTS: key(tranche_id, round_id) -> RoundResult {
+//     round_id: u64,
+//     quorum_reached: bool,
+//     total_power_voting: Uint128,
+//     total_pool: Uint128,
+//     funded: Vec<FundedProposal>
+// }
+// Outcome of a round, recorded when it ends and never changed afterwards
+pub const ROUND_RESULTS: Map<(u64, u64), RoundResult> = Map::new("round_results");
+#[cw_serde]
+pub struct RoundResult {
+    pub round_id: u64,
+    pub quorum_reached: bool,
+    pub total_power_voting: Uint128,
+    // Liquidity allocated to the round's proposals, including allocations redistributed from expired proposals
+    pub total_pool: Uint128,
+    // Proposals that receive liquidity, from the most voted to the least
+    pub funded: Vec<FundedProposal>,
+}
+
+#[cw_serde]
+pub struct FundedProposal {
+    pub prop_id: u64,
+    pub power: Uint128,
+    pub effective_power: Uint128,
+    pub percentage: Decimal,
+    pub amount: Uint128,
+}
+
+// CONSUMER_CHAINS: key(denom) -> ConsumerChain {
+//     denom: String,
+//     chain_id: String
+// }
+// Registry of ICS consumer chains, by the denom of their token on this chain. Maintained by the admin.
+pub const CONSUMER_CHAINS: Map<String, ConsumerChain> = Map::new("consumer_chains");
+#[cw_serde]
+pub struct ConsumerChain {
+    pub denom: String,
+    pub chain_id: String,
+}
+
+// STANDING_PROPOSALS: key(tranche_id, prop_id) -> StandingProposal {
+//     prop_id: u64,
+//     first_round_id: u64,
+//     last_round_id: u64
+// }
+// Proposals that stay available, with the same prop_id, in each round of the tranche from first_round_id to
+// last_round_id. They are only copied into PROP_MAP for a later round when they are first needed in it (see
+// load_proposal()), so that end_round doesn't have to carry them over.
+pub const STANDING_PROPOSALS: Map<(u64, u64), StandingProposal> = Map::new("standing_proposals");
+#[cw_serde]
+pub struct StandingProposal {
+    pub prop_id: u64,
+    pub first_round_id: u64,
+    pub last_round_id: u64,
+}
+
+// STICKY_VOTERS: key(tranche_id, voter_addr) -> ()
+// Voters whose vote is carried over into the next round of the tranche, see ApplyStickyVotes
+pub const STICKY_VOTERS: Map<(u64, Addr), ()> = Map::new("sticky_voters");
+
+// STICKY_VOTES_CURSOR: key(tranche_id) -> (round_id, last_voter_addr)
+// Last sticky voter whose vote was carried over into the round, so that ApplyStickyVotes can go through them in batches
+pub const STICKY_VOTES_CURSOR: Map<u64, (u64, Addr)> = Map::new("sticky_votes_cursor");
+
+// TICK_CURSOR: key(tranche_id) -> (round_id, prop_id)
+// Last proposal that Tick tried to execute, so that it goes through the proposals to execute in turn
+pub const TICK_CURSOR: Map<u64, (u64, u64)> = Map::new("tick_cursor");
+
+// SUCCESSORS: key(tranche_id, prop_id) -> successor_prop_id
+// Proposal of a later round that the creator of a proposal designated to receive its sticky votes
+pub const SUCCESSORS: Map<(u64, u64), u64> = Map::new("successors");
+
+// PROPOSAL_DEPOSITS: key(tranche_id, round_id, prop_id) -> ProposalDeposit {
+//     depositor: Addr,
+//     amount: Coin,
+//     status: DepositStatus
+// }
+pub const PROPOSAL_DEPOSITS: Map<(u64, u64, u64), ProposalDeposit> = Map::new("proposal_deposits");
+#[cw_serde]
+pub struct ProposalDeposit {
+    pub depositor: Addr,
+    pub amount: Coin,
+    pub status: DepositStatus,
+}
+
+#[cw_serde]
+pub enum DepositStatus {
+    // The deposit is held by the contract until the proposal's round ends and it is claimed
+    Held,
+    Refunded,
+    // The proposal did not get enough votes, and the deposit was burned or sent to the community pool
+    Forfeited,
+}
+
+// PROPOSAL_COUNT: key(tranche_id, round_id, creator_addr) -> u64
+// Number of proposals created by an address in a round, to enforce max_proposals_per_address
+pub const PROPOSAL_COUNT: Map<(u64, u64, Addr), u64> = Map::new("proposal_count");
+
+// APPROVED_POOLS: key(registry_key) -> RegistryEntry
+// Pools and counterparty denoms that proposals can target when proposal creation is curated. Maintained by the admin
+// and governance.
+pub const APPROVED_POOLS: Map<String, RegistryEntry> = Map::new("approved_pools");
+#[cw_serde]
+pub enum RegistryEntry {
+    Pool { dex: Dex, pool_id: String },
+    CounterpartyDenom { denom: String },
+}
+
+// PENDING_EXECUTION: (tranche_id, round_id, prop_id) of the proposal whose liquidity is being deployed,
+// read back in the reply once the resulting position is known
+pub const PENDING_EXECUTION: Item<(u64, u64, u64)> = Item::new("pending_execution");
+
+// PENDING_WITHDRAWAL: (tranche_id, round_id, prop_id, counterparty_balance) of the proposal whose liquidity is
+// being withdrawn, with the contract's counterparty balance before the withdrawal. The difference is what the
+// withdrawal returned of the counterparty (see liquidity_withdrawn()).
+pub const PENDING_WITHDRAWAL: Item<(u64, u64, u64, Uint128)> = Item::new("pending_withdrawal");
+
+// STREAMED_WITHDRAWALS: key(tranche_id, round_id, prop_id) -> height
+// Proposals whose liquidity is being withdrawn in tranches, with the height from which ProcessWithdrawals tries to
+// withdraw the next tranche. That is the height the tranche is due at, or the block after a failed attempt.
+pub const STREAMED_WITHDRAWALS: Map<(u64, u64, u64), u64> = Map::new("streamed_withdrawals");
+
+// DUE_WITHDRAWALS: key(height, (tranche_id, round_id, prop_id)) -> ()
+// STREAMED_WITHDRAWALS indexed by height, so that ProcessWithdrawals only reads the withdrawals that are due
+pub const DUE_WITHDRAWALS: Map<(u64, (u64, u64, u64)), ()> = Map::new("due_withdrawals");
+
+// ACTIVE_POSITIONS: key(rollover_key) -> (tranche_id, round_id, prop_id)
+// The latest proposal holding a position that can be rolled over, by the position it targets (see rollover_key())
+pub const ACTIVE_POSITIONS: Map<String, (u64, u64, u64)> = Map::new("active_positions");
+
+// EXTRA_POOL: key(tranche_id, round_id) -> Uint128
+// Allocations of expired proposals that are redistributed to the proposals funded in this round, on top of total_pool
+pub const EXTRA_POOL: Map<(u64, u64), Uint128> = Map::new("extra_pool");
+
+// PROPS_BY_SCORE: key((tranche_id, round_id), score, prop_id) -> prop_id
+pub const PROPS_BY_SCORE: Map<((u64, u64), u128, u64), u64> = Map::new("props_by_score");
+
+// PROP_POWER_BY_TIER: key((tranche_id, round_id), prop_id, lockup_tier) -> Uint128
+// Power voting for a proposal, split by the lockup tier of the locks backing the votes
+pub const PROP_POWER_BY_TIER: Map<((u64, u64), u64, u64), Uint128> = Map::new("prop_power_by_tier");
+
+// TOTAL_POWER_VOTING: key(tranche_id, round_id) -> Uint128
+pub const TOTAL_POWER_VOTING: Map<(u64, u64), Uint128> = Map::new("total_power_voting");
 
-// TOTAL_POWER_VOTING: key(round_id) -> Uint128
-pub const TOTAL_POWER_VOTING: Map<u64, Uint128> = Map::new("total_power_voting");
+// TOTAL_TOKENS_VOTING: key(tranche_id, round_id) -> Uint128
+// Locked tokens backing the votes of the round, before lockup scaling. Compared to the tokens in locks that end
+// after the round for the quorum (see active_locked()).
+pub const TOTAL_TOKENS_VOTING: Map<(u64, u64), Uint128> = Map::new("total_tokens_voting");
@@ -0,0 +1,3176 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, BankMsg, Binary, Coin, ContractResult, CosmosMsg, Decimal,
+    Empty, Env, Event, Order, OwnedDeps, Querier, QuerierResult, QueryRequest, Reply, Response,
+    StdResult, SubMsg, SubMsgResponse, SubMsgResult, SystemError, SystemResult, Uint128, Uint64,
+    WasmMsg, WasmQuery,
+};
+use osmosis_std::shim::Any;
+use osmosis_std::types::cosmos::distribution::v1beta1::MsgFundCommunityPool;
+use osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
+    FullPositionBreakdown, MsgAddToPosition, MsgAddToPositionResponse, MsgCreatePosition,
+    MsgCreatePositionResponse, Pool as ConcentratedPool, Position, PositionByIdRequest,
+    PositionByIdResponse,
+};
+use osmosis_std::types::osmosis::gamm::v1beta1::{
+    MsgExitPool, MsgJoinSwapExternAmountIn, MsgJoinSwapExternAmountInResponse,
+    QueryCalcExitPoolCoinsFromSharesRequest, QueryCalcExitPoolCoinsFromSharesResponse,
+    QueryCalcJoinPoolSharesRequest, QueryCalcJoinPoolSharesResponse,
+};
+use osmosis_std::types::osmosis::poolmanager::v1beta1::{
+    PoolRequest, PoolResponse as OsmosisPoolResponse, SpotPriceRequest, SpotPriceResponse,
+    TotalPoolLiquidityRequest, TotalPoolLiquidityResponse,
+};
+
+use crate::astroport::{Asset, AssetInfo, PairExecuteMsg, PairInfo, PairQueryMsg, PoolResponse};
+use crate::contract::{
+    capped_shares, execute, instantiate, query, reply, sudo, ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID,
+    ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID, DEFAULT_APPLY_STICKY_VOTES_LIMIT,
+    INSTANTIATE_COVENANT_REPLY_ID, MAX_ROUNDS_ENDED_PER_CALL, OSMOSIS_ADD_TO_POSITION_REPLY_ID,
+    OSMOSIS_CREATE_POSITION_REPLY_ID, OSMOSIS_JOIN_POOL_REPLY_ID,
+    OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID,
+};
+use crate::error::ContractError;
+use crate::msg::{
+    ContractInfo, ExecuteMsg, InfoResponse, InstantiateMsg, NumTokensResponse, OwnerOfResponse,
+    ProposalResponse, QueryMsg, RoundResultResponse, SudoMsg, TotalPowerAtHeightResponse,
+    TotalPowerAtRoundResponse, TrancheInfo, UserVoteResponse, VotingPowerAtHeightResponse,
+    VotingPowerAtRoundResponse,
+};
+use crate::state::{
+    CovenantParams, DeploymentMethod, Dex, ExecutionStatus, Expiration, FailedAllocationPolicy,
+    ForfeitedDepositDestination, LiquidityPosition, Proposal, ProposalCreationMode,
+    ProposalEligibility, QuorumFailurePolicy, RegistryEntry, RoundResult, StreamedWithdrawal,
+    ACTIVE_POSITIONS, PROPOSAL_MAP, ROUND_MAP, ROUND_RESULTS, VOTE_MAP,
+};
+
+pub const ADMIN: &str = "admin";
+pub const VOTER: &str = "voter";
+pub const CREATOR: &str = "creator";
+pub const RECEIVER: &str = "receiver";
+pub const DEPOSITOR: &str = "depositor";
+pub const DENOM: &str = "uatom";
+pub const COUNTERPARTY_DENOM: &str = "untrn";
+pub const PAIR: &str = "astroport_pair";
+pub const LP_TOKEN: &str = "factory/astroport_pair/lp";
+pub const COVENANT_CODE_ID: u64 = 7;
+pub const TOTAL_POOL: u128 = 1_000;
+pub const ROUND_LENGTH: u64 = 604_800_000_000_000;
+pub const ONE_MONTH: u64 = 2_629_746_000_000_000;
+
+pub const OSMOSIS_POOL_ID: u64 = 1;
+
+pub type MockDeps = OwnedDeps<MockStorage, MockApi, OsmosisMockQuerier>;
+
+// Fields of the covenant's instantiate message that the template provides
+pub const COVENANT_TEMPLATE: &str = r#"{"label":"atom_wars_pol","clock_tick_max_gas":"500000","covenant_party_config":{"native_denom":"uatom"}}"#;
+
+// Stand-in for the Timewave covenant's instantiate message. Like the covenant's own messages, it denies
+// unknown fields.
+#[cw_serde]
+pub struct MockCovenantInstantiateMsg {
+    pub label: String,
+    pub clock_tick_max_gas: Option<Uint64>,
+    pub pool_address: String,
+    pub pool_price_config: MockPoolPriceConfig,
+    pub covenant_party_config: MockCovenantPartyConfig,
+}
+
+#[cw_serde]
+pub struct MockPoolPriceConfig {
+    pub expected_spot_price: Decimal,
+    pub acceptable_price_spread: Decimal,
+}
+
+#[cw_serde]
+pub struct MockCovenantPartyConfig {
+    pub addr: String,
+    pub native_denom: String,
+}
+
+// Reserves of the mock Astroport pair
+#[derive(Clone)]
+pub struct MockPair {
+    pub reserve: Uint128,
+    pub counterparty_reserve: Uint128,
+    pub total_share: Uint128,
+}
+
+impl Default for MockPair {
+    fn default() -> Self {
+        MockPair {
+            reserve: Uint128::new(10_000),
+            counterparty_reserve: Uint128::new(20_000),
+            total_share: Uint128::new(10_000),
+        }
+    }
+}
+
+impl MockPair {
+    fn query(&self, msg: PairQueryMsg) -> Binary {
+        let assets = |reserve: Uint128, counterparty_reserve: Uint128| {
+            vec![
+                Asset::native(DENOM, reserve),
+                Asset::native(COUNTERPARTY_DENOM, counterparty_reserve),
+            ]
+        };
+
+        match msg {
+            PairQueryMsg::Pair {} => to_json_binary(&PairInfo {
+                asset_infos: vec![
+                    AssetInfo::NativeToken {
+                        denom: DENOM.to_string(),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: COUNTERPARTY_DENOM.to_string(),
+                    },
+                ],
+                contract_addr: PAIR.to_string(),
+                liquidity_token: LP_TOKEN.to_string(),
+            }),
+            PairQueryMsg::Pool {} => to_json_binary(&PoolResponse {
+                assets: assets(self.reserve, self.counterparty_reserve),
+                total_share: self.total_share,
+            }),
+            PairQueryMsg::Share { amount } => to_json_binary(&assets(
+                self.reserve.multiply_ratio(amount, self.total_share),
+                self.counterparty_reserve
+                    .multiply_ratio(amount, self.total_share),
+            )),
+        }
+        .unwrap()
+    }
+}
+
+pub fn set_mock_pair(deps: &mut MockDeps, pair: MockPair) {
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == PAIR => {
+            SystemResult::Ok(ContractResult::Ok(pair.query(from_json(msg).unwrap())))
+        }
+        _ => SystemResult::Err(SystemError::UnsupportedRequest {
+            kind: "unexpected query".to_string(),
+        }),
+    });
+}
+
+// Osmosis pool that pairs the pool denom with the counterparty denom, with the pool denom as token0
+#[derive(Clone)]
+pub struct MockOsmosisPool {
+    // GAMM shares minted for each token joined, and burned for each pool denom exited
+    pub shares_per_token: u128,
+    // Liquidity of the concentrated liquidity positions, by position id
+    pub positions: HashMap<u64, String>,
+}
+
+impl Default for MockOsmosisPool {
+    fn default() -> Self {
+        MockOsmosisPool {
+            shares_per_token: 100,
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl MockOsmosisPool {
+    fn query(&self, path: &str, data: Binary) -> StdResult<Binary> {
+        let pool_denom = |amount: u128| Coin::new(amount, DENOM).into();
+        let counterparty = |amount: u128| Coin::new(amount, COUNTERPARTY_DENOM).into();
+
+        match path {
+            "/osmosis.poolmanager.v1beta1.Query/TotalPoolLiquidity" => {
+                TotalPoolLiquidityRequest::try_from(data)?;
+                to_json_binary(&TotalPoolLiquidityResponse {
+                    liquidity: vec![pool_denom(10_000), counterparty(20_000)],
+                })
+            }
+            "/osmosis.poolmanager.v1beta1.Query/SpotPrice" => {
+                SpotPriceRequest::try_from(data)?;
+                to_json_binary(&SpotPriceResponse {
+                    spot_price: "2".to_string(),
+                })
+            }
+            "/osmosis.poolmanager.v1beta1.Query/Pool" => {
+                let request = PoolRequest::try_from(data)?;
+                let pool = ConcentratedPool {
+                    id: request.pool_id,
+                    token0: DENOM.to_string(),
+                    token1: COUNTERPARTY_DENOM.to_string(),
+                    ..ConcentratedPool::default()
+                };
+                to_json_binary(&OsmosisPoolResponse {
+                    pool: Some(Any {
+                        type_url: ConcentratedPool::TYPE_URL.to_string(),
+                        value: Binary::from(pool).to_vec(),
+                    }),
+                })
+            }
+            "/osmosis.gamm.v1beta1.Query/CalcJoinPoolShares" => {
+                let request = QueryCalcJoinPoolSharesRequest::try_from(data)?;
+                let amount: u128 = request.tokens_in[0].amount.parse().unwrap();
+                to_json_binary(&QueryCalcJoinPoolSharesResponse {
+                    share_out_amount: (amount * self.shares_per_token).to_string(),
+                    tokens_out: request.tokens_in,
+                })
+            }
+            "/osmosis.gamm.v1beta1.Query/CalcExitPoolCoinsFromShares" => {
+                let request = QueryCalcExitPoolCoinsFromSharesRequest::try_from(data)?;
+                let shares: u128 = request.share_in_amount.parse().unwrap();
+                let amount = shares / self.shares_per_token;
+                to_json_binary(&QueryCalcExitPoolCoinsFromSharesResponse {
+                    tokens_out: vec![pool_denom(amount), counterparty(2 * amount)],
+                })
+            }
+            "/osmosis.concentratedliquidity.v1beta1.Query/PositionById" => {
+                let request = PositionByIdRequest::try_from(data)?;
+                to_json_binary(&PositionByIdResponse {
+                    position: self.positions.get(&request.position_id).map(|liquidity| {
+                        FullPositionBreakdown {
+                            position: Some(Position {
+                                position_id: request.position_id,
+                                pool_id: OSMOSIS_POOL_ID,
+                                liquidity: liquidity.clone(),
+                                ..Position::default()
+                            }),
+                            ..FullPositionBreakdown::default()
+                        }
+                    }),
+                })
+            }
+            path => panic!("unexpected Osmosis query {}", path),
+        }
+    }
+}
+
+// MockQuerier that also answers the Stargate queries made to Osmosis, about the mock Osmosis pool if one is set
+#[derive(Default)]
+pub struct OsmosisMockQuerier {
+    base: MockQuerier,
+    pub osmosis_pool: Option<MockOsmosisPool>,
+}
+
+impl Deref for OsmosisMockQuerier {
+    type Target = MockQuerier;
+
+    fn deref(&self) -> &MockQuerier {
+        &self.base
+    }
+}
+
+impl DerefMut for OsmosisMockQuerier {
+    fn deref_mut(&mut self) -> &mut MockQuerier {
+        &mut self.base
+    }
+}
+
+impl Querier for OsmosisMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match from_json::<QueryRequest<Empty>>(bin_request) {
+            Ok(QueryRequest::Stargate { path, data }) => match &self.osmosis_pool {
+                Some(pool) => SystemResult::Ok(pool.query(&path, data).into()),
+                None => SystemResult::Err(SystemError::UnsupportedRequest { kind: path }),
+            },
+            _ => self.base.raw_query(bin_request),
+        }
+    }
+}
+
+pub fn mock_dependencies() -> MockDeps {
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: OsmosisMockQuerier::default(),
+        custom_query_type: PhantomData,
+    }
+}
+
+pub fn instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        denom: DENOM.to_string(),
+        tranches: vec![TrancheInfo {
+            name: "tranche".to_string(),
+            total_pool: Uint128::new(TOTAL_POOL),
+            round_length: ROUND_LENGTH,
+            genesis_time: None,
+            eligibility: ProposalEligibility::Any,
+        }],
+        pool_denom: DENOM.to_string(),
+        top_n_props_count: 2,
+        covenant_code_id: COVENANT_CODE_ID,
+        covenant_instantiate_template: COVENANT_TEMPLATE.to_string(),
+        covenant_withdraw_msg: r#"{"claim":{}}"#.to_string(),
+        failed_allocation_policy: FailedAllocationPolicy::ReturnToPool,
+        consumer_chain_multiplier: Decimal::one(),
+        min_vote_share: Decimal::zero(),
+        max_allocation_share: Decimal::one(),
+        quorum: Decimal::zero(),
+        quorum_failure_policy: QuorumFailurePolicy::KeepPositions,
+        proposal_deposit: None,
+        deposit_refund_vote_share: Decimal::zero(),
+        forfeited_deposit_destination: ForfeitedDepositDestination::Burn,
+        max_proposals_per_address: None,
+        max_standing_rounds: 1,
+        admin: None,
+        dao: None,
+        time_weighted_voting_power: false,
+        governance: None,
+        proposal_creation_mode: ProposalCreationMode::Open,
+    }
+}
+
+pub fn covenant_params(deployment: DeploymentMethod) -> CovenantParams {
+    CovenantParams {
+        dex: Dex::Astroport,
+        pool_id: PAIR.to_string(),
+        counterparty_denom: COUNTERPARTY_DENOM.to_string(),
+        min_price: Some(Decimal::percent(50)),
+        max_price: Some(Decimal::percent(150)),
+        slippage_tolerance: Decimal::percent(1),
+        duration: 1,
+        party_receiver_addr: RECEIVER.to_string(),
+        deployment,
+        streamed_withdrawal: None,
+    }
+}
+
+// Instantiates the contract with a single tranche, at the mock block time, next to the mock Astroport pair
+pub fn setup() -> MockDeps {
+    let mut deps = mock_dependencies();
+    set_mock_pair(&mut deps, MockPair::default());
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(ADMIN, &[]),
+        instantiate_msg(),
+    )
+    .unwrap();
+    deps
+}
+
+pub fn lock_tokens(deps: &mut MockDeps, env: &Env, sender: &str, amount: u128, lock_duration: u64) {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(sender, &[Coin::new(amount, DENOM)]),
+        ExecuteMsg::LockTokens { lock_duration },
+    )
+    .unwrap();
+}
+
+pub fn create_proposal_msg(
+    covenant_params: CovenantParams,
+    standing_rounds: Option<u64>,
+) -> ExecuteMsg {
+    ExecuteMsg::CreateProposal {
+        tranche_id: 0,
+        title: "proposal".to_string(),
+        description: String::new(),
+        url: String::new(),
+        covenant_params: Box::new(covenant_params),
+        standing_rounds,
+    }
+}
+
+pub fn try_create_proposal(
+    deps: &mut MockDeps,
+    env: &Env,
+    covenant_params: CovenantParams,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        create_proposal_msg(covenant_params, None),
+    )
+}
+
+// Returns the id of the proposal created in a response
+pub fn created_prop_id(response: &Response) -> u64 {
+    response
+        .attributes
+        .iter()
+        .find(|attr| attr.key == "prop_id")
+        .map(|attr| attr.value.parse().unwrap())
+        .unwrap()
+}
+
+// Creates a proposal in the current round of tranche 0 and returns its id
+pub fn create_proposal(deps: &mut MockDeps, env: &Env, covenant_params: CovenantParams) -> u64 {
+    let response = try_create_proposal(deps, env, covenant_params).unwrap();
+    created_prop_id(&response)
+}
+
+pub fn vote(deps: &mut MockDeps, env: &Env, sender: &str, proposal_id: u64) {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(sender, &[]),
+        ExecuteMsg::Vote {
+            tranche_id: 0,
+            proposal_id,
+        },
+    )
+    .unwrap();
+}
+
+// Returns the env `rounds` rounds after the mock block time
+pub fn env_after_rounds(rounds: u64) -> Env {
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_nanos(ROUND_LENGTH * rounds);
+    env.block.height += rounds * 100_000;
+    env
+}
+
+// Creates a proposal in round 0 that receives all the votes, and returns the env of round 1, in which it
+// can be executed
+pub fn funded_proposal(deps: &mut MockDeps, covenant_params: CovenantParams) -> (Env, u64) {
+    let env = mock_env();
+    lock_tokens(deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    let prop_id = create_proposal(deps, &env, covenant_params);
+    vote(deps, &env, VOTER, prop_id);
+    (env_after_rounds(1), prop_id)
+}
+
+pub fn execute_proposal(
+    deps: &mut MockDeps,
+    env: &Env,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteProposal {
+            tranche_id: 0,
+            round_id,
+            prop_id,
+        },
+    )
+}
+
+pub fn deposit_counterparty(
+    deps: &mut MockDeps,
+    env: &Env,
+    round_id: u64,
+    prop_id: u64,
+    amount: u128,
+) {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(DEPOSITOR, &[Coin::new(amount, COUNTERPARTY_DENOM)]),
+        ExecuteMsg::DepositCounterparty {
+            tranche_id: 0,
+            round_id,
+            prop_id,
+        },
+    )
+    .unwrap();
+}
+
+pub fn withdraw_liquidity(
+    deps: &mut MockDeps,
+    env: &Env,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::WithdrawLiquidity {
+            tranche_id: 0,
+            round_id,
+            prop_id,
+        },
+    )
+}
+
+// Sets the contract's balance of the counterparty denom
+pub fn set_counterparty_balance(deps: &mut MockDeps, env: &Env, amount: u128) {
+    deps.querier.update_balance(
+        env.contract.address.clone(),
+        vec![Coin::new(amount, COUNTERPARTY_DENOM)],
+    );
+}
+
+pub fn load_proposal(deps: &MockDeps, round_id: u64, prop_id: u64) -> Proposal {
+    PROPOSAL_MAP
+        .load(&deps.storage, (0, round_id, prop_id))
+        .unwrap()
+}
+
+// Replies to a submessage with the given events
+pub fn reply_ok(deps: &mut MockDeps, env: &Env, id: u64, events: Vec<Event>) -> Response {
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse { events, data: None }),
+        },
+    )
+    .unwrap()
+}
+
+// Replies to a submessage with the given message response data
+pub fn reply_data(
+    deps: &mut MockDeps,
+    env: &Env,
+    id: u64,
+    data: Option<Binary>,
+) -> Result<Response, ContractError> {
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data,
+            }),
+        },
+    )
+}
+
+pub fn reply_err(deps: &mut MockDeps, env: &Env, id: u64, reason: &str) -> Response {
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        Reply {
+            id,
+            result: SubMsgResult::Err(reason.to_string()),
+        },
+    )
+    .unwrap()
+}
+
+// Instantiates the contract with the given message, next to the mock Astroport pair
+pub fn setup_with(msg: InstantiateMsg) -> MockDeps {
+    let mut deps = mock_dependencies();
+    set_mock_pair(&mut deps, MockPair::default());
+    instantiate(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), msg).unwrap();
+    deps
+}
+
+// Instantiates the contract with the given quorum
+pub fn setup_with_quorum(quorum: Decimal) -> MockDeps {
+    setup_with(InstantiateMsg {
+        quorum,
+        ..instantiate_msg()
+    })
+}
+
+pub fn end_round(deps: &mut MockDeps, env: &Env) {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::EndRound { tranche_id: 0 },
+    )
+    .unwrap();
+}
+
+pub fn round_quorum_reached(deps: &MockDeps, round_id: u64) -> Option<bool> {
+    ROUND_MAP
+        .load(&deps.storage, (0, round_id))
+        .unwrap()
+        .quorum_reached
+}
+
+// Returns the wasm message of a submessage
+pub fn wasm_msg(sub_msg: &SubMsg) -> &WasmMsg {
+    match &sub_msg.msg {
+        CosmosMsg::Wasm(msg) => msg,
+        msg => panic!("expected a wasm message, got {:?}", msg),
+    }
+}
+
+#[test]
+fn covenant_instantiate_msg_only_has_fields_the_covenant_accepts() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+
+    let response = execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    let (code_id, msg, funds) = match wasm_msg(&response.messages[0]) {
+        WasmMsg::Instantiate {
+            code_id,
+            msg,
+            funds,
+            ..
+        } => (*code_id, msg, funds),
+        msg => panic!("expected a covenant instantiation, got {:?}", msg),
+    };
+
+    assert_eq!(code_id, COVENANT_CODE_ID);
+    assert_eq!(funds, &vec![Coin::new(TOTAL_POOL, DENOM)]);
+    assert_eq!(
+        from_json::<MockCovenantInstantiateMsg>(msg).unwrap(),
+        MockCovenantInstantiateMsg {
+            label: "atom_wars_pol".to_string(),
+            clock_tick_max_gas: Some(Uint64::new(500_000)),
+            pool_address: PAIR.to_string(),
+            pool_price_config: MockPoolPriceConfig {
+                expected_spot_price: Decimal::one(),
+                acceptable_price_spread: Decimal::percent(50),
+            },
+            covenant_party_config: MockCovenantPartyConfig {
+                addr: RECEIVER.to_string(),
+                native_denom: DENOM.to_string(),
+            },
+        }
+    );
+}
+
+#[test]
+fn covenant_template_takes_precedence_over_proposal() {
+    let mut deps = mock_dependencies();
+    set_mock_pair(&mut deps, MockPair::default());
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(ADMIN, &[]),
+        InstantiateMsg {
+            covenant_instantiate_template: format!(
+                r#"{{"label":"atom_wars_pol","pool_address":"fixed_pair","covenant_party_config":{{"addr":"fixed_receiver","native_denom":"{}"}}}}"#,
+                DENOM
+            ),
+            ..instantiate_msg()
+        },
+    )
+    .unwrap();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+
+    let response = execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    let msg: MockCovenantInstantiateMsg = match wasm_msg(&response.messages[0]) {
+        WasmMsg::Instantiate { msg, .. } => from_json(msg).unwrap(),
+        msg => panic!("expected a covenant instantiation, got {:?}", msg),
+    };
+
+    assert_eq!(msg.pool_address, "fixed_pair");
+    assert_eq!(msg.covenant_party_config.addr, "fixed_receiver");
+    assert_eq!(msg.pool_price_config.expected_spot_price, Decimal::one());
+}
+
+#[test]
+fn covenant_address_is_stored_when_instantiation_succeeds() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+
+    let response = execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(response.messages[0].id, INSTANTIATE_COVENANT_REPLY_ID);
+    match wasm_msg(&response.messages[0]) {
+        WasmMsg::Instantiate { admin, funds, .. } => {
+            assert_eq!(admin, &Some(env.contract.address.to_string()));
+            assert_eq!(funds, &vec![Coin::new(TOTAL_POOL, DENOM)]);
+        }
+        msg => panic!("expected a covenant instantiation, got {:?}", msg),
+    }
+    assert_eq!(
+        load_proposal(&deps, 0, prop_id).status,
+        ExecutionStatus::Pending
+    );
+
+    reply_ok(
+        &mut deps,
+        &env,
+        INSTANTIATE_COVENANT_REPLY_ID,
+        vec![Event::new("instantiate").add_attribute("_contract_address", "covenant")],
+    );
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Executed);
+    assert_eq!(
+        proposal.position,
+        Some(LiquidityPosition::Covenant {
+            address: Addr::unchecked("covenant"),
+        })
+    );
+    assert_eq!(proposal.deployed_amount, Uint128::new(TOTAL_POOL));
+
+    // Executed proposals can't be executed again
+    assert!(execute_proposal(&mut deps, &env, 0, prop_id).is_err());
+}
+
+#[test]
+fn failed_covenant_instantiation_can_be_retried() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+
+    execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    reply_err(&mut deps, &env, INSTANTIATE_COVENANT_REPLY_ID, "out of gas");
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(
+        proposal.status,
+        ExecutionStatus::Failed {
+            reason: "out of gas".to_string(),
+        }
+    );
+    assert_eq!(proposal.position, None);
+    assert_eq!(proposal.deployed_amount, Uint128::zero());
+
+    let response = execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(response.messages[0].id, INSTANTIATE_COVENANT_REPLY_ID);
+}
+
+#[test]
+fn covenant_reply_without_contract_address_fails() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+
+    execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    let err = reply(
+        deps.as_mut(),
+        env,
+        Reply {
+            id: INSTANTIATE_COVENANT_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("did not return a contract address"));
+}
+
+// Params of a proposal that provides liquidity to the mock Astroport pair, whose price (2) is within its bounds
+fn astroport_params(streamed_withdrawal: Option<StreamedWithdrawal>) -> CovenantParams {
+    CovenantParams {
+        min_price: Some(Decimal::percent(150)),
+        max_price: Some(Decimal::percent(250)),
+        streamed_withdrawal,
+        ..covenant_params(DeploymentMethod::Astroport)
+    }
+}
+
+// Executes an Astroport proposal with `deposit` counterparty deposited, and replies that `share` liquidity
+// tokens were minted. The counterparty that wasn't provided stays in the contract.
+fn deployed_astroport_proposal(
+    deps: &mut MockDeps,
+    params: CovenantParams,
+    deposit: u128,
+    share: u128,
+) -> u64 {
+    let (env, prop_id) = funded_proposal(deps, params);
+    deposit_counterparty(deps, &env, 0, prop_id, deposit);
+
+    execute_proposal(deps, &env, 0, prop_id).unwrap();
+    reply_ok(
+        deps,
+        &env,
+        ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID,
+        vec![Event::new("wasm").add_attribute("share", share.to_string())],
+    );
+    set_counterparty_balance(deps, &env, deposit - 2 * TOTAL_POOL);
+
+    prop_id
+}
+
+#[test]
+fn astroport_liquidity_is_provided_in_the_ratio_of_the_reserves() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, astroport_params(None));
+
+    // The pair holds twice as much counterparty as pool denom
+    let err = execute_proposal(&mut deps, &env, 0, prop_id).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Not enough counterparty deposited, 2000untrn needed"));
+
+    deposit_counterparty(&mut deps, &env, 0, prop_id, 2_500);
+    let response = execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(
+        response.messages[0].id,
+        ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID
+    );
+    match wasm_msg(&response.messages[0]) {
+        WasmMsg::Execute {
+            contract_addr,
+            msg,
+            funds,
+        } => {
+            assert_eq!(contract_addr, PAIR);
+            assert_eq!(
+                funds,
+                &vec![
+                    Coin::new(TOTAL_POOL, DENOM),
+                    Coin::new(2_000, COUNTERPARTY_DENOM)
+                ]
+            );
+            assert_eq!(
+                from_json::<PairExecuteMsg>(msg).unwrap(),
+                PairExecuteMsg::ProvideLiquidity {
+                    assets: vec![
+                        Asset::native(DENOM, Uint128::new(TOTAL_POOL)),
+                        Asset::native(COUNTERPARTY_DENOM, Uint128::new(2_000)),
+                    ],
+                    slippage_tolerance: Some(Decimal::percent(1)),
+                    auto_stake: Some(false),
+                    receiver: None,
+                }
+            );
+        }
+        msg => panic!("expected a liquidity provision, got {:?}", msg),
+    }
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.counterparty.unwrap().provided, Uint128::new(2_000));
+}
+
+#[test]
+fn astroport_reply_stores_the_liquidity_tokens_minted() {
+    let mut deps = setup();
+    let prop_id = deployed_astroport_proposal(&mut deps, astroport_params(None), 2_000, 1_000);
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Executed);
+    assert_eq!(
+        proposal.position,
+        Some(LiquidityPosition::Astroport {
+            pair: Addr::unchecked(PAIR),
+            lp_token: LP_TOKEN.to_string(),
+            lp_amount: Uint128::new(1_000),
+        })
+    );
+}
+
+#[test]
+fn astroport_reply_without_share_fails() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, astroport_params(None));
+    deposit_counterparty(&mut deps, &env, 0, prop_id, 2_000);
+    execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+
+    let err = reply(
+        deps.as_mut(),
+        env,
+        Reply {
+            id: ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![Event::new("wasm").add_attribute("action", "provide_liquidity")],
+                data: None,
+            }),
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("did not return the liquidity provided"));
+}
+
+#[test]
+fn astroport_withdrawal_requires_the_share_of_the_reserves() {
+    let mut deps = setup();
+    let prop_id = deployed_astroport_proposal(&mut deps, astroport_params(None), 2_000, 1_000);
+
+    // The term of the proposal is the round after its own
+    let err = withdraw_liquidity(&mut deps, &env_after_rounds(1), 0, prop_id).unwrap_err();
+    assert!(err.to_string().contains("term has not ended yet"));
+
+    let response = withdraw_liquidity(&mut deps, &env_after_rounds(2), 0, prop_id).unwrap();
+    assert_eq!(
+        response.messages[0].id,
+        ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID
+    );
+    match wasm_msg(&response.messages[0]) {
+        WasmMsg::Execute {
+            contract_addr,
+            msg,
+            funds,
+        } => {
+            assert_eq!(contract_addr, PAIR);
+            assert_eq!(funds, &vec![Coin::new(1_000, LP_TOKEN)]);
+            // 1000 of the 10000 liquidity tokens are worth 1000uatom and 2000untrn, minus the slippage tolerance
+            assert_eq!(
+                from_json::<PairExecuteMsg>(msg).unwrap(),
+                PairExecuteMsg::WithdrawLiquidity {
+                    assets: vec![],
+                    min_assets_to_receive: Some(vec![
+                        Asset::native(DENOM, Uint128::new(990)),
+                        Asset::native(COUNTERPARTY_DENOM, Uint128::new(1_980)),
+                    ]),
+                }
+            );
+        }
+        msg => panic!("expected a liquidity withdrawal, got {:?}", msg),
+    }
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Withdrawn);
+    assert_eq!(proposal.position, None);
+    assert_eq!(proposal.deployed_amount, Uint128::zero());
+}
+
+#[test]
+fn astroport_withdrawal_out_of_price_bounds_fails() {
+    let mut deps = setup();
+    let prop_id = deployed_astroport_proposal(&mut deps, astroport_params(None), 2_000, 1_000);
+
+    set_mock_pair(
+        &mut deps,
+        MockPair {
+            counterparty_reserve: Uint128::new(30_000),
+            ..MockPair::default()
+        },
+    );
+    let err = withdraw_liquidity(&mut deps, &env_after_rounds(2), 0, prop_id).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("outside of the proposal's price bounds"));
+}
+
+#[test]
+fn astroport_withdrawal_refunds_counterparty_to_depositor() {
+    let mut deps = setup();
+    let prop_id = deployed_astroport_proposal(&mut deps, astroport_params(None), 2_500, 1_000);
+    let env = env_after_rounds(2);
+
+    withdraw_liquidity(&mut deps, &env, 0, prop_id).unwrap();
+    set_counterparty_balance(&mut deps, &env, 500 + 2_000);
+    let response = reply_ok(
+        &mut deps,
+        &env,
+        ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID,
+        vec![],
+    );
+
+    // What was withdrawn, plus the part of the deposit that was never provided
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: DEPOSITOR.to_string(),
+            amount: vec![Coin::new(2_500, COUNTERPARTY_DENOM)],
+        })
+    );
+    assert_eq!(load_proposal(&deps, 0, prop_id).counterparty, None);
+}
+
+#[test]
+fn streamed_astroport_withdrawal_refunds_counterparty_by_tranche() {
+    let mut deps = setup();
+    let params = astroport_params(Some(StreamedWithdrawal {
+        tranches: 2,
+        interval: 10,
+    }));
+    let prop_id = deployed_astroport_proposal(&mut deps, params, 2_500, 1_000);
+    let mut env = env_after_rounds(2);
+
+    // The first tranche only sends back the counterparty that came out of it
+    let response = withdraw_liquidity(&mut deps, &env, 0, prop_id).unwrap();
+    match wasm_msg(&response.messages[0]) {
+        WasmMsg::Execute { funds, .. } => assert_eq!(funds, &vec![Coin::new(500, LP_TOKEN)]),
+        msg => panic!("expected a liquidity withdrawal, got {:?}", msg),
+    }
+    set_counterparty_balance(&mut deps, &env, 500 + 1_000);
+    let response = reply_ok(
+        &mut deps,
+        &env,
+        ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID,
+        vec![],
+    );
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: DEPOSITOR.to_string(),
+            amount: vec![Coin::new(1_000, COUNTERPARTY_DENOM)],
+        })
+    );
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(
+        proposal.status,
+        ExecutionStatus::Withdrawing {
+            tranches_left: 1,
+            next_height: env.block.height + 10,
+        }
+    );
+    assert_eq!(proposal.deployed_amount, Uint128::new(500));
+    set_counterparty_balance(&mut deps, &env, 500);
+
+    // The last tranche also sends back the part of the deposit that was never provided
+    env.block.height += 10;
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::WithdrawTranche {
+            tranche_id: 0,
+            round_id: 0,
+            prop_id,
+        },
+    )
+    .unwrap();
+    set_counterparty_balance(&mut deps, &env, 500 + 1_000);
+    let response = reply_ok(
+        &mut deps,
+        &env,
+        ASTROPORT_WITHDRAW_LIQUIDITY_REPLY_ID,
+        vec![],
+    );
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: DEPOSITOR.to_string(),
+            amount: vec![Coin::new(1_500, COUNTERPARTY_DENOM)],
+        })
+    );
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Withdrawn);
+    assert_eq!(proposal.counterparty, None);
+}
+
+#[test]
+fn expired_locks_do_not_count_towards_quorum() {
+    let mut deps = setup_with_quorum(Decimal::percent(60));
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, "short_locker", 1_000, ONE_MONTH);
+
+    // The short lock has ended by round 5, but it was never unlocked
+    let env = env_after_rounds(5);
+    let prop_id = create_proposal(&mut deps, &env, covenant_params(DeploymentMethod::Covenant));
+    vote(&mut deps, &env, VOTER, prop_id);
+
+    end_round(&mut deps, &env_after_rounds(6));
+    assert_eq!(round_quorum_reached(&deps, 5), Some(true));
+}
+
+#[test]
+fn locking_after_round_end_does_not_count_towards_its_quorum() {
+    let mut deps = setup_with_quorum(Decimal::percent(60));
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    let prop_id = create_proposal(&mut deps, &env, covenant_params(DeploymentMethod::Covenant));
+    vote(&mut deps, &env, VOTER, prop_id);
+
+    // Locking ends the round first, so the new lock only counts from the next round on
+    let env = env_after_rounds(1);
+    lock_tokens(&mut deps, &env, "late_locker", 1_000, ONE_MONTH * 12);
+    assert_eq!(round_quorum_reached(&deps, 0), Some(true));
+
+    end_round(&mut deps, &env_after_rounds(2));
+    assert_eq!(round_quorum_reached(&deps, 1), Some(false));
+}
+
+#[test]
+fn unlocking_expired_locks_keeps_quorum_consistent() {
+    let mut deps = setup_with_quorum(Decimal::percent(60));
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, "short_locker", 1_000, ONE_MONTH);
+
+    let env = env_after_rounds(5);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("short_locker", &[]),
+        ExecuteMsg::UnlockTokens {},
+    )
+    .unwrap();
+    let prop_id = create_proposal(&mut deps, &env, covenant_params(DeploymentMethod::Covenant));
+    vote(&mut deps, &env, VOTER, prop_id);
+
+    end_round(&mut deps, &env_after_rounds(6));
+    assert_eq!(round_quorum_reached(&deps, 5), Some(true));
+}
+
+#[test]
+fn covenant_is_asked_to_unwind_its_position_when_its_term_ends() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+    execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    reply_ok(
+        &mut deps,
+        &env,
+        INSTANTIATE_COVENANT_REPLY_ID,
+        vec![Event::new("instantiate").add_attribute("_contract_address", "covenant")],
+    );
+
+    // The price of the pair (1) is within the proposal's bounds
+    set_mock_pair(
+        &mut deps,
+        MockPair {
+            counterparty_reserve: Uint128::new(10_000),
+            ..MockPair::default()
+        },
+    );
+    let response = withdraw_liquidity(&mut deps, &env_after_rounds(2), 0, prop_id).unwrap();
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "covenant".to_string(),
+            msg: Binary::from(br#"{"claim":{}}"#),
+            funds: vec![],
+        })
+    );
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Withdrawn);
+    assert_eq!(proposal.position, None);
+    assert_eq!(proposal.deployed_amount, Uint128::zero());
+}
+
+// Returns the number of tranches that ProcessWithdrawals tried to withdraw
+fn process_withdrawals(deps: &mut MockDeps, env: &Env) -> usize {
+    let response = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ProcessWithdrawals { limit: None },
+    )
+    .unwrap();
+    response.messages.len()
+}
+
+#[test]
+fn process_withdrawals_moves_past_tranches_that_fail() {
+    let mut deps = setup();
+    let params = astroport_params(Some(StreamedWithdrawal {
+        tranches: 2,
+        interval: 10,
+    }));
+    let prop_id = deployed_astroport_proposal(&mut deps, params, 2_000, 1_000);
+    let mut env = env_after_rounds(2);
+    withdraw_liquidity(&mut deps, &env, 0, prop_id).unwrap();
+
+    env.block.height += 5;
+    assert_eq!(process_withdrawals(&mut deps, &env), 0);
+
+    // The tranche is due. If its withdrawal fails, it is only retried from the next block on.
+    env.block.height += 5;
+    assert_eq!(process_withdrawals(&mut deps, &env), 1);
+    assert_eq!(process_withdrawals(&mut deps, &env), 0);
+
+    env.block.height += 1;
+    assert_eq!(process_withdrawals(&mut deps, &env), 1);
+
+    // Once the last tranche is withdrawn, there is nothing left to process
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::WithdrawTranche {
+            tranche_id: 0,
+            round_id: 0,
+            prop_id,
+        },
+    )
+    .unwrap();
+    env.block.height += 100;
+    assert_eq!(process_withdrawals(&mut deps, &env), 0);
+}
+
+// Returns the proposals that Tick tried to execute
+fn tick(deps: &mut MockDeps, env: &Env, limit: u32) -> Vec<u64> {
+    let response = sudo(
+        deps.as_mut(),
+        env.clone(),
+        SudoMsg::Tick { limit: Some(limit) },
+    )
+    .unwrap();
+    response
+        .messages
+        .iter()
+        .map(|msg| match wasm_msg(msg) {
+            WasmMsg::Execute { msg, .. } => match from_json(msg).unwrap() {
+                ExecuteMsg::ExecuteProposal { prop_id, .. } => prop_id,
+                msg => panic!("expected a proposal execution, got {:?}", msg),
+            },
+            msg => panic!("expected a proposal execution, got {:?}", msg),
+        })
+        .collect()
+}
+
+#[test]
+fn tick_goes_through_the_proposals_in_turn() {
+    let mut deps = setup();
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 2_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, "other_voter", 1_000, ONE_MONTH * 12);
+    // Neither proposal has a counterparty deposit, so their executions keep failing
+    let first = create_proposal(&mut deps, &env, astroport_params(None));
+    let second = create_proposal(&mut deps, &env, astroport_params(None));
+    vote(&mut deps, &env, VOTER, first);
+    vote(&mut deps, &env, "other_voter", second);
+
+    let env = env_after_rounds(1);
+    assert_eq!(tick(&mut deps, &env, 1), vec![first]);
+    assert_eq!(tick(&mut deps, &env, 1), vec![second]);
+    assert_eq!(tick(&mut deps, &env, 1), vec![first]);
+    assert_eq!(tick(&mut deps, &env, 5), vec![second, first]);
+}
+
+#[test]
+fn tick_retries_failed_deployments() {
+    let mut deps = setup();
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Covenant));
+
+    execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    reply_err(&mut deps, &env, INSTANTIATE_COVENANT_REPLY_ID, "out of gas");
+    assert_eq!(tick(&mut deps, &env, 5), vec![prop_id]);
+
+    execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    reply_ok(
+        &mut deps,
+        &env,
+        INSTANTIATE_COVENANT_REPLY_ID,
+        vec![Event::new("instantiate").add_attribute("_contract_address", "covenant")],
+    );
+    assert_eq!(tick(&mut deps, &env, 5), Vec::<u64>::new());
+}
+
+#[test]
+fn rounds_far_behind_are_ended_in_several_calls() {
+    let mut deps = setup();
+    let rounds = MAX_ROUNDS_ENDED_PER_CALL as u64 + 5;
+    let env = env_after_rounds(rounds);
+
+    // Actions in the tranche can't end all the rounds at once
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::CreateProposal {
+            tranche_id: 0,
+            title: "proposal".to_string(),
+            description: String::new(),
+            url: String::new(),
+            covenant_params: Box::new(covenant_params(DeploymentMethod::Covenant)),
+            standing_rounds: None,
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Too many rounds of tranche 0 to end at once"));
+
+    // Tick and EndRound end them in batches. Mock storage isn't reverted when an action fails, so start over.
+    let mut deps = setup();
+    assert_eq!(tick(&mut deps, &env, 5), Vec::<u64>::new());
+    assert_eq!(
+        round_quorum_reached(&deps, MAX_ROUNDS_ENDED_PER_CALL as u64 - 1),
+        Some(true)
+    );
+    assert!(ROUND_MAP
+        .may_load(&deps.storage, (0, rounds))
+        .unwrap()
+        .is_none());
+
+    end_round(&mut deps, &env);
+    assert_eq!(round_quorum_reached(&deps, rounds), None);
+    create_proposal(&mut deps, &env, covenant_params(DeploymentMethod::Covenant));
+}
+
+fn set_sticky_vote(deps: &mut MockDeps, env: &Env, sender: &str) {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(sender, &[]),
+        ExecuteMsg::SetStickyVote {
+            tranche_id: 0,
+            sticky: true,
+        },
+    )
+    .unwrap();
+}
+
+fn has_voted(deps: &MockDeps, round_id: u64, voter: &str) -> bool {
+    VOTE_MAP.has(&deps.storage, (0, round_id, Addr::unchecked(voter)))
+}
+
+#[test]
+fn sticky_votes_are_carried_over_by_tick_and_on_late_opt_in() {
+    let mut deps = mock_dependencies();
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(ADMIN, &[]),
+        InstantiateMsg {
+            max_standing_rounds: 2,
+            ..instantiate_msg()
+        },
+    )
+    .unwrap();
+    set_mock_pair(&mut deps, MockPair::default());
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, "a_voter", 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, "b_voter", 1_000, ONE_MONTH * 12);
+    let response = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::CreateProposal {
+            tranche_id: 0,
+            title: "proposal".to_string(),
+            description: String::new(),
+            url: String::new(),
+            covenant_params: Box::new(covenant_params(DeploymentMethod::Covenant)),
+            standing_rounds: Some(2),
+        },
+    )
+    .unwrap();
+    let prop_id: u64 = response
+        .attributes
+        .iter()
+        .find(|attr| attr.key == "prop_id")
+        .map(|attr| attr.value.parse().unwrap())
+        .unwrap();
+    vote(&mut deps, &env, "a_voter", prop_id);
+    vote(&mut deps, &env, "b_voter", prop_id);
+    set_sticky_vote(&mut deps, &env, "b_voter");
+
+    // Tick carries the votes over in the next round
+    let env = env_after_rounds(1);
+    tick(&mut deps, &env, 5);
+    assert!(has_voted(&deps, 1, "b_voter"));
+    assert!(!has_voted(&deps, 1, "a_voter"));
+
+    // Tick has gone past this voter, so their vote is carried over when they opt in
+    set_sticky_vote(&mut deps, &env, "a_voter");
+    assert!(has_voted(&deps, 1, "a_voter"));
+}
+
+#[test]
+fn sticky_votes_left_are_carried_over_before_the_round_is_tallied() {
+    let mut deps = setup_with(InstantiateMsg {
+        max_standing_rounds: 2,
+        ..instantiate_msg()
+    });
+    let env = mock_env();
+    let voters = (0..=DEFAULT_APPLY_STICKY_VOTES_LIMIT)
+        .map(|i| format!("voter_{:02}", i))
+        .collect::<Vec<String>>();
+    let response = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::CreateProposal {
+            tranche_id: 0,
+            title: "proposal".to_string(),
+            description: String::new(),
+            url: String::new(),
+            covenant_params: Box::new(covenant_params(DeploymentMethod::Covenant)),
+            standing_rounds: Some(2),
+        },
+    )
+    .unwrap();
+    let prop_id = created_prop_id(&response);
+    for voter in voters.iter() {
+        lock_tokens(&mut deps, &env, voter, 1_000, ONE_MONTH * 12);
+        vote(&mut deps, &env, voter, prop_id);
+        set_sticky_vote(&mut deps, &env, voter);
+    }
+
+    // Tick never ran during the second round. Ending it carries the first batch of sticky votes over, and waits
+    // for the rest before tallying it.
+    let env = env_after_rounds(2);
+    end_round(&mut deps, &env);
+    assert_eq!(round_quorum_reached(&deps, 0), Some(true));
+    assert_eq!(round_quorum_reached(&deps, 1), None);
+    assert!(!has_voted(&deps, 1, voters.last().unwrap()));
+
+    end_round(&mut deps, &env);
+    assert!(voters.iter().all(|voter| has_voted(&deps, 1, voter)));
+    assert_eq!(round_quorum_reached(&deps, 1), Some(true));
+    assert_eq!(
+        round_result(&deps, &env, 1).total_power_voting,
+        round_result(&deps, &env, 0).total_power_voting
+    );
+}
+
+fn transfer_nft(
+    deps: &mut MockDeps,
+    env: &Env,
+    sender: &str,
+    recipient: &str,
+    token_id: &str,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(sender, &[]),
+        ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: token_id.to_string(),
+        },
+    )
+}
+
+fn approve(
+    deps: &mut MockDeps,
+    env: &Env,
+    sender: &str,
+    spender: &str,
+    expires: Option<Expiration>,
+) {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(sender, &[]),
+        ExecuteMsg::Approve {
+            spender: spender.to_string(),
+            token_id: "0".to_string(),
+            expires,
+        },
+    )
+    .unwrap();
+}
+
+fn owner_of(deps: &MockDeps, env: &Env, token_id: &str) -> OwnerOfResponse {
+    from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::OwnerOf {
+                token_id: token_id.to_string(),
+                include_expired: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+fn num_tokens(deps: &MockDeps) -> u64 {
+    let response: NumTokensResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::NumTokens {}).unwrap()).unwrap();
+    response.count
+}
+
+#[test]
+fn approved_spender_can_transfer_lock_once() {
+    let mut deps = setup();
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+
+    assert!(transfer_nft(&mut deps, &env, "spender", RECEIVER, "0").is_err());
+
+    approve(&mut deps, &env, VOTER, "spender", None);
+    assert_eq!(owner_of(&deps, &env, "0").approvals.len(), 1);
+    transfer_nft(&mut deps, &env, "spender", RECEIVER, "0").unwrap();
+
+    // The approval was cleared when the lock changed hands
+    let owner = owner_of(&deps, &env, "0");
+    assert_eq!(owner.owner, RECEIVER);
+    assert!(owner.approvals.is_empty());
+    assert!(transfer_nft(&mut deps, &env, "spender", VOTER, "0").is_err());
+}
+
+#[test]
+fn expired_and_revoked_approvals_do_not_allow_transfers() {
+    let mut deps = setup();
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+
+    // Approvals that have already expired are rejected
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(VOTER, &[]),
+        ExecuteMsg::Approve {
+            spender: "spender".to_string(),
+            token_id: "0".to_string(),
+            expires: Some(Expiration::AtHeight(env.block.height)),
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Approval has already expired"));
+
+    approve(
+        &mut deps,
+        &env,
+        VOTER,
+        "spender",
+        Some(Expiration::AtHeight(env.block.height + 1)),
+    );
+    let mut later = env.clone();
+    later.block.height += 1;
+    assert!(owner_of(&deps, &later, "0").approvals.is_empty());
+    assert!(transfer_nft(&mut deps, &later, "spender", RECEIVER, "0").is_err());
+
+    approve(&mut deps, &env, VOTER, "other_spender", None);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(VOTER, &[]),
+        ExecuteMsg::Revoke {
+            spender: "other_spender".to_string(),
+            token_id: "0".to_string(),
+        },
+    )
+    .unwrap();
+    assert!(transfer_nft(&mut deps, &env, "other_spender", RECEIVER, "0").is_err());
+}
+
+#[test]
+fn operator_can_transfer_and_approve_until_revoked() {
+    let mut deps = setup();
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(VOTER, &[]),
+        ExecuteMsg::ApproveAll {
+            operator: "operator".to_string(),
+            expires: None,
+        },
+    )
+    .unwrap();
+    approve(&mut deps, &env, "operator", "spender", None);
+    transfer_nft(&mut deps, &env, "spender", RECEIVER, "0").unwrap();
+    transfer_nft(&mut deps, &env, "operator", RECEIVER, "1").unwrap();
+    assert_eq!(owner_of(&deps, &env, "1").owner, RECEIVER);
+
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(VOTER, &[]),
+        ExecuteMsg::RevokeAll {
+            operator: "operator".to_string(),
+        },
+    )
+    .unwrap();
+    assert!(transfer_nft(&mut deps, &env, "operator", RECEIVER, "2").is_err());
+}
+
+#[test]
+fn num_tokens_counts_locks_and_unlocks() {
+    let mut deps = setup();
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 3);
+    assert_eq!(num_tokens(&deps), 2);
+
+    let mut env = env;
+    env.block.time = env.block.time.plus_nanos(ONE_MONTH + 1);
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info(VOTER, &[]),
+        ExecuteMsg::UnlockTokens {},
+    )
+    .unwrap();
+    assert_eq!(num_tokens(&deps), 1);
+}
+
+// Returns the voting power of VOTER and the total power, at the height and in the round
+fn voting_powers(deps: &MockDeps, height: u64, round_id: u64) -> [Uint128; 4] {
+    let at_height: VotingPowerAtHeightResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::VotingPowerAtHeight {
+                address: VOTER.to_string(),
+                height: Some(height),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let total_at_height: TotalPowerAtHeightResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TotalPowerAtHeight {
+                height: Some(height),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let at_round: VotingPowerAtRoundResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::VotingPowerAtRound {
+                address: VOTER.to_string(),
+                tranche_id: 0,
+                round_id,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let total_at_round: TotalPowerAtRoundResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TotalPowerAtRound {
+                tranche_id: 0,
+                round_id,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    [
+        at_height.power,
+        total_at_height.power,
+        at_round.power,
+        total_at_round.power,
+    ]
+}
+
+#[test]
+fn voting_power_queries_agree_with_votes() {
+    let mut deps = mock_dependencies();
+    set_mock_pair(&mut deps, MockPair::default());
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(ADMIN, &[]),
+        InstantiateMsg {
+            time_weighted_voting_power: true,
+            ..instantiate_msg()
+        },
+    )
+    .unwrap();
+
+    // 4x, 1x and 1.5x of the tokens, given the time left after the end of the first round
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+    lock_tokens(&mut deps, &env, VOTER, 1_001, ONE_MONTH * 3);
+    let prop_id = create_proposal(&mut deps, &env, covenant_params(DeploymentMethod::Covenant));
+    vote(&mut deps, &env, VOTER, prop_id);
+    assert_eq!(load_proposal(&deps, 0, prop_id).power.u128(), 6_501);
+    assert_eq!(
+        voting_powers(&deps, env.block.height + 1, 0),
+        [Uint128::new(6_501); 4]
+    );
+
+    // By the eleventh round, the one month lock has expired and the three months lock has less than a month left
+    let round_id = MAX_ROUNDS_ENDED_PER_CALL as u64;
+    let later = env_after_rounds(round_id);
+    end_round(&mut deps, &later);
+    let prop_id = create_proposal(
+        &mut deps,
+        &later,
+        covenant_params(DeploymentMethod::Covenant),
+    );
+    vote(&mut deps, &later, VOTER, prop_id);
+    assert_eq!(load_proposal(&deps, round_id, prop_id).power.u128(), 5_001);
+    assert_eq!(
+        voting_powers(&deps, later.block.height + 1, round_id),
+        [Uint128::new(5_001); 4]
+    );
+
+    // The first round, and the heights it was current at, keep their power
+    assert_eq!(
+        voting_powers(&deps, env.block.height + 1, 0),
+        [Uint128::new(6_501); 4]
+    );
+}
+
+#[test]
+fn voting_power_at_height_is_the_locked_tokens_without_time_weighting() {
+    let mut deps = setup();
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH);
+    lock_tokens(&mut deps, &env, "other_voter", 500, ONE_MONTH * 12);
+    assert_eq!(
+        voting_powers(&deps, env.block.height, 0)[..2],
+        [Uint128::zero(); 2]
+    );
+    assert_eq!(
+        voting_powers(&deps, env.block.height + 1, 0)[..2],
+        [Uint128::new(2_000), Uint128::new(2_500)]
+    );
+
+    // The one month lock can't vote in later rounds, but its tokens are locked until they are unlocked
+    let round_id = MAX_ROUNDS_ENDED_PER_CALL as u64;
+    let later = env_after_rounds(round_id);
+    end_round(&mut deps, &later);
+    assert_eq!(
+        voting_powers(&deps, later.block.height + 1, round_id),
+        [
+            Uint128::new(2_000),
+            Uint128::new(2_500),
+            Uint128::new(1_000),
+            Uint128::new(1_500)
+        ]
+    );
+
+    let mut unlocked = later.clone();
+    unlocked.block.height += 1;
+    execute(
+        deps.as_mut(),
+        unlocked.clone(),
+        mock_info(VOTER, &[]),
+        ExecuteMsg::UnlockTokens {},
+    )
+    .unwrap();
+    assert_eq!(
+        voting_powers(&deps, unlocked.block.height + 1, round_id)[..2],
+        [Uint128::new(1_000), Uint128::new(1_500)]
+    );
+    assert_eq!(
+        voting_powers(&deps, unlocked.block.height, round_id)[..2],
+        [Uint128::new(2_000), Uint128::new(2_500)]
+    );
+}
+
+#[test]
+fn dao_and_info_queries() {
+    let deps = setup();
+    let info: InfoResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Info {}).unwrap()).unwrap();
+    assert_eq!(
+        info.info,
+        ContractInfo {
+            contract: "atom_wars".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    );
+
+    // The DAO defaults to the instantiator
+    let dao: Addr = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Dao {}).unwrap()).unwrap();
+    assert_eq!(dao, Addr::unchecked(ADMIN));
+
+    let deps = setup_with(InstantiateMsg {
+        dao: Some("dao".to_string()),
+        ..instantiate_msg()
+    });
+    let dao: Addr = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Dao {}).unwrap()).unwrap();
+    assert_eq!(dao, Addr::unchecked("dao"));
+}
+
+fn assert_rejected(deps: &mut MockDeps, covenant_params: CovenantParams, reason: &str) {
+    let err = try_create_proposal(deps, &mock_env(), covenant_params).unwrap_err();
+    assert!(err.to_string().contains(reason), "{}: {}", reason, err);
+}
+
+fn osmosis_params(deployment: DeploymentMethod) -> CovenantParams {
+    CovenantParams {
+        dex: Dex::Osmosis,
+        pool_id: "1".to_string(),
+        ..covenant_params(deployment)
+    }
+}
+
+#[test]
+fn invalid_covenant_params_are_rejected() {
+    let mut deps = setup();
+    let params = || covenant_params(DeploymentMethod::Astroport);
+    let cases = vec![
+        (
+            CovenantParams {
+                duration: 0,
+                ..params()
+            },
+            "Duration must be at least one round",
+        ),
+        (
+            CovenantParams {
+                slippage_tolerance: Decimal::percent(101),
+                ..params()
+            },
+            "Slippage tolerance must be between 0 and 1",
+        ),
+        (
+            CovenantParams {
+                min_price: Some(Decimal::zero()),
+                ..params()
+            },
+            "Price bounds must be greater than zero",
+        ),
+        (
+            CovenantParams {
+                max_price: Some(Decimal::zero()),
+                ..params()
+            },
+            "Price bounds must be greater than zero",
+        ),
+        (
+            CovenantParams {
+                min_price: Some(Decimal::one()),
+                max_price: Some(Decimal::one()),
+                ..params()
+            },
+            "Minimum price must be lower than maximum price",
+        ),
+        (
+            CovenantParams {
+                streamed_withdrawal: Some(StreamedWithdrawal {
+                    tranches: 0,
+                    interval: 10,
+                }),
+                ..params()
+            },
+            "Streamed withdrawals need at least one tranche",
+        ),
+        (
+            CovenantParams {
+                streamed_withdrawal: Some(StreamedWithdrawal {
+                    tranches: 2,
+                    interval: 0,
+                }),
+                ..params()
+            },
+            "Streamed withdrawals need at least one tranche",
+        ),
+        (
+            CovenantParams {
+                streamed_withdrawal: Some(StreamedWithdrawal {
+                    tranches: 2,
+                    interval: 10,
+                }),
+                ..covenant_params(DeploymentMethod::Covenant)
+            },
+            "Covenant positions can't be withdrawn in tranches",
+        ),
+        (
+            CovenantParams {
+                party_receiver_addr: String::new(),
+                ..params()
+            },
+            "Party receiver address must be set",
+        ),
+        (
+            covenant_params(DeploymentMethod::OsmosisGamm),
+            "Osmosis deployments can only target Osmosis pools",
+        ),
+        (
+            osmosis_params(DeploymentMethod::Astroport),
+            "Astroport deployments can only target Astroport pairs",
+        ),
+        (
+            osmosis_params(DeploymentMethod::OsmosisConcentrated {
+                lower_tick: 100,
+                upper_tick: 100,
+            }),
+            "Lower tick must be lower than upper tick",
+        ),
+        (
+            CovenantParams {
+                counterparty_denom: DENOM.to_string(),
+                ..params()
+            },
+            "Counterparty denom must be different from the pool denom",
+        ),
+        (
+            CovenantParams {
+                pool_id: "unknown_pair".to_string(),
+                ..params()
+            },
+            "Unknown Astroport pair",
+        ),
+        (
+            CovenantParams {
+                pool_id: "pool".to_string(),
+                ..osmosis_params(DeploymentMethod::OsmosisGamm)
+            },
+            "Osmosis pool id must be a number",
+        ),
+        (
+            osmosis_params(DeploymentMethod::OsmosisGamm),
+            "Unknown Osmosis pool",
+        ),
+        (
+            CovenantParams {
+                counterparty_denom: "uosmo".to_string(),
+                ..params()
+            },
+            "Pool does not pair the pool denom with the counterparty denom",
+        ),
+    ];
+
+    for (covenant_params, reason) in cases {
+        assert_rejected(&mut deps, covenant_params, reason);
+    }
+
+    // None of the rejected proposals were stored, and the valid params are accepted
+    assert_eq!(create_proposal(&mut deps, &mock_env(), params()), 0);
+}
+
+#[test]
+fn proposals_outside_the_tranche_eligibility_are_rejected() {
+    let tranche = |eligibility| TrancheInfo {
+        eligibility,
+        ..instantiate_msg().tranches[0].clone()
+    };
+    let reason = "Tranche tranche does not accept proposals for this counterparty denom";
+
+    let mut deps = setup_with(InstantiateMsg {
+        tranches: vec![tranche(ProposalEligibility::CounterpartyDenoms {
+            denoms: vec!["uosmo".to_string()],
+        })],
+        ..instantiate_msg()
+    });
+    assert_rejected(
+        &mut deps,
+        covenant_params(DeploymentMethod::Astroport),
+        reason,
+    );
+
+    let mut deps = setup_with(InstantiateMsg {
+        tranches: vec![tranche(ProposalEligibility::ConsumerChains)],
+        ..instantiate_msg()
+    });
+    assert_rejected(
+        &mut deps,
+        covenant_params(DeploymentMethod::Astroport),
+        reason,
+    );
+
+    // Once the counterparty denom is registered as a consumer chain, the proposal can be created
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(ADMIN, &[]),
+        ExecuteMsg::AddConsumerChain {
+            denom: COUNTERPARTY_DENOM.to_string(),
+            chain_id: "neutron-1".to_string(),
+        },
+    )
+    .unwrap();
+    create_proposal(
+        &mut deps,
+        &mock_env(),
+        covenant_params(DeploymentMethod::Astroport),
+    );
+}
+
+fn expire_proposal(
+    deps: &mut MockDeps,
+    env: &Env,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExpireProposal {
+            tranche_id: 0,
+            round_id,
+            prop_id,
+        },
+    )
+}
+
+fn round_result(deps: &MockDeps, env: &Env, round_id: u64) -> RoundResult {
+    tranche_round_result(deps, env, 0, round_id)
+}
+
+fn tranche_round_result(deps: &MockDeps, env: &Env, tranche_id: u64, round_id: u64) -> RoundResult {
+    let response: RoundResultResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::RoundResult {
+                tranche_id,
+                round_id,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    response.result
+}
+
+#[test]
+fn proposals_expire_only_after_their_execution_deadline() {
+    let mut deps = setup_with(InstantiateMsg {
+        failed_allocation_policy: FailedAllocationPolicy::Redistribute,
+        ..instantiate_msg()
+    });
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Astroport));
+    deposit_counterparty(&mut deps, &env, 0, prop_id, 500);
+
+    // The proposal can still be executed during the round after its own
+    let err = expire_proposal(&mut deps, &env, 0, prop_id).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Execution deadline has not passed yet"));
+    assert_eq!(
+        load_proposal(&deps, 0, prop_id).status,
+        ExecutionStatus::Pending
+    );
+
+    // Once that round is over, it expires and the counterparty deposit goes back to the depositor
+    let env = env_after_rounds(2);
+    end_round(&mut deps, &env);
+    let response = expire_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: DEPOSITOR.to_string(),
+            amount: vec![Coin::new(500, COUNTERPARTY_DENOM)],
+        })
+    );
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Expired);
+    assert_eq!(proposal.counterparty, None);
+
+    let err = expire_proposal(&mut deps, &env, 0, prop_id).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Proposal was executed or has already expired"));
+}
+
+#[test]
+fn expired_allocations_are_redistributed_to_the_current_round() {
+    for (policy, total_pool) in [
+        (FailedAllocationPolicy::Redistribute, 2 * TOTAL_POOL),
+        (FailedAllocationPolicy::ReturnToPool, TOTAL_POOL),
+    ] {
+        let mut deps = setup_with(InstantiateMsg {
+            failed_allocation_policy: policy,
+            ..instantiate_msg()
+        });
+        let (_, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Astroport));
+        assert_eq!(
+            round_result(&deps, &env_after_rounds(1), 0).funded[0].amount,
+            Uint128::new(TOTAL_POOL)
+        );
+
+        let env = env_after_rounds(2);
+        end_round(&mut deps, &env);
+        expire_proposal(&mut deps, &env, 0, prop_id).unwrap();
+
+        // Two proposals of round 2 split the pool, with the expired allocation on top of it when it is
+        // redistributed
+        let first = create_proposal(
+            &mut deps,
+            &env,
+            covenant_params(DeploymentMethod::Astroport),
+        );
+        let second = create_proposal(
+            &mut deps,
+            &env,
+            covenant_params(DeploymentMethod::Astroport),
+        );
+        lock_tokens(&mut deps, &env, "other_voter", 3_000, ONE_MONTH * 12);
+        vote(&mut deps, &env, VOTER, first);
+        vote(&mut deps, &env, "other_voter", second);
+
+        let result = round_result(&deps, &env_after_rounds(3), 2);
+        assert_eq!(result.total_pool, Uint128::new(total_pool));
+        assert_eq!(
+            result
+                .funded
+                .iter()
+                .map(|prop| (prop.prop_id, prop.amount.u128()))
+                .collect::<Vec<_>>(),
+            vec![(second, total_pool * 3 / 4), (first, total_pool / 4)]
+        );
+    }
+}
+
+#[test]
+fn round_results_are_recorded_once_when_the_round_ends() {
+    let mut deps = setup_with(InstantiateMsg {
+        failed_allocation_policy: FailedAllocationPolicy::Redistribute,
+        ..instantiate_msg()
+    });
+    let (env, prop_id) = funded_proposal(&mut deps, covenant_params(DeploymentMethod::Astroport));
+    assert!(!ROUND_RESULTS.has(&deps.storage, (0, 0)));
+
+    end_round(&mut deps, &env);
+    let result = ROUND_RESULTS.load(&deps.storage, (0, 0)).unwrap();
+    assert_eq!(round_result(&deps, &env, 0), result);
+    assert_eq!(result.funded[0].prop_id, prop_id);
+
+    // Votes of the next round, config changes, and the allocation of the round expiring into a later one don't
+    // change it
+    lock_tokens(&mut deps, &env, "other_voter", 3_000, ONE_MONTH * 12);
+    let other = create_proposal(
+        &mut deps,
+        &env,
+        covenant_params(DeploymentMethod::Astroport),
+    );
+    vote(&mut deps, &env, VOTER, other);
+    vote(&mut deps, &env, "other_voter", other);
+    manage_registry(
+        &mut deps,
+        ADMIN,
+        ExecuteMsg::SetProposalCreationMode {
+            mode: ProposalCreationMode::Curated,
+        },
+    )
+    .unwrap();
+
+    let env = env_after_rounds(2);
+    end_round(&mut deps, &env);
+    expire_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(ROUND_RESULTS.load(&deps.storage, (0, 0)).unwrap(), result);
+    assert_eq!(round_result(&deps, &env, 0), result);
+}
+
+const LOWER_TICK: i64 = -1_000;
+const UPPER_TICK: i64 = 1_000;
+
+// Executes a funded proposal deployed straight into the mock Osmosis pool, and returns the response
+fn executed_osmosis_proposal(
+    deps: &mut MockDeps,
+    deployment: DeploymentMethod,
+) -> (Env, u64, Response) {
+    deps.querier.osmosis_pool = Some(MockOsmosisPool::default());
+    let (env, prop_id) = funded_proposal(deps, osmosis_params(deployment));
+    let response = execute_proposal(deps, &env, 0, prop_id).unwrap();
+    (env, prop_id, response)
+}
+
+#[test]
+fn osmosis_gamm_proposal_joins_the_pool_with_the_pool_denom() {
+    let mut deps = setup();
+    let (env, prop_id, response) =
+        executed_osmosis_proposal(&mut deps, DeploymentMethod::OsmosisGamm);
+
+    // The pool gives 100 shares per token, and 1% slippage is tolerated
+    assert_eq!(response.messages[0].id, OSMOSIS_JOIN_POOL_REPLY_ID);
+    assert_eq!(
+        response.messages[0].msg,
+        MsgJoinSwapExternAmountIn {
+            sender: env.contract.address.to_string(),
+            pool_id: OSMOSIS_POOL_ID,
+            token_in: Some(Coin::new(TOTAL_POOL, DENOM).into()),
+            share_out_min_amount: "99000".to_string(),
+        }
+        .into()
+    );
+
+    let data = MsgJoinSwapExternAmountInResponse {
+        share_out_amount: "99500".to_string(),
+    };
+    reply_data(
+        &mut deps,
+        &env,
+        OSMOSIS_JOIN_POOL_REPLY_ID,
+        Some(data.into()),
+    )
+    .unwrap();
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Executed);
+    assert_eq!(proposal.deployed_amount, Uint128::new(TOTAL_POOL));
+    assert_eq!(
+        proposal.position,
+        Some(LiquidityPosition::OsmosisGamm {
+            pool_id: OSMOSIS_POOL_ID,
+            shares: Uint128::new(99_500),
+        })
+    );
+}
+
+#[test]
+fn osmosis_concentrated_proposal_creates_a_position_in_its_tick_range() {
+    let mut deps = setup();
+    let (env, prop_id, response) = executed_osmosis_proposal(
+        &mut deps,
+        DeploymentMethod::OsmosisConcentrated {
+            lower_tick: LOWER_TICK,
+            upper_tick: UPPER_TICK,
+        },
+    );
+
+    // The pool denom is the pool's token0, so only the minimum amount of token0 is set
+    assert_eq!(response.messages[0].id, OSMOSIS_CREATE_POSITION_REPLY_ID);
+    assert_eq!(
+        response.messages[0].msg,
+        MsgCreatePosition {
+            pool_id: OSMOSIS_POOL_ID,
+            sender: env.contract.address.to_string(),
+            lower_tick: LOWER_TICK,
+            upper_tick: UPPER_TICK,
+            tokens_provided: vec![Coin::new(TOTAL_POOL, DENOM).into()],
+            token_min_amount0: "990".to_string(),
+            token_min_amount1: "0".to_string(),
+        }
+        .into()
+    );
+
+    let data = MsgCreatePositionResponse {
+        position_id: 42,
+        amount0: "995".to_string(),
+        ..MsgCreatePositionResponse::default()
+    };
+    reply_data(
+        &mut deps,
+        &env,
+        OSMOSIS_CREATE_POSITION_REPLY_ID,
+        Some(data.into()),
+    )
+    .unwrap();
+
+    let proposal = load_proposal(&deps, 0, prop_id);
+    assert_eq!(proposal.status, ExecutionStatus::Executed);
+    assert_eq!(
+        proposal.position,
+        Some(LiquidityPosition::OsmosisConcentrated {
+            pool_id: OSMOSIS_POOL_ID,
+            position_id: 42,
+        })
+    );
+}
+
+#[test]
+fn osmosis_replies_without_a_valid_response_fail() {
+    let concentrated = DeploymentMethod::OsmosisConcentrated {
+        lower_tick: LOWER_TICK,
+        upper_tick: UPPER_TICK,
+    };
+    let cases = vec![
+        (
+            DeploymentMethod::OsmosisGamm,
+            OSMOSIS_JOIN_POOL_REPLY_ID,
+            None,
+            "Join pool response is empty",
+        ),
+        (
+            concentrated.clone(),
+            OSMOSIS_CREATE_POSITION_REPLY_ID,
+            None,
+            "Create position response is empty",
+        ),
+        (
+            DeploymentMethod::OsmosisGamm,
+            OSMOSIS_JOIN_POOL_REPLY_ID,
+            Some(Binary::from(b"not a response".to_vec())),
+            "Unable to decode",
+        ),
+        (
+            concentrated,
+            OSMOSIS_CREATE_POSITION_REPLY_ID,
+            Some(Binary::from(b"not a response".to_vec())),
+            "Unable to decode",
+        ),
+    ];
+
+    for (deployment, reply_id, data, reason) in cases {
+        let mut deps = setup();
+        let (env, _, _) = executed_osmosis_proposal(&mut deps, deployment);
+        let err = reply_data(&mut deps, &env, reply_id, data).unwrap_err();
+        assert!(err.to_string().contains(reason), "{}: {}", reason, err);
+    }
+}
+
+#[test]
+fn failed_osmosis_deployment_can_be_retried() {
+    let mut deps = setup();
+    let (env, prop_id, _) = executed_osmosis_proposal(&mut deps, DeploymentMethod::OsmosisGamm);
+
+    reply_err(
+        &mut deps,
+        &env,
+        OSMOSIS_JOIN_POOL_REPLY_ID,
+        "insufficient shares",
+    );
+    assert_eq!(
+        load_proposal(&deps, 0, prop_id).status,
+        ExecutionStatus::Failed {
+            reason: "insufficient shares".to_string()
+        }
+    );
+
+    let response = execute_proposal(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(response.messages[0].id, OSMOSIS_JOIN_POOL_REPLY_ID);
+}
+
+// Creates a proposal in the round of `env` that VOTER votes for, next to another proposal that gets
+// `other_power` of the votes, and returns the id of the first one
+fn proposal_sharing_the_pool(
+    deps: &mut MockDeps,
+    env: &Env,
+    params: CovenantParams,
+    other_power: u128,
+) -> u64 {
+    let prop_id = create_proposal(deps, env, params);
+    vote(deps, env, VOTER, prop_id);
+    if other_power > 0 {
+        let other_id = create_proposal(deps, env, covenant_params(DeploymentMethod::Astroport));
+        lock_tokens(deps, env, "other_voter", other_power, ONE_MONTH * 12);
+        vote(deps, env, "other_voter", other_id);
+    }
+    prop_id
+}
+
+fn active_position(deps: &MockDeps, key: &str) -> Option<(u64, u64, u64)> {
+    ACTIVE_POSITIONS
+        .may_load(&deps.storage, key.to_string())
+        .unwrap()
+}
+
+#[test]
+fn smaller_allocation_withdraws_the_excess_of_a_rolled_over_gamm_position() {
+    let mut deps = setup();
+    let (env, first, _) = executed_osmosis_proposal(&mut deps, DeploymentMethod::OsmosisGamm);
+    let data = MsgJoinSwapExternAmountInResponse {
+        share_out_amount: "99500".to_string(),
+    };
+    reply_data(
+        &mut deps,
+        &env,
+        OSMOSIS_JOIN_POOL_REPLY_ID,
+        Some(data.into()),
+    )
+    .unwrap();
+    assert_eq!(
+        active_position(&deps, "0/osmosis_gamm/1"),
+        Some((0, 0, first))
+    );
+
+    // The proposal of round 1 for the same pool gets half of the pool, once the first one's term has ended
+    let second = proposal_sharing_the_pool(
+        &mut deps,
+        &env,
+        osmosis_params(DeploymentMethod::OsmosisGamm),
+        1_000,
+    );
+    let env = env_after_rounds(2);
+    let response = execute_proposal(&mut deps, &env, 1, second).unwrap();
+
+    // Half of the shares are withdrawn, at 1% slippage from the 497uatom and 994untrn they are worth
+    assert_eq!(response.messages.len(), 1);
+    assert_eq!(response.messages[0].id, OSMOSIS_WITHDRAW_LIQUIDITY_REPLY_ID);
+    assert_eq!(
+        response.messages[0].msg,
+        MsgExitPool {
+            sender: env.contract.address.to_string(),
+            pool_id: OSMOSIS_POOL_ID,
+            share_in_amount: "49750".to_string(),
+            token_out_mins: vec![
+                Coin::new(492, DENOM).into(),
+                Coin::new(984, COUNTERPARTY_DENOM).into(),
+            ],
+        }
+        .into()
+    );
+
+    let proposal = load_proposal(&deps, 1, second);
+    assert_eq!(proposal.status, ExecutionStatus::Executed);
+    assert_eq!(proposal.deployed_amount, Uint128::new(TOTAL_POOL / 2));
+    assert_eq!(
+        proposal.position,
+        Some(LiquidityPosition::OsmosisGamm {
+            pool_id: OSMOSIS_POOL_ID,
+            shares: Uint128::new(49_750),
+        })
+    );
+
+    let previous = load_proposal(&deps, 0, first);
+    assert_eq!(
+        previous.status,
+        ExecutionStatus::RolledOver {
+            round_id: 1,
+            prop_id: second
+        }
+    );
+    assert_eq!(previous.deployed_amount, Uint128::zero());
+    assert_eq!(
+        active_position(&deps, "0/osmosis_gamm/1"),
+        Some((0, 1, second))
+    );
+}
+
+#[test]
+fn bigger_allocation_tops_up_a_rolled_over_concentrated_position() {
+    let mut deps = setup();
+    deps.querier.osmosis_pool = Some(MockOsmosisPool::default());
+    let concentrated = DeploymentMethod::OsmosisConcentrated {
+        lower_tick: LOWER_TICK,
+        upper_tick: UPPER_TICK,
+    };
+    let key = format!("0/osmosis_concentrated/1/{}/{}", LOWER_TICK, UPPER_TICK);
+
+    // The proposal of round 0 gets half of the pool
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    let first =
+        proposal_sharing_the_pool(&mut deps, &env, osmosis_params(concentrated.clone()), 1_000);
+    let env = env_after_rounds(1);
+    execute_proposal(&mut deps, &env, 0, first).unwrap();
+    let data = MsgCreatePositionResponse {
+        position_id: 42,
+        ..MsgCreatePositionResponse::default()
+    };
+    reply_data(
+        &mut deps,
+        &env,
+        OSMOSIS_CREATE_POSITION_REPLY_ID,
+        Some(data.into()),
+    )
+    .unwrap();
+    assert_eq!(active_position(&deps, &key), Some((0, 0, first)));
+
+    // The proposal of round 1 for the same position gets all of it
+    let second = proposal_sharing_the_pool(&mut deps, &env, osmosis_params(concentrated), 0);
+    let env = env_after_rounds(2);
+    let response = execute_proposal(&mut deps, &env, 1, second).unwrap();
+
+    assert_eq!(response.messages[0].id, OSMOSIS_ADD_TO_POSITION_REPLY_ID);
+    assert_eq!(
+        response.messages[0].msg,
+        MsgAddToPosition {
+            position_id: 42,
+            sender: env.contract.address.to_string(),
+            amount0: "500".to_string(),
+            amount1: "0".to_string(),
+            token_min_amount0: "495".to_string(),
+            token_min_amount1: "0".to_string(),
+        }
+        .into()
+    );
+    assert_eq!(
+        load_proposal(&deps, 1, second).status,
+        ExecutionStatus::Pending
+    );
+
+    // Osmosis replaces the position with a new one
+    let data = MsgAddToPositionResponse {
+        position_id: 43,
+        ..MsgAddToPositionResponse::default()
+    };
+    reply_data(
+        &mut deps,
+        &env,
+        OSMOSIS_ADD_TO_POSITION_REPLY_ID,
+        Some(data.into()),
+    )
+    .unwrap();
+
+    let proposal = load_proposal(&deps, 1, second);
+    assert_eq!(proposal.status, ExecutionStatus::Executed);
+    assert_eq!(proposal.deployed_amount, Uint128::new(TOTAL_POOL));
+    assert_eq!(
+        proposal.position,
+        Some(LiquidityPosition::OsmosisConcentrated {
+            pool_id: OSMOSIS_POOL_ID,
+            position_id: 43,
+        })
+    );
+    assert_eq!(
+        load_proposal(&deps, 0, first).status,
+        ExecutionStatus::RolledOver {
+            round_id: 1,
+            prop_id: second
+        }
+    );
+    assert_eq!(active_position(&deps, &key), Some((0, 1, second)));
+}
+
+#[test]
+fn astroport_positions_are_not_rolled_over() {
+    let mut deps = setup();
+    let first = deployed_astroport_proposal(&mut deps, astroport_params(None), 2_000, 1_000);
+
+    let env = env_after_rounds(1);
+    let second = proposal_sharing_the_pool(&mut deps, &env, astroport_params(None), 0);
+    let env = env_after_rounds(2);
+    deposit_counterparty(&mut deps, &env, 1, second, 2_000);
+    let response = execute_proposal(&mut deps, &env, 1, second).unwrap();
+
+    // The second proposal provides its whole allocation, and the first one keeps its position
+    assert_eq!(
+        response.messages[0].id,
+        ASTROPORT_PROVIDE_LIQUIDITY_REPLY_ID
+    );
+    match wasm_msg(&response.messages[0]) {
+        WasmMsg::Execute { funds, .. } => assert_eq!(
+            funds,
+            &vec![
+                Coin::new(TOTAL_POOL, DENOM),
+                Coin::new(2_000, COUNTERPARTY_DENOM)
+            ]
+        ),
+        msg => panic!("expected a liquidity provision, got {:?}", msg),
+    }
+
+    let previous = load_proposal(&deps, 0, first);
+    assert_eq!(previous.status, ExecutionStatus::Executed);
+    assert_eq!(previous.deployed_amount, Uint128::new(TOTAL_POOL));
+    assert!(previous.position.is_some());
+    assert!(ACTIVE_POSITIONS
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .next()
+        .is_none());
+}
+
+fn funded_amounts(result: &RoundResult) -> Vec<(u64, u128)> {
+    result
+        .funded
+        .iter()
+        .map(|prop| (prop.prop_id, prop.amount.u128()))
+        .collect()
+}
+
+#[test]
+fn tranches_vote_and_allocate_independently() {
+    let tranche = instantiate_msg().tranches[0].clone();
+    let mut deps = setup_with(InstantiateMsg {
+        tranches: vec![
+            tranche.clone(),
+            TrancheInfo {
+                name: "second".to_string(),
+                total_pool: Uint128::new(3 * TOTAL_POOL),
+                ..tranche
+            },
+        ],
+        ..instantiate_msg()
+    });
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, "other_voter", 1_000, ONE_MONTH * 12);
+
+    // Two proposals in each tranche
+    let mut props = vec![];
+    for tranche_id in 0..2 {
+        let mut tranche_props = vec![];
+        for _ in 0..2 {
+            let response = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(CREATOR, &[]),
+                ExecuteMsg::CreateProposal {
+                    tranche_id,
+                    title: "proposal".to_string(),
+                    description: String::new(),
+                    url: String::new(),
+                    covenant_params: Box::new(covenant_params(DeploymentMethod::Astroport)),
+                    standing_rounds: None,
+                },
+            )
+            .unwrap();
+            let prop_id = response
+                .attributes
+                .iter()
+                .find(|attr| attr.key == "prop_id")
+                .map(|attr| attr.value.parse::<u64>().unwrap())
+                .unwrap();
+            tranche_props.push(prop_id);
+        }
+        props.push(tranche_props);
+    }
+
+    // Voting in one tranche doesn't use up the power to vote in the other one
+    let votes = [
+        (VOTER, 0, props[0][0]),
+        (VOTER, 1, props[1][1]),
+        ("other_voter", 1, props[1][1]),
+    ];
+    for (voter, tranche_id, proposal_id) in votes {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(voter, &[]),
+            ExecuteMsg::Vote {
+                tranche_id,
+                proposal_id,
+            },
+        )
+        .unwrap();
+    }
+
+    let user_vote = |tranche_id: u64, address: &str| {
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::UserVote {
+                tranche_id,
+                round_id: 0,
+                address: address.to_string(),
+            },
+        )
+        .map(|response| from_json::<UserVoteResponse>(&response).unwrap().vote)
+    };
+    let vote = user_vote(0, VOTER).unwrap();
+    assert_eq!(vote.prop_id, props[0][0]);
+    assert_eq!(user_vote(1, VOTER).unwrap().prop_id, props[1][1]);
+    assert!(user_vote(0, "other_voter").is_err());
+
+    // Each tranche only counts its own votes, and splits its own pool
+    let env = env_after_rounds(1);
+    let first = tranche_round_result(&deps, &env, 0, 0);
+    assert_eq!(first.total_power_voting, vote.power);
+    assert_eq!(first.total_pool, Uint128::new(TOTAL_POOL));
+    assert_eq!(funded_amounts(&first), vec![(props[0][0], TOTAL_POOL)]);
+
+    let second = tranche_round_result(&deps, &env, 1, 0);
+    assert_eq!(second.total_power_voting, vote.power * Uint128::new(2));
+    assert_eq!(second.total_pool, Uint128::new(3 * TOTAL_POOL));
+    assert_eq!(funded_amounts(&second), vec![(props[1][1], 3 * TOTAL_POOL)]);
+}
+
+#[test]
+fn consumer_chain_multiplier_increases_the_allocation_of_consumer_chain_proposals() {
+    let mut deps = setup_with(InstantiateMsg {
+        consumer_chain_multiplier: Decimal::percent(200),
+        ..instantiate_msg()
+    });
+    let env = mock_env();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    lock_tokens(&mut deps, &env, "other_voter", 1_000, ONE_MONTH * 12);
+
+    // Whether the counterparty denom is a consumer chain is recorded when the proposal is created
+    let other = create_proposal(
+        &mut deps,
+        &env,
+        covenant_params(DeploymentMethod::Astroport),
+    );
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(ADMIN, &[]),
+        ExecuteMsg::AddConsumerChain {
+            denom: COUNTERPARTY_DENOM.to_string(),
+            chain_id: "neutron-1".to_string(),
+        },
+    )
+    .unwrap();
+    let consumer = create_proposal(
+        &mut deps,
+        &env,
+        covenant_params(DeploymentMethod::Astroport),
+    );
+    assert!(!load_proposal(&deps, 0, other).consumer_chain);
+    assert!(load_proposal(&deps, 0, consumer).consumer_chain);
+
+    // Both proposals get the same votes, but the consumer chain's count twice when the pool is split
+    vote(&mut deps, &env, VOTER, other);
+    vote(&mut deps, &env, "other_voter", consumer);
+
+    let result = round_result(&deps, &env_after_rounds(1), 0);
+    let funded = |prop_id: u64| {
+        result
+            .funded
+            .iter()
+            .find(|prop| prop.prop_id == prop_id)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(funded(consumer).power, funded(other).power);
+    assert_eq!(
+        funded(consumer).effective_power,
+        funded(other).power * Uint128::new(2)
+    );
+    assert_eq!(funded(consumer).amount, Uint128::new(666));
+    assert_eq!(funded(other).amount, Uint128::new(333));
+}
+
+fn shares(powers: &[u128], max_share: Decimal) -> Vec<Decimal> {
+    let powers: Vec<Uint128> = powers.iter().map(|power| Uint128::new(*power)).collect();
+    capped_shares(&powers, max_share)
+}
+
+#[test]
+fn shares_are_proportional_to_power_below_the_cap() {
+    assert_eq!(
+        shares(&[1, 3], Decimal::one()),
+        vec![Decimal::percent(25), Decimal::percent(75)]
+    );
+}
+
+#[test]
+fn share_above_the_cap_is_redistributed_to_the_other_proposals() {
+    // The first proposal would get 60%, and its extra 10% is split 3:1 between the others
+    assert_eq!(
+        shares(&[6, 3, 1], Decimal::percent(50)),
+        vec![
+            Decimal::percent(50),
+            Decimal::permille(375),
+            Decimal::permille(125)
+        ]
+    );
+
+    // Redistributing the first proposal's extra share takes the second one over the cap as well
+    assert_eq!(
+        shares(&[5, 4, 1], Decimal::percent(40)),
+        vec![
+            Decimal::percent(40),
+            Decimal::percent(40),
+            Decimal::percent(20)
+        ]
+    );
+
+    // When every proposal is capped, the rest of the pool is not allocated
+    assert_eq!(
+        shares(&[1, 1], Decimal::percent(30)),
+        vec![Decimal::percent(30), Decimal::percent(30)]
+    );
+}
+
+#[test]
+fn rounding_dust_is_not_allocated() {
+    let thirds = shares(&[1, 1, 1], Decimal::one());
+    assert_eq!(thirds, vec![Decimal::from_ratio(1u128, 3u128); 3]);
+    assert!(thirds.iter().copied().sum::<Decimal>() < Decimal::one());
+
+    let mut deps = setup_with(InstantiateMsg {
+        top_n_props_count: 3,
+        ..instantiate_msg()
+    });
+    let env = mock_env();
+    for voter in ["voter_a", "voter_b", "voter_c"] {
+        lock_tokens(&mut deps, &env, voter, 1_000, ONE_MONTH * 12);
+        let prop_id = create_proposal(
+            &mut deps,
+            &env,
+            covenant_params(DeploymentMethod::Astroport),
+        );
+        vote(&mut deps, &env, voter, prop_id);
+    }
+
+    // The amounts are rounded down, so they never add up to more than the pool
+    let result = round_result(&deps, &env_after_rounds(1), 0);
+    assert_eq!(
+        result
+            .funded
+            .iter()
+            .map(|prop| prop.amount.u128())
+            .collect::<Vec<_>>(),
+        vec![333; 3]
+    );
+}
+
+#[test]
+fn proposals_below_the_min_vote_share_are_not_funded() {
+    let mut deps = setup_with(InstantiateMsg {
+        top_n_props_count: 3,
+        min_vote_share: Decimal::percent(30),
+        ..instantiate_msg()
+    });
+    let env = mock_env();
+    let mut props = vec![];
+    for (voter, amount) in [("voter_a", 1_000), ("voter_b", 1_000), ("voter_c", 250)] {
+        lock_tokens(&mut deps, &env, voter, amount, ONE_MONTH * 12);
+        let prop_id = create_proposal(
+            &mut deps,
+            &env,
+            covenant_params(DeploymentMethod::Astroport),
+        );
+        vote(&mut deps, &env, voter, prop_id);
+        props.push(prop_id);
+    }
+
+    // The last proposal has 1/9 of the votes, so the pool is split between the other two
+    let result = round_result(&deps, &env_after_rounds(1), 0);
+    let mut funded = funded_amounts(&result);
+    funded.sort_unstable();
+    assert_eq!(
+        funded,
+        vec![(props[0], TOTAL_POOL / 2), (props[1], TOTAL_POOL / 2)]
+    );
+}
+
+// Sets up the contract with a deposit of 100 pool denom per proposal, refunded to proposals with at least 10% of
+// the votes
+fn setup_with_deposit(destination: ForfeitedDepositDestination) -> MockDeps {
+    setup_with(InstantiateMsg {
+        proposal_deposit: Some(Coin::new(100, DENOM)),
+        deposit_refund_vote_share: Decimal::percent(10),
+        forfeited_deposit_destination: destination,
+        ..instantiate_msg()
+    })
+}
+
+fn create_proposal_with_funds(
+    deps: &mut MockDeps,
+    env: &Env,
+    funds: &[Coin],
+) -> Result<u64, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, funds),
+        create_proposal_msg(covenant_params(DeploymentMethod::Astroport), None),
+    )
+    .map(|response| created_prop_id(&response))
+}
+
+fn claim_deposit(
+    deps: &mut MockDeps,
+    env: &Env,
+    round_id: u64,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimDeposit {
+            tranche_id: 0,
+            round_id,
+            prop_id,
+        },
+    )
+}
+
+#[test]
+fn proposals_need_exactly_the_deposit() {
+    let mut deps = setup_with_deposit(ForfeitedDepositDestination::Burn);
+    let env = mock_env();
+
+    for funds in [
+        vec![],
+        vec![Coin::new(99, DENOM)],
+        vec![Coin::new(100, COUNTERPARTY_DENOM)],
+        vec![Coin::new(100, DENOM), Coin::new(1, COUNTERPARTY_DENOM)],
+    ] {
+        let err = create_proposal_with_funds(&mut deps, &env, &funds).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Must send a proposal deposit of 100uatom"));
+    }
+    create_proposal_with_funds(&mut deps, &env, &[Coin::new(100, DENOM)]).unwrap();
+
+    // Without a deposit configured, proposals can't be sent any funds
+    let mut deps = setup();
+    let err = create_proposal_with_funds(&mut deps, &env, &[Coin::new(100, DENOM)]).unwrap_err();
+    assert!(err.to_string().contains("Proposals don't need a deposit"));
+}
+
+#[test]
+fn deposit_is_refunded_with_enough_votes_and_burned_otherwise() {
+    let mut deps = setup_with_deposit(ForfeitedDepositDestination::Burn);
+    let env = mock_env();
+    let deposit = [Coin::new(100, DENOM)];
+    let voted = create_proposal_with_funds(&mut deps, &env, &deposit).unwrap();
+    let ignored = create_proposal_with_funds(&mut deps, &env, &deposit).unwrap();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    vote(&mut deps, &env, VOTER, voted);
+
+    let err = claim_deposit(&mut deps, &env, 0, voted).unwrap_err();
+    assert!(err.to_string().contains("Round has not ended yet"));
+
+    let env = env_after_rounds(1);
+    end_round(&mut deps, &env);
+    let response = claim_deposit(&mut deps, &env, 0, voted).unwrap();
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: CREATOR.to_string(),
+            amount: deposit.to_vec(),
+        })
+    );
+    let err = claim_deposit(&mut deps, &env, 0, voted).unwrap_err();
+    assert!(err.to_string().contains("Deposit was already claimed"));
+
+    let response = claim_deposit(&mut deps, &env, 0, ignored).unwrap();
+    assert_eq!(
+        response.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Burn {
+            amount: deposit.to_vec(),
+        })
+    );
+}
+
+#[test]
+fn forfeited_deposit_can_go_to_the_community_pool() {
+    let mut deps = setup_with_deposit(ForfeitedDepositDestination::CommunityPool);
+    let env = mock_env();
+    let prop_id = create_proposal_with_funds(&mut deps, &env, &[Coin::new(100, DENOM)]).unwrap();
+
+    // A vote for another proposal makes the round's total power non-zero
+    let other = create_proposal_with_funds(&mut deps, &env, &[Coin::new(100, DENOM)]).unwrap();
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    vote(&mut deps, &env, VOTER, other);
+
+    let env = env_after_rounds(1);
+    end_round(&mut deps, &env);
+    let response = claim_deposit(&mut deps, &env, 0, prop_id).unwrap();
+    assert_eq!(
+        response.messages[0].msg,
+        MsgFundCommunityPool {
+            amount: vec![Coin::new(100, DENOM).into()],
+            depositor: env.contract.address.to_string(),
+        }
+        .into()
+    );
+}
+
+#[test]
+fn proposals_per_address_are_limited_per_round() {
+    let mut deps = setup_with(InstantiateMsg {
+        max_proposals_per_address: Some(1),
+        ..instantiate_msg()
+    });
+    let params = || covenant_params(DeploymentMethod::Astroport);
+    create_proposal(&mut deps, &mock_env(), params());
+    let err = try_create_proposal(&mut deps, &mock_env(), params()).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Cannot create more than 1 proposals per round"));
+
+    // The limit starts over in the next round
+    create_proposal(&mut deps, &env_after_rounds(1), params());
+}
+
+fn edit_proposal(deps: &mut MockDeps, prop_id: u64) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::EditProposal {
+            tranche_id: 0,
+            prop_id,
+            title: "edited".to_string(),
+            description: String::new(),
+            url: String::new(),
+        },
+    )
+}
+
+fn withdraw_proposal(deps: &mut MockDeps, prop_id: u64) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(CREATOR, &[]),
+        ExecuteMsg::WithdrawProposal {
+            tranche_id: 0,
+            prop_id,
+        },
+    )
+}
+
+#[test]
+fn proposals_can_be_edited_and_withdrawn_until_they_receive_votes() {
+    let mut deps = setup();
+    let env = mock_env();
+    let params = || covenant_params(DeploymentMethod::Astroport);
+    let voted = create_proposal(&mut deps, &env, params());
+    let withdrawn = create_proposal(&mut deps, &env, params());
+
+    edit_proposal(&mut deps, voted).unwrap();
+    assert_eq!(load_proposal(&deps, 0, voted).title, "edited");
+    withdraw_proposal(&mut deps, withdrawn).unwrap();
+    assert!(PROPOSAL_MAP.has(&deps.storage, (0, 0, voted)));
+    assert!(!PROPOSAL_MAP.has(&deps.storage, (0, 0, withdrawn)));
+
+    // A vote without power is rejected, and does not keep the creator from editing the proposal
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("no_power", &[]),
+        ExecuteMsg::Vote {
+            tranche_id: 0,
+            proposal_id: voted,
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Sender has no voting power in this round"));
+    assert!(!load_proposal(&deps, 0, voted).received_votes);
+    edit_proposal(&mut deps, voted).unwrap();
+
+    // Once it got a vote with power, it can be neither edited nor withdrawn
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    vote(&mut deps, &env, VOTER, voted);
+    assert!(load_proposal(&deps, 0, voted).received_votes);
+    for result in [
+        edit_proposal(&mut deps, voted),
+        withdraw_proposal(&mut deps, voted),
+    ] {
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Proposal has already received votes"));
+    }
+}
+
+fn manage_registry(
+    deps: &mut MockDeps,
+    sender: &str,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg)
+}
+
+#[test]
+fn curated_proposals_can_only_target_approved_pools() {
+    let mut deps = setup_with(InstantiateMsg {
+        proposal_creation_mode: ProposalCreationMode::Curated,
+        governance: Some("governance".to_string()),
+        ..instantiate_msg()
+    });
+    let params = || covenant_params(DeploymentMethod::Astroport);
+    let pool = RegistryEntry::Pool {
+        dex: Dex::Astroport,
+        pool_id: PAIR.to_string(),
+    };
+    let counterparty_denom = RegistryEntry::CounterpartyDenom {
+        denom: COUNTERPARTY_DENOM.to_string(),
+    };
+    let reason = "Proposals can only target approved pools";
+    assert_rejected(&mut deps, params(), reason);
+
+    // Only the admin and governance manage the registry
+    let err = manage_registry(
+        &mut deps,
+        CREATOR,
+        ExecuteMsg::ApprovePool {
+            entry: pool.clone(),
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Sender is neither the admin nor governance"));
+
+    // Approving the pair, or the counterparty denom, lets proposals target it
+    manage_registry(
+        &mut deps,
+        ADMIN,
+        ExecuteMsg::ApprovePool {
+            entry: pool.clone(),
+        },
+    )
+    .unwrap();
+    create_proposal(&mut deps, &mock_env(), params());
+
+    manage_registry(
+        &mut deps,
+        "governance",
+        ExecuteMsg::RevokePool { entry: pool },
+    )
+    .unwrap();
+    assert_rejected(&mut deps, params(), reason);
+
+    manage_registry(
+        &mut deps,
+        "governance",
+        ExecuteMsg::ApprovePool {
+            entry: counterparty_denom,
+        },
+    )
+    .unwrap();
+    create_proposal(&mut deps, &mock_env(), params());
+
+    // Opening proposal creation back up lifts the check
+    let mut deps = setup_with(InstantiateMsg {
+        proposal_creation_mode: ProposalCreationMode::Curated,
+        ..instantiate_msg()
+    });
+    manage_registry(
+        &mut deps,
+        ADMIN,
+        ExecuteMsg::SetProposalCreationMode {
+            mode: ProposalCreationMode::Open,
+        },
+    )
+    .unwrap();
+    create_proposal(&mut deps, &mock_env(), params());
+}
+
+fn query_proposal(deps: &MockDeps, round_id: u64, prop_id: u64) -> Option<Proposal> {
+    query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Proposal {
+            tranche_id: 0,
+            round_id,
+            prop_id,
+        },
+    )
+    .ok()
+    .map(|response| from_json::<ProposalResponse>(&response).unwrap().proposal)
+}
+
+fn retire_proposal(
+    deps: &mut MockDeps,
+    sender: &str,
+    prop_id: u64,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::RetireProposal {
+            tranche_id: 0,
+            prop_id,
+        },
+    )
+}
+
+#[test]
+fn standing_proposals_are_copied_into_later_rounds_until_retired() {
+    let mut deps = setup_with(InstantiateMsg {
+        max_standing_rounds: 3,
+        ..instantiate_msg()
+    });
+    let env = mock_env();
+    let params = || covenant_params(DeploymentMethod::Astroport);
+    for standing_rounds in [0, 4] {
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(CREATOR, &[]),
+            create_proposal_msg(params(), Some(standing_rounds)),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Proposals can stand for 1 to 3 rounds"));
+    }
+
+    let response = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(CREATOR, &[]),
+        create_proposal_msg(params(), Some(3)),
+    )
+    .unwrap();
+    let prop_id = created_prop_id(&response);
+    let single_round = create_proposal(&mut deps, &env, params());
+    lock_tokens(&mut deps, &env, VOTER, 1_000, ONE_MONTH * 12);
+    vote(&mut deps, &env, VOTER, prop_id);
+
+    // The proposal only shows up in a round once the round has started
+    assert!(query_proposal(&deps, 1, prop_id).is_none());
+
+    // It is copied from its first round without its votes, and only stored once it is voted on
+    let env = env_after_rounds(1);
+    end_round(&mut deps, &env);
+    let copy = query_proposal(&deps, 1, prop_id).unwrap();
+    assert_eq!(copy.round_id, 1);
+    assert_eq!(copy.title, "proposal");
+    assert!(copy.power.is_zero());
+    assert!(!copy.received_votes);
+    assert!(!PROPOSAL_MAP.has(&deps.storage, (0, 1, prop_id)));
+    assert!(query_proposal(&deps, 1, single_round).is_none());
+
+    vote(&mut deps, &env, VOTER, prop_id);
+    let stored = PROPOSAL_MAP.load(&deps.storage, (0, 1, prop_id)).unwrap();
+    assert!(!stored.power.is_zero());
+    assert_eq!(
+        query_proposal(&deps, 0, prop_id).unwrap().power,
+        stored.power
+    );
+
+    // Retiring it makes the current round its last one
+    let err = retire_proposal(&mut deps, VOTER, prop_id).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Sender is not the proposal's creator"));
+    let err = retire_proposal(&mut deps, CREATOR, single_round).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Proposal is not a standing proposal"));
+    retire_proposal(&mut deps, CREATOR, prop_id).unwrap();
+    let err = retire_proposal(&mut deps, CREATOR, prop_id).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Proposal does not stand past the current round"));
+
+    let env = env_after_rounds(2);
+    end_round(&mut deps, &env);
+    assert!(query_proposal(&deps, 1, prop_id).is_some());
+    assert!(query_proposal(&deps, 2, prop_id).is_none());
+}
@@ -0,0 +1,824 @@
+use atom_wars::{
+    CurrentRoundResponse, ProposalPowerByTierResponse, ProposalResponse,
+    QueryMsg as AtomWarsQueryMsg, RoundResponse, RoundResultResponse, UserVoteResponse, Vote,
+};
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_json_binary, BankMsg, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response,
+    StdError, StdResult, Timestamp, Uint128,
+};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::msg::{
+    ClaimableTribute, ClaimableTributesResponse, ConfigResponse, ExecuteMsg, HasClaimedResponse,
+    InstantiateMsg, QueryMsg, TributeInfo, TributesResponse,
+};
+use crate::state::{
+    Config, Tribute, TributeWeighting, CONFIG, SWEEP_CURSOR, TRIBUTES_BY_DEPOSITOR, TRIBUTE_CLAIMS,
+    TRIBUTE_ID, TRIBUTE_MAP,
+};
+
+const DEFAULT_SWEEP_LIMIT: u32 = 30;
+const DEFAULT_QUERY_LIMIT: u32 = 30;
+const MAX_QUERY_LIMIT: u32 = 100;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let config = Config {
+        atom_wars_contract: deps.api.addr_validate(&msg.atom_wars_contract)?,
+        claim_window: msg.claim_window,
+        refund_window: msg.refund_window,
+        treasury: msg
+            .treasury
+            .map(|treasury| deps.api.addr_validate(&treasury))
+            .transpose()?,
+    };
+    CONFIG.save(deps.storage, &config)?;
+    TRIBUTE_ID.save(deps.storage, &0)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "initialisation")
+        .add_attribute("sender", info.sender)
+        .add_attribute("atom_wars_contract", config.atom_wars_contract))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::AddTribute {
+            tranche_id,
+            round_id,
+            proposal_id,
+            weighting,
+        } => add_tribute(
+            deps,
+            env,
+            info,
+            tranche_id,
+            round_id,
+            proposal_id,
+            weighting,
+        ),
+        ExecuteMsg::ClaimTribute {
+            tranche_id,
+            round_id,
+            tribute_id,
+        } => claim_tribute(deps, env, info, tranche_id, round_id, tribute_id),
+        ExecuteMsg::RefundTribute {
+            tranche_id,
+            round_id,
+            proposal_id,
+            tribute_id,
+        } => refund_tribute(
+            deps,
+            env,
+            info,
+            tranche_id,
+            round_id,
+            proposal_id,
+            tribute_id,
+        ),
+        ExecuteMsg::SweepTributes {
+            tranche_id,
+            round_id,
+            limit,
+        } => sweep_tributes(deps, env, info, tranche_id, round_id, limit),
+    }
+}
+
+fn add_tribute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    proposal_id: u64,
+    weighting: Option<TributeWeighting>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Check that the round is currently ongoing
+    let current_round_id = query_current_round_id(deps.as_ref(), &config, tranche_id)?;
+    if round_id != current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round is not currently ongoing",
+        )));
+    }
+
+    // Check that the sender has sent funds
+    if info.funds.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Must send funds to add tribute",
+        )));
+    }
+
+    // Check that the sender has only sent one type of coin for the tribute
+    if info.funds.len() != 1 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Must send exactly one coin",
+        )));
+    }
+
+    // Check that the proposal exists in the round
+    query_proposal(deps.as_ref(), &config, tranche_id, round_id, proposal_id)?;
+
+    let weighting = weighting.unwrap_or(TributeWeighting::VotingPower);
+    if let TributeWeighting::MinLockupTier { min_lockup_tier } = weighting {
+        if ![0, 1, 3, 6].contains(&min_lockup_tier) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Minimum lockup tier must be 0, 1, 3, or 6",
+            )));
+        }
+    }
+
+    // Create tribute in TributeMap
+    let tribute_id = TRIBUTE_ID.load(deps.storage)?;
+    TRIBUTE_ID.save(deps.storage, &(tribute_id + 1))?;
+    let tribute = Tribute {
+        funds: info.funds[0].clone(),
+        depositor: info.sender.clone(),
+        refunded: false,
+        weighting,
+        claimed_amount: Uint128::zero(),
+        swept: false,
+    };
+    TRIBUTE_MAP.save(
+        deps.storage,
+        ((tranche_id, round_id), proposal_id, tribute_id),
+        &tribute,
+    )?;
+    TRIBUTES_BY_DEPOSITOR.save(
+        deps.storage,
+        (info.sender.clone(), tribute_id),
+        &(tranche_id, round_id, proposal_id),
+    )?;
+
+    Ok(Response::new().add_attribute("action", "add_tribute"))
+}
+
+// ClaimTribute(tranche_id, round_id, tribute_id):
+//     Check that the round is ended
+//     Check that the claim window is still open
+//     Check that the prop won
+//     Look up sender's vote for the round
+//     Check that the sender voted for the prop
+//     Check that the sender has not already claimed the tribute
+//     Divide sender's eligible vote power by the prop's eligible power to figure out their percentage
+//     Use the sender's percentage to send them the right portion of the tribute
+//     Mark on the sender's vote that they claimed the tribute
+fn claim_tribute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    tribute_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Check that the sender has not already claimed the tribute using the TRIBUTE_CLAIMS map
+    if TRIBUTE_CLAIMS.may_load(deps.storage, (info.sender.clone(), tribute_id))? == Some(true) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender has already claimed the tribute",
+        )));
+    }
+
+    // Check that the round is ended
+    let current_round_id = query_current_round_id(deps.as_ref(), &config, tranche_id)?;
+    if round_id >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+
+    // Check that the claim window for the round has not closed
+    if env.block.time > claim_deadline(deps.as_ref(), &config, tranche_id, round_id)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Claim window for this round has closed",
+        )));
+    }
+
+    // Look up sender's vote for the round, error if it cannot be found
+    let vote: UserVoteResponse = deps.querier.query_wasm_smart(
+        &config.atom_wars_contract,
+        &AtomWarsQueryMsg::UserVote {
+            tranche_id,
+            round_id,
+            address: info.sender.to_string(),
+        },
+    )?;
+    let vote = vote.vote;
+
+    // Check that the sender voted for one of the winning proposals
+    let winning_prop_ids = get_funded_prop_ids(deps.as_ref(), &config, tranche_id, round_id)?;
+    if !winning_prop_ids.contains(&vote.prop_id) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Proposal did not win the last round",
+        )));
+    }
+
+    // Load the tribute
+    let mut tribute = TRIBUTE_MAP.load(
+        deps.storage,
+        ((tranche_id, round_id), vote.prop_id, tribute_id),
+    )?;
+
+    // Find how much of the sender's power and the prop's power counts towards this tribute
+    let (voter_power, prop_power) = get_eligible_power(
+        deps.as_ref(),
+        &config,
+        tranche_id,
+        round_id,
+        &vote,
+        &tribute.weighting,
+    )?;
+
+    if voter_power.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender has no voting power eligible for this tribute",
+        )));
+    }
+
+    // Use the sender's share of the eligible power to figure out how much of the tribute to send them
+    let amount = tribute.funds.amount.multiply_ratio(voter_power, prop_power);
+
+    // Mark in the TRIBUTE_CLAIMS that the sender has claimed this tribute
+    TRIBUTE_CLAIMS.save(deps.storage, (info.sender.clone(), tribute_id), &true)?;
+
+    // Keep track of how much of the tribute has been paid out, so that the remainder can be swept later
+    tribute.claimed_amount += amount;
+    TRIBUTE_MAP.save(
+        deps.storage,
+        ((tranche_id, round_id), vote.prop_id, tribute_id),
+        &tribute,
+    )?;
+
+    // Send the tribute to the sender
+    Ok(Response::new()
+        .add_attribute("action", "claim_tribute")
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin {
+                denom: tribute.funds.denom,
+                amount,
+            }],
+        }))
+}
+
+// RefundTribute(tranche_id, round_id, prop_id, tribute_id):
+//     Check that the round is ended
+//     Check that the refund window is still open
+//     Check that the prop lost
+//     Check that the sender is the depositor of the tribute
+//     Check that the sender has not already refunded the tribute
+//     Send the tribute back to the sender
+fn refund_tribute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    proposal_id: u64,
+    tribute_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Check that the round is ended by checking that the round_id is not the current round
+    let current_round_id = query_current_round_id(deps.as_ref(), &config, tranche_id)?;
+    if round_id >= current_round_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Round has not ended yet",
+        )));
+    }
+
+    // Check that the refund window for the round has not closed
+    if env.block.time > refund_deadline(deps.as_ref(), &config, tranche_id, round_id)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Refund window for this round has closed",
+        )));
+    }
+
+    // Get the winning props for the round
+    let winning_prop_ids = get_funded_prop_ids(deps.as_ref(), &config, tranche_id, round_id)?;
+
+    // Check that this prop lost
+    if winning_prop_ids.contains(&proposal_id) {
+        return Err(ContractError::Std(StdError::generic_err("Proposal won")));
+    }
+
+    // Load the tribute
+    let mut tribute = TRIBUTE_MAP.load(
+        deps.storage,
+        ((tranche_id, round_id), proposal_id, tribute_id),
+    )?;
+
+    // Check that the sender is the depositor of the tribute
+    if tribute.depositor != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender is not the depositor of the tribute",
+        )));
+    }
+
+    // Check that the sender has not already refunded the tribute
+    if tribute.refunded {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sender has already refunded the tribute",
+        )));
+    }
+
+    // Check that the tribute has not been swept
+    if tribute.swept {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Tribute has already been swept",
+        )));
+    }
+
+    // Mark the tribute as refunded
+    tribute.refunded = true;
+    TRIBUTE_MAP.save(
+        deps.storage,
+        ((tranche_id, round_id), proposal_id, tribute_id),
+        &tribute,
+    )?;
+
+    // Send the tribute back to the sender
+    Ok(Response::new()
+        .add_attribute("action", "refund_tribute")
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![tribute.funds],
+        }))
+}
+
+// SweepTributes(tranche_id, round_id, limit):
+//     Check that the claim and refund windows for the round have closed
+//     Iterate up to `limit` tributes of the round, starting after the last one swept
+//     Send the unclaimed remainder of each tribute on a winning proposal, and each unrefunded tribute on a proposal
+//     that did not win, to the treasury, or back to the depositor if there is none
+//     Mark each tribute as swept
+//     Save the last tribute processed so that the next call picks up from there
+fn sweep_tributes(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    tranche_id: u64,
+    round_id: u64,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Check that the claim and refund windows for the round have closed
+    if env.block.time <= claim_deadline(deps.as_ref(), &config, tranche_id, round_id)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Claim window for this round has not closed yet",
+        )));
+    }
+    if env.block.time <= refund_deadline(deps.as_ref(), &config, tranche_id, round_id)? {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Refund window for this round has not closed yet",
+        )));
+    }
+
+    // Continue from where the last sweep of this round left off
+    let start = SWEEP_CURSOR
+        .may_load(deps.storage, (tranche_id, round_id))?
+        .map(Bound::exclusive);
+    let limit = limit.unwrap_or(DEFAULT_SWEEP_LIMIT) as usize;
+
+    let tributes = TRIBUTE_MAP
+        .sub_prefix((tranche_id, round_id))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let winning_prop_ids = get_funded_prop_ids(deps.as_ref(), &config, tranche_id, round_id)?;
+
+    let mut response = Response::new().add_attribute("action", "sweep_tributes");
+    let mut tributes_swept: u64 = 0;
+
+    for ((prop_id, tribute_id), mut tribute) in tributes {
+        SWEEP_CURSOR.save(deps.storage, (tranche_id, round_id), &(prop_id, tribute_id))?;
+
+        if tribute.refunded || tribute.swept {
+            continue;
+        }
+
+        // Nothing was claimed from tributes on proposals that did not win, so all of it is swept
+        let unclaimed = if winning_prop_ids.contains(&prop_id) {
+            tribute.funds.amount - tribute.claimed_amount
+        } else {
+            tribute.funds.amount
+        };
+
+        tribute.swept = true;
+        TRIBUTE_MAP.save(
+            deps.storage,
+            ((tranche_id, round_id), prop_id, tribute_id),
+            &tribute,
+        )?;
+
+        if unclaimed.is_zero() {
+            continue;
+        }
+
+        let recipient = config
+            .treasury
+            .clone()
+            .unwrap_or_else(|| tribute.depositor.clone());
+
+        tributes_swept += 1;
+        response = response.add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: tribute.funds.denom,
+                amount: unclaimed,
+            }],
+        });
+    }
+
+    Ok(response
+        .add_attribute("tranche_id", tranche_id.to_string())
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("tributes_swept", tributes_swept.to_string()))
+}
+
+// Returns how much of the voter's power and of the proposal's power counts towards a tribute with the given weighting
+fn get_eligible_power(
+    deps: Deps,
+    config: &Config,
+    tranche_id: u64,
+    round_id: u64,
+    vote: &Vote,
+    weighting: &TributeWeighting,
+) -> StdResult<(Uint128, Uint128)> {
+    match weighting {
+        TributeWeighting::VotingPower => {
+            let proposal = query_proposal(deps, config, tranche_id, round_id, vote.prop_id)?;
+            Ok((vote.power, proposal.power))
+        }
+        TributeWeighting::MinLockupTier { min_lockup_tier } => {
+            let voter_power: Uint128 = vote
+                .locks
+                .iter()
+                .filter(|lock| lock.lockup_tier >= *min_lockup_tier)
+                .map(|lock| lock.scaled_power)
+                .sum();
+
+            let prop_tiers: ProposalPowerByTierResponse = deps.querier.query_wasm_smart(
+                &config.atom_wars_contract,
+                &AtomWarsQueryMsg::ProposalPowerByTier {
+                    tranche_id,
+                    round_id,
+                    prop_id: vote.prop_id,
+                },
+            )?;
+            let prop_power: Uint128 = prop_tiers
+                .tiers
+                .iter()
+                .filter(|tier| tier.lockup_tier >= *min_lockup_tier)
+                .map(|tier| tier.power)
+                .sum();
+
+            Ok((voter_power, prop_power))
+        }
+    }
+}
+
+// Returns the time after which tribute for the round can no longer be claimed
+fn claim_deadline(
+    deps: Deps,
+    config: &Config,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Timestamp> {
+    Ok(query_round_end(deps, config, tranche_id, round_id)?.plus_nanos(config.claim_window))
+}
+
+// Returns the time after which tribute for the round can no longer be refunded
+fn refund_deadline(
+    deps: Deps,
+    config: &Config,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Timestamp> {
+    Ok(query_round_end(deps, config, tranche_id, round_id)?.plus_nanos(config.refund_window))
+}
+
+fn query_round_end(
+    deps: Deps,
+    config: &Config,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Timestamp> {
+    let round: RoundResponse = deps.querier.query_wasm_smart(
+        &config.atom_wars_contract,
+        &AtomWarsQueryMsg::Round {
+            tranche_id,
+            round_id,
+        },
+    )?;
+    Ok(round.round.round_end)
+}
+
+fn query_current_round_id(deps: Deps, config: &Config, tranche_id: u64) -> StdResult<u64> {
+    let current_round: CurrentRoundResponse = deps.querier.query_wasm_smart(
+        &config.atom_wars_contract,
+        &AtomWarsQueryMsg::CurrentRound { tranche_id },
+    )?;
+    Ok(current_round.round.round_id)
+}
+
+fn query_proposal(
+    deps: Deps,
+    config: &Config,
+    tranche_id: u64,
+    round_id: u64,
+    prop_id: u64,
+) -> StdResult<atom_wars::Proposal> {
+    let proposal: ProposalResponse = deps.querier.query_wasm_smart(
+        &config.atom_wars_contract,
+        &AtomWarsQueryMsg::Proposal {
+            tranche_id,
+            round_id,
+            prop_id,
+        },
+    )?;
+    Ok(proposal.proposal)
+}
+
+// Returns the ids of the proposals that received liquidity in the round, from the result atom_wars recorded when
+// the round ended
+fn get_funded_prop_ids(
+    deps: Deps,
+    config: &Config,
+    tranche_id: u64,
+    round_id: u64,
+) -> StdResult<Vec<u64>> {
+    let round_result: RoundResultResponse = deps.querier.query_wasm_smart(
+        &config.atom_wars_contract,
+        &AtomWarsQueryMsg::RoundResult {
+            tranche_id,
+            round_id,
+        },
+    )?;
+    Ok(round_result
+        .result
+        .funded
+        .into_iter()
+        .map(|funded| funded.prop_id)
+        .collect())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => query_config(deps),
+        QueryMsg::ProposalTributes {
+            tranche_id,
+            round_id,
+            proposal_id,
+        } => query_proposal_tributes(deps, tranche_id, round_id, proposal_id),
+        QueryMsg::RoundTributes {
+            tranche_id,
+            round_id,
+            start_after,
+            limit,
+        } => query_round_tributes(deps, tranche_id, round_id, start_after, limit),
+        QueryMsg::ClaimableTributes {
+            address,
+            tranche_id,
+            start_round_id,
+            limit,
+        } => query_claimable_tributes(deps, env, address, tranche_id, start_round_id, limit),
+        QueryMsg::HasClaimed {
+            address,
+            tribute_id,
+        } => query_has_claimed(deps, address, tribute_id),
+        QueryMsg::RefundableTributes {
+            depositor,
+            start_after,
+            limit,
+        } => query_refundable_tributes(deps, env, depositor, start_after, limit),
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<Binary> {
+    let config = CONFIG.load(deps.storage)?;
+    to_json_binary(&ConfigResponse { config })
+}
+
+// Returns all tributes attached to a proposal
+pub fn query_proposal_tributes(
+    deps: Deps,
+    tranche_id: u64,
+    round_id: u64,
+    proposal_id: u64,
+) -> StdResult<Binary> {
+    let tributes = TRIBUTE_MAP
+        .prefix(((tranche_id, round_id), proposal_id))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|x| {
+            x.map(|(tribute_id, tribute)| TributeInfo {
+                tranche_id,
+                round_id,
+                proposal_id,
+                tribute_id,
+                tribute,
+            })
+        })
+        .collect::<StdResult<Vec<TributeInfo>>>()?;
+    to_json_binary(&TributesResponse { tributes })
+}
+
+// Returns the tributes of a round, ordered by (proposal_id, tribute_id)
+pub fn query_round_tributes(
+    deps: Deps,
+    tranche_id: u64,
+    round_id: u64,
+    start_after: Option<(u64, u64)>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let tributes = TRIBUTE_MAP
+        .sub_prefix((tranche_id, round_id))
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|x| {
+            x.map(|((proposal_id, tribute_id), tribute)| TributeInfo {
+                tranche_id,
+                round_id,
+                proposal_id,
+                tribute_id,
+                tribute,
+            })
+        })
+        .collect::<StdResult<Vec<TributeInfo>>>()?;
+    to_json_binary(&TributesResponse { tributes })
+}
+
+// Returns the tribute that the user could claim right now, looking at up to `limit` ended rounds starting at `start_round_id`.
+// This is the same calculation as ClaimTribute, without sending anything.
+pub fn query_claimable_tributes(
+    deps: Deps,
+    env: Env,
+    address: String,
+    tranche_id: u64,
+    start_round_id: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let config = CONFIG.load(deps.storage)?;
+    let current_round_id = query_current_round_id(deps, &config, tranche_id)?;
+
+    let start_round_id = start_round_id.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as u64;
+    let end_round_id = current_round_id.min(start_round_id.saturating_add(limit));
+
+    let mut claimable = vec![];
+
+    for round_id in start_round_id..end_round_id {
+        // Skip rounds where the claim window has closed
+        if env.block.time > claim_deadline(deps, &config, tranche_id, round_id)? {
+            continue;
+        }
+
+        // Skip rounds where the user did not vote
+        let vote: UserVoteResponse = match deps.querier.query_wasm_smart(
+            &config.atom_wars_contract,
+            &AtomWarsQueryMsg::UserVote {
+                tranche_id,
+                round_id,
+                address: address.to_string(),
+            },
+        ) {
+            Ok(vote) => vote,
+            Err(_) => continue,
+        };
+        let vote = vote.vote;
+
+        // Skip rounds where the user's proposal did not win
+        if !get_funded_prop_ids(deps, &config, tranche_id, round_id)?.contains(&vote.prop_id) {
+            continue;
+        }
+
+        let tributes = TRIBUTE_MAP
+            .prefix(((tranche_id, round_id), vote.prop_id))
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<(u64, Tribute)>>>()?;
+
+        for (tribute_id, tribute) in tributes {
+            if TRIBUTE_CLAIMS.may_load(deps.storage, (address.clone(), tribute_id))? == Some(true) {
+                continue;
+            }
+
+            let (voter_power, prop_power) = get_eligible_power(
+                deps,
+                &config,
+                tranche_id,
+                round_id,
+                &vote,
+                &tribute.weighting,
+            )?;
+            if voter_power.is_zero() {
+                continue;
+            }
+
+            claimable.push(ClaimableTribute {
+                tranche_id,
+                round_id,
+                proposal_id: vote.prop_id,
+                tribute_id,
+                amount: Coin {
+                    denom: tribute.funds.denom,
+                    amount: tribute.funds.amount.multiply_ratio(voter_power, prop_power),
+                },
+            });
+        }
+    }
+
+    to_json_binary(&ClaimableTributesResponse {
+        tributes: claimable,
+    })
+}
+
+pub fn query_has_claimed(deps: Deps, address: String, tribute_id: u64) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let claimed = TRIBUTE_CLAIMS
+        .may_load(deps.storage, (address, tribute_id))?
+        .unwrap_or(false);
+    to_json_binary(&HasClaimedResponse { claimed })
+}
+
+// Returns the depositor's tributes that can currently be refunded: the round has ended, its refund window is still
+// open, the proposal lost, and the tribute has not been refunded or swept
+pub fn query_refundable_tributes(
+    deps: Deps,
+    env: Env,
+    depositor: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let depositor = deps.api.addr_validate(&depositor)?;
+    let config = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let deposits = TRIBUTES_BY_DEPOSITOR
+        .prefix(depositor)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<(u64, (u64, u64, u64))>>>()?;
+
+    let mut tributes = vec![];
+
+    for (tribute_id, (tranche_id, round_id, proposal_id)) in deposits {
+        if round_id >= query_current_round_id(deps, &config, tranche_id)?
+            || env.block.time > refund_deadline(deps, &config, tranche_id, round_id)?
+        {
+            continue;
+        }
+
+        let tribute = TRIBUTE_MAP.load(
+            deps.storage,
+            ((tranche_id, round_id), proposal_id, tribute_id),
+        )?;
+        if tribute.refunded || tribute.swept {
+            continue;
+        }
+
+        if get_funded_prop_ids(deps, &config, tranche_id, round_id)?.contains(&proposal_id) {
+            continue;
+        }
+
+        tributes.push(TributeInfo {
+            tranche_id,
+            round_id,
+            proposal_id,
+            tribute_id,
+            tribute,
+        });
+    }
+
+    to_json_binary(&TributesResponse { tributes })
+}
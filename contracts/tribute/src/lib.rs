@@ -0,0 +1,13 @@
+pub mod contract;
+mod error;
+mod msg;
+mod state;
+
+#[cfg(test)]
+mod testing;
+
+pub use msg::{
+    ClaimableTribute, ClaimableTributesResponse, ConfigResponse, ExecuteMsg, HasClaimedResponse,
+    InstantiateMsg, QueryMsg, TributeInfo, TributesResponse,
+};
+pub use state::{Config, Tribute, TributeWeighting};
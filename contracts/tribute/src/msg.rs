@@ -0,0 +1,113 @@
+use cosmwasm_std::Coin;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Config, Tribute, TributeWeighting};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub atom_wars_contract: String,
+    pub claim_window: u64,
+    pub refund_window: u64,
+    pub treasury: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    AddTribute {
+        tranche_id: u64,
+        round_id: u64,
+        proposal_id: u64,
+        // Defaults to TributeWeighting::VotingPower
+        weighting: Option<TributeWeighting>,
+    },
+    ClaimTribute {
+        tranche_id: u64,
+        round_id: u64,
+        tribute_id: u64,
+    },
+    RefundTribute {
+        tranche_id: u64,
+        round_id: u64,
+        proposal_id: u64,
+        tribute_id: u64,
+    },
+    SweepTributes {
+        tranche_id: u64,
+        round_id: u64,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    ProposalTributes {
+        tranche_id: u64,
+        round_id: u64,
+        proposal_id: u64,
+    },
+    RoundTributes {
+        tranche_id: u64,
+        round_id: u64,
+        // (proposal_id, tribute_id) of the last tribute in the previous page
+        start_after: Option<(u64, u64)>,
+        limit: Option<u32>,
+    },
+    ClaimableTributes {
+        address: String,
+        tranche_id: u64,
+        start_round_id: Option<u64>,
+        limit: Option<u32>,
+    },
+    HasClaimed {
+        address: String,
+        tribute_id: u64,
+    },
+    RefundableTributes {
+        depositor: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+// We define a custom struct for each query response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub config: Config,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TributeInfo {
+    pub tranche_id: u64,
+    pub round_id: u64,
+    pub proposal_id: u64,
+    pub tribute_id: u64,
+    pub tribute: Tribute,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TributesResponse {
+    pub tributes: Vec<TributeInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimableTribute {
+    pub tranche_id: u64,
+    pub round_id: u64,
+    pub proposal_id: u64,
+    pub tribute_id: u64,
+    pub amount: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimableTributesResponse {
+    pub tributes: Vec<ClaimableTribute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HasClaimedResponse {
+    pub claimed: bool,
+}
@@ -0,0 +1,63 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Coin, Uint128};
+use cw_storage_plus::{Item, Map};
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+#[cw_serde]
+pub struct Config {
+    pub atom_wars_contract: Addr,
+    // How long after the end of a round voters can claim tribute, in nanos
+    pub claim_window: u64,
+    // How long after the end of a round depositors can get tribute on proposals that did not win refunded, in nanos.
+    // Once both windows have closed, unclaimed and unrefunded tribute can be swept.
+    pub refund_window: u64,
+    // Where swept tribute goes. If not set, it goes back to the depositor.
+    pub treasury: Option<Addr>,
+}
+
+pub const TRIBUTE_ID: Item<u64> = Item::new("tribute_id");
+
+// TRIBUTE_MAP: key((tranche_id, round_id), prop_id, tribute_id) -> Tribute {
+//     depositor: Address,
+//     funds: Coin,
+//     refunded: bool,
+//     weighting: TributeWeighting,
+//     claimed_amount: Uint128,
+//     swept: bool
+// }
+pub const TRIBUTE_MAP: Map<((u64, u64), u64, u64), Tribute> = Map::new("tribute_map");
+#[cw_serde]
+pub struct Tribute {
+    pub depositor: Addr,
+    pub funds: Coin,
+    pub refunded: bool,
+    pub weighting: TributeWeighting,
+    // Total amount paid out to voters so far
+    pub claimed_amount: Uint128,
+    // Whether the unclaimed remainder was swept after the claim window closed
+    pub swept: bool,
+}
+
+// How the tribute is split between the voters of the proposal
+#[cw_serde]
+pub enum TributeWeighting {
+    // Each voter gets a share proportional to their voting power. Voting power is already scaled by the
+    // lock multiplier, so voters with longer locks get a bigger share.
+    VotingPower,
+    // Only power backed by locks in `min_lockup_tier` or above counts, both for the voter and the proposal total.
+    // Lockup tiers are the minimum number of remaining months that a lock exceeds at the end of the round (0, 1, 3 or 6).
+    MinLockupTier { min_lockup_tier: u64 },
+}
+
+// TRIBUTES_BY_DEPOSITOR: key(depositor_addr, tribute_id) -> (tranche_id, round_id, prop_id)
+// Lets depositors find their tributes without iterating all of TRIBUTE_MAP
+pub const TRIBUTES_BY_DEPOSITOR: Map<(Addr, u64), (u64, u64, u64)> =
+    Map::new("tributes_by_depositor");
+
+// SWEEP_CURSOR: key(tranche_id, round_id) -> (prop_id, tribute_id)
+// The last tribute processed by SweepTributes for a round, so that sweeping can be done in batches
+pub const SWEEP_CURSOR: Map<(u64, u64), (u64, u64)> = Map::new("sweep_cursor");
+
+// TributeClaims: key(sender_addr, tribute_id) -> bool
+pub const TRIBUTE_CLAIMS: Map<(Addr, u64), bool> = Map::new("tribute_claims");
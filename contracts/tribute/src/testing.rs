@@ -0,0 +1,601 @@
+use atom_wars::{
+    CovenantParams, CurrentRoundResponse, DeploymentMethod, Dex, ExecutionStatus, FundedProposal,
+    LockPower, Proposal, ProposalPowerByTierResponse, ProposalResponse,
+    QueryMsg as AtomWarsQueryMsg, Round, RoundResponse, RoundResult, RoundResultResponse,
+    TierPower, UserVoteResponse, Vote,
+};
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+};
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, BankMsg, Binary, Coin, ContractResult, CosmosMsg, Decimal,
+    OwnedDeps, SystemError, SystemResult, Timestamp, Uint128, WasmQuery,
+};
+
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{
+    ClaimableTributesResponse, ExecuteMsg, HasClaimedResponse, InstantiateMsg, QueryMsg,
+    TributesResponse,
+};
+use crate::state::TributeWeighting;
+
+const ATOM_WARS: &str = "atom_wars";
+const DEPOSITOR: &str = "depositor";
+const CLAIM_WINDOW: u64 = 86_400_000_000_000;
+const REFUND_WINDOW: u64 = 2 * CLAIM_WINDOW;
+const ROUND_LENGTH: u64 = 3_600_000_000_000;
+
+// State of the atom_wars contract that the mock answers the tribute contract's queries from
+#[derive(Clone)]
+struct MockAtomWars {
+    current_round_id: u64,
+    // Proposals of round 0, with their power
+    proposals: Vec<(u64, Uint128)>,
+    // Proposals of round 0 that received liquidity
+    funded: Vec<u64>,
+    // Votes cast in round 0
+    votes: Vec<(String, Vote)>,
+}
+
+fn genesis_time() -> Timestamp {
+    // Round 0 ended a minute before the mock block time
+    mock_env()
+        .block
+        .time
+        .minus_nanos(ROUND_LENGTH + 60_000_000_000)
+}
+
+fn round(round_id: u64) -> Round {
+    Round {
+        round_id,
+        round_end: genesis_time().plus_nanos(ROUND_LENGTH * (round_id + 1)),
+        quorum_reached: None,
+    }
+}
+
+fn proposal(prop_id: u64, power: Uint128) -> Proposal {
+    Proposal {
+        tranche_id: 0,
+        round_id: 0,
+        prop_id,
+        creator: Addr::unchecked("creator"),
+        created_at: genesis_time(),
+        title: String::new(),
+        description: String::new(),
+        url: String::new(),
+        received_votes: !power.is_zero(),
+        covenant_params: CovenantParams {
+            dex: Dex::Osmosis,
+            pool_id: "1".to_string(),
+            counterparty_denom: "uosmo".to_string(),
+            min_price: None,
+            max_price: None,
+            slippage_tolerance: Decimal::percent(1),
+            duration: 1,
+            party_receiver_addr: "receiver".to_string(),
+            deployment: DeploymentMethod::OsmosisGamm,
+            streamed_withdrawal: None,
+        },
+        status: ExecutionStatus::Pending,
+        consumer_chain: false,
+        power,
+        effective_power: power,
+        percentage: Decimal::zero(),
+        amount: Uint128::zero(),
+        position: None,
+        deployed_amount: Uint128::zero(),
+        counterparty: None,
+    }
+}
+
+fn vote(prop_id: u64, power: u128, lockup_tier: u64) -> Vote {
+    Vote {
+        prop_id,
+        power: Uint128::new(power),
+        locks: vec![LockPower {
+            lock_id: 0,
+            lockup_time: 0,
+            lockup_tier,
+            raw_power: Uint128::new(power),
+            scaled_power: Uint128::new(power),
+        }],
+    }
+}
+
+impl MockAtomWars {
+    fn query(&self, msg: AtomWarsQueryMsg) -> Result<Binary, String> {
+        let proposal_with_id = |prop_id: u64| {
+            self.proposals
+                .iter()
+                .find(|(id, _)| *id == prop_id)
+                .map(|(id, power)| proposal(*id, *power))
+                .ok_or_else(|| "proposal not found".to_string())
+        };
+
+        let response = match msg {
+            AtomWarsQueryMsg::CurrentRound { .. } => to_json_binary(&CurrentRoundResponse {
+                round: round(self.current_round_id),
+            }),
+            AtomWarsQueryMsg::Round { round_id, .. } => to_json_binary(&RoundResponse {
+                round: round(round_id),
+            }),
+            AtomWarsQueryMsg::Proposal { prop_id, .. } => to_json_binary(&ProposalResponse {
+                proposal: proposal_with_id(prop_id)?,
+            }),
+            AtomWarsQueryMsg::UserVote { address, .. } => {
+                let (_, vote) = self
+                    .votes
+                    .iter()
+                    .find(|(voter, _)| *voter == address)
+                    .ok_or_else(|| "vote not found".to_string())?;
+                to_json_binary(&UserVoteResponse {
+                    address: Addr::unchecked(address),
+                    vote: vote.clone(),
+                })
+            }
+            AtomWarsQueryMsg::RoundResult { round_id, .. } => {
+                to_json_binary(&RoundResultResponse {
+                    result: RoundResult {
+                        round_id,
+                        quorum_reached: true,
+                        total_power_voting: Uint128::new(400),
+                        total_pool: Uint128::new(1_000),
+                        funded: self
+                            .funded
+                            .iter()
+                            .map(|prop_id| {
+                                proposal_with_id(*prop_id).map(|proposal| FundedProposal {
+                                    prop_id: proposal.prop_id,
+                                    power: proposal.power,
+                                    effective_power: proposal.power,
+                                    percentage: Decimal::one(),
+                                    amount: Uint128::new(1_000),
+                                })
+                            })
+                            .collect::<Result<Vec<FundedProposal>, String>>()?,
+                    },
+                })
+            }
+            AtomWarsQueryMsg::ProposalPowerByTier { prop_id, .. } => {
+                let mut tiers: Vec<TierPower> = vec![];
+                for (_, vote) in self
+                    .votes
+                    .iter()
+                    .filter(|(_, vote)| vote.prop_id == prop_id)
+                {
+                    for lock in vote.locks.iter() {
+                        match tiers
+                            .iter_mut()
+                            .find(|tier| tier.lockup_tier == lock.lockup_tier)
+                        {
+                            Some(tier) => tier.power += lock.scaled_power,
+                            None => tiers.push(TierPower {
+                                lockup_tier: lock.lockup_tier,
+                                power: lock.scaled_power,
+                            }),
+                        }
+                    }
+                }
+                to_json_binary(&ProposalPowerByTierResponse { tiers })
+            }
+            _ => return Err("unsupported query".to_string()),
+        };
+        response.map_err(|err| err.to_string())
+    }
+}
+
+type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+fn set_atom_wars(deps: &mut MockDeps, atom_wars: MockAtomWars) {
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == ATOM_WARS => {
+            let msg: AtomWarsQueryMsg = from_json(msg).unwrap();
+            SystemResult::Ok(ContractResult::from(atom_wars.query(msg)))
+        }
+        _ => SystemResult::Err(SystemError::UnsupportedRequest {
+            kind: "unexpected query".to_string(),
+        }),
+    });
+}
+
+// Instantiates the tribute contract, and adds a tribute of 900 for each of proposals 0 and 1 of round 0
+fn setup(weighting: Option<TributeWeighting>) -> MockDeps {
+    let mut deps = mock_dependencies();
+    let atom_wars = MockAtomWars {
+        current_round_id: 0,
+        proposals: vec![(0, Uint128::new(300)), (1, Uint128::new(100))],
+        funded: vec![0],
+        votes: vec![
+            ("alice".to_string(), vote(0, 100, 6)),
+            ("bob".to_string(), vote(0, 200, 0)),
+            ("carol".to_string(), vote(1, 100, 6)),
+        ],
+    };
+    set_atom_wars(&mut deps, atom_wars.clone());
+
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin", &[]),
+        InstantiateMsg {
+            atom_wars_contract: ATOM_WARS.to_string(),
+            claim_window: CLAIM_WINDOW,
+            refund_window: REFUND_WINDOW,
+            treasury: None,
+        },
+    )
+    .unwrap();
+
+    // Tributes are added while round 0 is running
+    let mut env = mock_env();
+    env.block.time = genesis_time();
+    for proposal_id in [0, 1] {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(DEPOSITOR, &[Coin::new(900, "utribute")]),
+            ExecuteMsg::AddTribute {
+                tranche_id: 0,
+                round_id: 0,
+                proposal_id,
+                weighting: weighting.clone(),
+            },
+        )
+        .unwrap();
+    }
+
+    // Round 0 is over
+    set_atom_wars(
+        &mut deps,
+        MockAtomWars {
+            current_round_id: 1,
+            ..atom_wars
+        },
+    );
+
+    deps
+}
+
+fn claim(deps: &mut MockDeps, voter: &str, tribute_id: u64) -> Result<Uint128, String> {
+    let response = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(voter, &[]),
+        ExecuteMsg::ClaimTribute {
+            tranche_id: 0,
+            round_id: 0,
+            tribute_id,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(sent_amount(&response.messages[0].msg))
+}
+
+fn refund(
+    deps: &mut MockDeps,
+    sender: &str,
+    proposal_id: u64,
+    tribute_id: u64,
+) -> Result<Uint128, String> {
+    refund_at(deps, mock_env().block.time, sender, proposal_id, tribute_id)
+}
+
+fn refund_at(
+    deps: &mut MockDeps,
+    time: Timestamp,
+    sender: &str,
+    proposal_id: u64,
+    tribute_id: u64,
+) -> Result<Uint128, String> {
+    let mut env = mock_env();
+    env.block.time = time;
+    let response = execute(
+        deps.as_mut(),
+        env,
+        mock_info(sender, &[]),
+        ExecuteMsg::RefundTribute {
+            tranche_id: 0,
+            round_id: 0,
+            proposal_id,
+            tribute_id,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(sent_amount(&response.messages[0].msg))
+}
+
+fn sent_amount(msg: &CosmosMsg) -> Uint128 {
+    match msg {
+        CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount[0].amount,
+        _ => panic!("expected a bank send, got {:?}", msg),
+    }
+}
+
+#[test]
+fn add_tribute() {
+    let deps = setup(None);
+
+    let tributes: TributesResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ProposalTributes {
+                tranche_id: 0,
+                round_id: 0,
+                proposal_id: 0,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(tributes.tributes.len(), 1);
+    assert_eq!(tributes.tributes[0].tribute_id, 0);
+    assert_eq!(
+        tributes.tributes[0].tribute.depositor,
+        Addr::unchecked(DEPOSITOR)
+    );
+    assert_eq!(
+        tributes.tributes[0].tribute.funds,
+        Coin::new(900, "utribute")
+    );
+}
+
+#[test]
+fn add_tribute_validation() {
+    let mut deps = setup(None);
+    let add_tribute = |deps: &mut MockDeps, round_id: u64, funds: &[Coin]| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(DEPOSITOR, funds),
+            ExecuteMsg::AddTribute {
+                tranche_id: 0,
+                round_id,
+                proposal_id: 0,
+                weighting: None,
+            },
+        )
+        .unwrap_err()
+        .to_string()
+    };
+
+    // Round 0 has ended
+    assert!(
+        add_tribute(&mut deps, 0, &[Coin::new(1, "utribute")]).contains("not currently ongoing")
+    );
+    assert!(add_tribute(&mut deps, 1, &[]).contains("Must send funds"));
+    assert!(add_tribute(
+        &mut deps,
+        1,
+        &[Coin::new(1, "utribute"), Coin::new(1, "uother")]
+    )
+    .contains("exactly one coin"));
+}
+
+#[test]
+fn claim_tribute_by_voting_power() {
+    let mut deps = setup(None);
+
+    // Alice and Bob have 100 and 200 of the 300 power of proposal 0
+    assert_eq!(claim(&mut deps, "alice", 0).unwrap(), Uint128::new(300));
+    assert_eq!(claim(&mut deps, "bob", 0).unwrap(), Uint128::new(600));
+
+    assert!(claim(&mut deps, "alice", 0)
+        .unwrap_err()
+        .contains("already claimed"));
+    // Carol voted for proposal 1, which did not receive liquidity
+    assert!(claim(&mut deps, "carol", 1)
+        .unwrap_err()
+        .contains("did not win"));
+    assert!(claim(&mut deps, "dave", 0).is_err());
+}
+
+#[test]
+fn claim_tribute_by_lockup_tier() {
+    let mut deps = setup(Some(TributeWeighting::MinLockupTier { min_lockup_tier: 3 }));
+
+    // Only Alice's power is in tier 3 or above, so she gets all of the tribute
+    assert_eq!(claim(&mut deps, "alice", 0).unwrap(), Uint128::new(900));
+    assert!(claim(&mut deps, "bob", 0)
+        .unwrap_err()
+        .contains("no voting power eligible"));
+}
+
+#[test]
+fn claim_tribute_after_claim_window() {
+    let mut deps = setup(None);
+
+    let mut env = mock_env();
+    env.block.time = round(0).round_end.plus_nanos(CLAIM_WINDOW + 1);
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("alice", &[]),
+        ExecuteMsg::ClaimTribute {
+            tranche_id: 0,
+            round_id: 0,
+            tribute_id: 0,
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Claim window"));
+}
+
+#[test]
+fn refund_tribute() {
+    let mut deps = setup(None);
+
+    // Tribute 1 is on proposal 1, which did not receive liquidity
+    assert!(refund(&mut deps, "alice", 1, 1)
+        .unwrap_err()
+        .contains("not the depositor"));
+    assert_eq!(
+        refund(&mut deps, DEPOSITOR, 1, 1).unwrap(),
+        Uint128::new(900)
+    );
+    assert!(refund(&mut deps, DEPOSITOR, 1, 1)
+        .unwrap_err()
+        .contains("already refunded"));
+
+    // Tribute 0 is on proposal 0, which did
+    assert!(refund(&mut deps, DEPOSITOR, 0, 0)
+        .unwrap_err()
+        .contains("Proposal won"));
+}
+
+#[test]
+fn refund_tribute_after_refund_window() {
+    let mut deps = setup(None);
+
+    let refund_deadline = round(0).round_end.plus_nanos(REFUND_WINDOW);
+    assert!(
+        refund_at(&mut deps, refund_deadline.plus_nanos(1), DEPOSITOR, 1, 1)
+            .unwrap_err()
+            .contains("Refund window")
+    );
+    assert_eq!(
+        refund_at(&mut deps, refund_deadline, DEPOSITOR, 1, 1).unwrap(),
+        Uint128::new(900)
+    );
+}
+
+#[test]
+fn sweep_tributes() {
+    let mut deps = setup(None);
+    claim(&mut deps, "alice", 0).unwrap();
+
+    let sweep = |deps: &mut MockDeps, time: Timestamp| {
+        let mut env = mock_env();
+        env.block.time = time;
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info("anyone", &[]),
+            ExecuteMsg::SweepTributes {
+                tranche_id: 0,
+                round_id: 0,
+                limit: None,
+            },
+        )
+    };
+
+    let claim_deadline = round(0).round_end.plus_nanos(CLAIM_WINDOW);
+    assert!(sweep(&mut deps, claim_deadline).is_err());
+
+    // The tribute on the losing proposal can still be refunded until the refund window closes
+    let refund_deadline = round(0).round_end.plus_nanos(REFUND_WINDOW);
+    assert!(sweep(&mut deps, claim_deadline.plus_nanos(1)).is_err());
+    assert!(sweep(&mut deps, refund_deadline).is_err());
+
+    // The unclaimed remainder of the tribute on the winning proposal, and all of the unrefunded one on the losing
+    // proposal, go back to the depositor
+    let response = sweep(&mut deps, refund_deadline.plus_nanos(1)).unwrap();
+    assert_eq!(response.messages.len(), 2);
+    assert_eq!(sent_amount(&response.messages[0].msg), Uint128::new(600));
+    assert_eq!(sent_amount(&response.messages[1].msg), Uint128::new(900));
+    assert!(refund_at(&mut deps, refund_deadline.plus_nanos(1), DEPOSITOR, 1, 1).is_err());
+
+    // Sweeping again sends nothing
+    let response = sweep(&mut deps, refund_deadline.plus_nanos(1)).unwrap();
+    assert!(response.messages.is_empty());
+}
+
+fn query_tributes(deps: &MockDeps, msg: QueryMsg) -> Vec<(u64, u64)> {
+    let tributes: TributesResponse =
+        from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    tributes
+        .tributes
+        .iter()
+        .map(|tribute| (tribute.proposal_id, tribute.tribute_id))
+        .collect()
+}
+
+#[test]
+fn query_round_tributes() {
+    let deps = setup(None);
+    let round_tributes = |start_after: Option<(u64, u64)>| {
+        query_tributes(
+            &deps,
+            QueryMsg::RoundTributes {
+                tranche_id: 0,
+                round_id: 0,
+                start_after,
+                limit: Some(1),
+            },
+        )
+    };
+
+    assert_eq!(round_tributes(None), vec![(0, 0)]);
+    assert_eq!(round_tributes(Some((0, 0))), vec![(1, 1)]);
+    assert!(round_tributes(Some((1, 1))).is_empty());
+}
+
+#[test]
+fn query_claimable_tributes_and_claims() {
+    let mut deps = setup(None);
+    let claimable = |deps: &MockDeps, address: &str| {
+        let response: ClaimableTributesResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ClaimableTributes {
+                    address: address.to_string(),
+                    tranche_id: 0,
+                    start_round_id: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        response
+            .tributes
+            .iter()
+            .map(|tribute| (tribute.tribute_id, tribute.amount.amount.u128()))
+            .collect::<Vec<(u64, u128)>>()
+    };
+    let has_claimed = |deps: &MockDeps, address: &str| {
+        let response: HasClaimedResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::HasClaimed {
+                    address: address.to_string(),
+                    tribute_id: 0,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        response.claimed
+    };
+
+    // Carol voted for a proposal that did not receive liquidity
+    assert_eq!(claimable(&deps, "alice"), vec![(0, 300)]);
+    assert!(claimable(&deps, "carol").is_empty());
+    assert!(!has_claimed(&deps, "alice"));
+
+    claim(&mut deps, "alice", 0).unwrap();
+    assert!(claimable(&deps, "alice").is_empty());
+    assert!(has_claimed(&deps, "alice"));
+}
+
+#[test]
+fn query_refundable_tributes() {
+    let mut deps = setup(None);
+    let refundable = |deps: &MockDeps| {
+        query_tributes(
+            deps,
+            QueryMsg::RefundableTributes {
+                depositor: DEPOSITOR.to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+    };
+
+    // Only the tribute on the proposal that did not receive liquidity can be refunded
+    assert_eq!(refundable(&deps), vec![(1, 1)]);
+    refund(&mut deps, DEPOSITOR, 1, 1).unwrap();
+    assert!(refundable(&deps).is_empty());
+}